@@ -0,0 +1,3 @@
+ALTER TABLE IF EXISTS "game_play" DROP CONSTRAINT IF EXISTS "fk_game_play_game_base";
+DROP INDEX IF EXISTS "idx_game_play_game_id";
+DROP TABLE IF EXISTS "game_play";
@@ -0,0 +1,11 @@
+CREATE TABLE "game_play" (
+    "token" UUID PRIMARY KEY,
+    "game_id" UUID NOT NULL,
+    "created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX "idx_game_play_game_id" ON "game_play" ("game_id");
+
+ALTER TABLE "game_play"
+ADD CONSTRAINT "fk_game_play_game_base"
+FOREIGN KEY ("game_id") REFERENCES "game_base"("id") ON DELETE CASCADE;
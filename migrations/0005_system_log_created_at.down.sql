@@ -0,0 +1 @@
+DROP INDEX IF EXISTS "idx_system_log_created_at";
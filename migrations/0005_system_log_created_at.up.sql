@@ -0,0 +1 @@
+CREATE INDEX "idx_system_log_created_at" ON "system_log" ("created_at" DESC, "id" DESC);
@@ -0,0 +1 @@
+ALTER TABLE "game_base" ADD COLUMN "description" VARCHAR(500);
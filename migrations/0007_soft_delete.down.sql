@@ -0,0 +1,2 @@
+ALTER TABLE "game_base" DROP COLUMN IF EXISTS "deleted_at";
+ALTER TABLE "base_user" DROP COLUMN IF EXISTS "deleted_at";
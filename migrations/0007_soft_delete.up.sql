@@ -0,0 +1,2 @@
+ALTER TABLE "base_user" ADD COLUMN "deleted_at" TIMESTAMPTZ;
+ALTER TABLE "game_base" ADD COLUMN "deleted_at" TIMESTAMPTZ;
@@ -0,0 +1 @@
+DROP INDEX IF EXISTS "idx_game_base_name_trgm";
@@ -0,0 +1,3 @@
+CREATE EXTENSION IF NOT EXISTS "pg_trgm";
+
+CREATE INDEX "idx_game_base_name_trgm" ON "game_base" USING GIN ("name" gin_trgm_ops);
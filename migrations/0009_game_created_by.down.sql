@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS "idx_game_base_created_by";
+ALTER TABLE "game_base" DROP CONSTRAINT IF EXISTS "fk_game_base_created_by";
+ALTER TABLE "game_base" DROP COLUMN IF EXISTS "created_by";
@@ -0,0 +1,7 @@
+ALTER TABLE "game_base" ADD COLUMN "created_by" UUID;
+
+ALTER TABLE "game_base"
+ADD CONSTRAINT "fk_game_base_created_by"
+FOREIGN KEY ("created_by") REFERENCES "base_user"("id") ON DELETE SET NULL;
+
+CREATE INDEX "idx_game_base_created_by" ON "game_base" ("created_by");
@@ -0,0 +1,12 @@
+CREATE TABLE "client_popup" (
+    "id" SMALLINT PRIMARY KEY DEFAULT 1 CHECK ("id" = 1),
+    "heading" VARCHAR(200) NOT NULL,
+    "paragraph" TEXT NOT NULL,
+    "active" BOOLEAN NOT NULL DEFAULT FALSE,
+    "start_at" TIMESTAMPTZ,
+    "end_at" TIMESTAMPTZ,
+    "updated_at" TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO "client_popup" (id, heading, paragraph, active)
+VALUES (1, 'Velkommen', 'Takk for at du har lastet ned appen vår!', FALSE);
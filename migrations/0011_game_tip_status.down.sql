@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS "idx_game_tip_status_created_at";
+ALTER TABLE "game_tip" DROP COLUMN IF EXISTS "status";
+DROP TYPE IF EXISTS "tip_status";
@@ -0,0 +1,8 @@
+CREATE TYPE "tip_status" AS ENUM (
+    'new',
+    'reviewed',
+    'dismissed'
+);
+
+ALTER TABLE "game_tip" ADD COLUMN "status" tip_status NOT NULL DEFAULT 'new';
+CREATE INDEX "idx_game_tip_status_created_at" ON "game_tip" ("status", "created_at" DESC);
@@ -0,0 +1,5 @@
+CREATE TABLE "blocked_key_combo" (
+    "prefix" VARCHAR(100) NOT NULL,
+    "suffix" VARCHAR(100) NOT NULL,
+    PRIMARY KEY ("prefix", "suffix")
+);
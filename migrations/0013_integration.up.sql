@@ -0,0 +1,5 @@
+CREATE TABLE "integration" (
+    "subject" VARCHAR(100) PRIMARY KEY,
+    "name" integration_name NOT NULL,
+    "created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
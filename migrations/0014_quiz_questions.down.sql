@@ -0,0 +1 @@
+ALTER TABLE "quiz_game" DROP COLUMN IF EXISTS "questions";
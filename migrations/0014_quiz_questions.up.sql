@@ -0,0 +1,13 @@
+ALTER TABLE "quiz_game" ADD COLUMN "questions" JSONB NOT NULL DEFAULT '[]'::jsonb;
+
+UPDATE "quiz_game" quiz
+SET "questions" = (
+    SELECT COALESCE(
+        jsonb_agg(
+            jsonb_build_object('prompt', round.prompt, 'options', '[]'::jsonb, 'correct_index', 0)
+            ORDER BY round.position
+        ),
+        '[]'::jsonb
+    )
+    FROM unnest(quiz."rounds") WITH ORDINALITY AS round(prompt, position)
+);
@@ -0,0 +1,6 @@
+ALTER TABLE "game_play" DROP CONSTRAINT IF EXISTS "fk_game_play_game_base";
+
+ALTER TABLE "game_play"
+ADD CONSTRAINT "fk_game_play_game_base"
+FOREIGN KEY ("game_id") REFERENCES "game_base"("id") ON DELETE CASCADE
+DEFERRABLE INITIALLY DEFERRED;
@@ -0,0 +1 @@
+ALTER TABLE "game_base" DROP COLUMN IF EXISTS "deleted_at";
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS "idx_game_tip_submitted_by";
+ALTER TABLE "game_tip" DROP CONSTRAINT IF EXISTS "fk_game_tip_submitted_by";
+ALTER TABLE "game_tip" DROP COLUMN IF EXISTS "submitted_by";
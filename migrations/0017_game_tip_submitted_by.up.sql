@@ -0,0 +1,7 @@
+ALTER TABLE "game_tip" ADD COLUMN "submitted_by" UUID;
+
+ALTER TABLE "game_tip"
+ADD CONSTRAINT "fk_game_tip_submitted_by"
+FOREIGN KEY ("submitted_by") REFERENCES "base_user"("id") ON DELETE SET NULL;
+
+CREATE INDEX "idx_game_tip_submitted_by" ON "game_tip" ("submitted_by");
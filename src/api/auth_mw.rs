@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{str::FromStr, sync::Arc};
 
 use axum::{
     body::Body,
@@ -7,9 +7,9 @@ use axum::{
     middleware::Next,
     response::Response,
 };
-use jsonwebtoken::{Algorithm, DecodingKey, TokenData, Validation, decode, decode_header};
-use serde_json::json;
-use sqlx::{Pool, Postgres};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, TokenData, Validation, decode, decode_header, errors::ErrorKind,
+};
 use tracing::warn;
 
 use crate::{
@@ -17,13 +17,12 @@ use crate::{
     config::app_config::CONFIG,
     db::user::{ensure_pseudo_user, get_base_user_by_auth0_id},
     models::{
-        auth::{Claims, Jwks},
+        auth::{Claims, Jwk, Jwks},
         error::ServerError,
-        integration::{INTEGRATION_NAMES, IntegrationName},
-        system_log::{LogAction, LogCeverity},
+        system_log::{LogAction, LogCeverity, LogMetadata},
         user::SubjectId,
     },
-    service::util::{extract_header, to_uuid},
+    service::util::{extract_header, parse_guest_id},
 };
 
 static GUEST_AUTHORIZATION: &str = "X-Guest-Authentication";
@@ -36,14 +35,14 @@ pub async fn auth_mw(
     let pseudo_header = extract_header(GUEST_AUTHORIZATION, req.headers());
     let token_header = extract_header(AUTHORIZATION.as_str(), req.headers());
 
-    match (pseudo_header, token_header) {
-        (_, Some(token_header)) => {
+    match pick_credential(pseudo_header, token_header) {
+        Some(Credential::Token(token_header)) => {
             handle_token_header(state.clone(), &mut req, &token_header).await?;
         }
-        (Some(pseudo_header), None) => {
-            handle_pseudo_user(state.get_pool(), &mut req, &pseudo_header).await?;
+        Some(Credential::Guest(pseudo_header)) => {
+            handle_pseudo_user(&state, &mut req, &pseudo_header).await?;
         }
-        _ => {
+        None => {
             warn!("Unauthorized request - no valid authentication header provided");
             return Err(ServerError::AccessDenied);
         }
@@ -52,15 +51,65 @@ pub async fn auth_mw(
     Ok(next.run(req).await)
 }
 
+#[derive(Debug, PartialEq)]
+enum Credential {
+    Token(String),
+    Guest(String),
+}
+
+/// A bearer token always wins, so a signed in user whose client still sends
+/// the guest header keeps their permissions
+fn pick_credential(
+    pseudo_header: Option<String>,
+    token_header: Option<String>,
+) -> Option<Credential> {
+    match (pseudo_header, token_header) {
+        (Some(_), Some(token_header)) => {
+            warn!("Request carried both a bearer token and a guest header, using the token");
+            Some(Credential::Token(token_header))
+        }
+        (None, Some(token_header)) => Some(Credential::Token(token_header)),
+        (Some(pseudo_header), None) => Some(Credential::Guest(pseudo_header)),
+        (None, None) => None,
+    }
+}
+
 async fn handle_pseudo_user(
-    pool: &Pool<Postgres>,
+    state: &AppState,
     request: &mut Request<Body>,
     pseudo_header: &str,
 ) -> Result<(), ServerError> {
-    let pseudo_id = to_uuid(pseudo_header)?;
+    let pseudo_id = parse_guest_id(pseudo_header)?;
+    state.get_pseudo_activity().record(pseudo_id);
 
-    let pool_clone = pool.clone();
-    tokio::task::spawn(async move { ensure_pseudo_user(&pool_clone, pseudo_id).await });
+    // Clients fire several requests on cold start, only one of them needs to upsert.
+    // Ids seen within the last minute already have a row and a fresh enough last_active.
+    // Ids that keep falling out of the recently seen set are throttled, so a
+    // misbehaving client can not churn through upserts
+    if state
+        .get_recent_pseudo_ids()
+        .first_sighting(pseudo_id)
+        .await
+        && state
+            .get_pseudo_upsert_limiter()
+            .try_acquire(pseudo_id)
+            .is_ok()
+    {
+        let pool = state.get_pool().clone();
+        let in_flight = state.get_pseudo_in_flight().clone();
+        let spawned = state.get_tasks().try_spawn(async move {
+            in_flight
+                .run_exclusive(pseudo_id, ensure_pseudo_user(&pool, pseudo_id))
+                .await
+        });
+
+        if spawned.is_none() {
+            warn!(
+                "Background task limit reached, skipped ensuring pseudo user {}",
+                pseudo_id
+            );
+        }
+    }
     let subject = SubjectId::PseudoUser(pseudo_id);
 
     request.extensions_mut().insert(subject);
@@ -81,13 +130,12 @@ async fn handle_token_header(
         ));
     };
 
-    let token_data = verify_jwt(token, state.get_jwks()).await?;
+    let token_data = verify_jwt(token, &state).await?;
     let claims: Claims = serde_json::from_value(token_data.claims)?;
 
     let subject = match claims.is_machine() {
         true => {
-            let Some(int_name) = IntegrationName::from_subject(&claims.sub, &INTEGRATION_NAMES)
-            else {
+            let Some(int_name) = state.get_integrations().resolve(&claims.sub).await else {
                 warn!(
                     "Unknown integration subject attempted authentication: {}",
                     claims.sub
@@ -111,7 +159,7 @@ async fn handle_token_header(
                     .ceverity(LogCeverity::Critical)
                     .function("handle_base_user")
                     .description("Auth0 user authenticated but not found in database - sync issue")
-                    .metadata(json!({"auth0_id": claims.auth0_id()}))
+                    .metadata(LogMetadata::entity(claims.auth0_id()))
                     .log_async();
 
                 return Err(ServerError::Internal(
@@ -129,7 +177,82 @@ async fn handle_token_header(
     Ok(())
 }
 
-async fn verify_jwt(token: &str, jwks: &Jwks) -> Result<TokenData<serde_json::Value>, ServerError> {
+/// A kid we do not know usually means Auth0 rotated its keys, so the key set
+/// is re-fetched once before the token is rejected
+async fn verify_jwt(
+    token: &str,
+    state: &AppState,
+) -> Result<TokenData<serde_json::Value>, ServerError> {
+    let mut jwks = state.get_jwks();
+    let unknown_kid = decode_header(token)
+        .ok()
+        .and_then(|header| header.kid)
+        .is_some_and(|kid| !jwks.has_kid(&kid));
+
+    if unknown_kid && state.get_jwks_store().refresh().await {
+        jwks = state.get_jwks();
+    }
+
+    verify_jwt_with(
+        token,
+        &jwks,
+        &CONFIG.auth0.jwt_algorithms,
+        &CONFIG.auth0.audience,
+        &CONFIG.auth0.domain,
+        CONFIG.auth0.jwt_leeway_secs,
+    )
+}
+
+/// The algorithm comes from the JWK, never the token header alone, and must be
+/// an RSA or ECDSA algorithm on the allow-list that fits the key type. Accepting
+/// `none` or HMAC here would let a token signed with the public key pass as valid.
+fn jwk_algorithm(jwk: &Jwk, allowed: &[Algorithm]) -> Result<Algorithm, ServerError> {
+    let algorithm = match (jwk.alg.as_str(), jwk.kty.as_str(), jwk.crv.as_str()) {
+        ("", "EC", "P-384") => Algorithm::ES384,
+        ("", "EC", _) => Algorithm::ES256,
+        ("", _, _) => Algorithm::RS256,
+        (alg, _, _) => Algorithm::from_str(alg)
+            .map_err(|_| ServerError::JwtVerification(format!("Unknown JWK alg: {}", alg)))?,
+    };
+
+    let fits_key = match algorithm {
+        Algorithm::RS256
+        | Algorithm::RS384
+        | Algorithm::RS512
+        | Algorithm::PS256
+        | Algorithm::PS384
+        | Algorithm::PS512 => jwk.kty == "RSA",
+        Algorithm::ES256 | Algorithm::ES384 => jwk.kty == "EC",
+        _ => false,
+    };
+
+    if !fits_key || !allowed.contains(&algorithm) {
+        return Err(ServerError::JwtVerification(format!(
+            "JWT algorithm {:?} is not allowed for a {} key",
+            algorithm, jwk.kty
+        )));
+    }
+
+    Ok(algorithm)
+}
+
+fn decoding_key(jwk: &Jwk, algorithm: Algorithm) -> Result<DecodingKey, ServerError> {
+    let key = match algorithm {
+        Algorithm::ES256 | Algorithm::ES384 => DecodingKey::from_ec_components(&jwk.x, &jwk.y),
+        _ => DecodingKey::from_rsa_components(&jwk.n, &jwk.e),
+    };
+
+    key.map_err(|e| ServerError::JwtVerification(format!("Failed to get decoding key: {}", e)))
+}
+
+fn verify_jwt_with(
+    token: &str,
+    jwks: &Jwks,
+    allowed: &[Algorithm],
+    audience: &str,
+    issuer: &str,
+    leeway_secs: u64,
+) -> Result<TokenData<serde_json::Value>, ServerError> {
     let header = decode_header(token)
         .map_err(|e| ServerError::JwtVerification(format!("Failed to decode header: {}", e)))?;
 
@@ -137,19 +260,252 @@ async fn verify_jwt(token: &str, jwks: &Jwks) -> Result<TokenData<serde_json::Va
         .kid
         .ok_or_else(|| ServerError::JwtVerification("Missing JWT kid".into()))?;
 
-    let jwk = jwks
-        .keys
-        .iter()
-        .find(|jwk| jwk.kid == kid)
-        .ok_or_else(|| ServerError::JwtVerification("JWK is not well known".into()))?;
+    let jwk =
+        jwks.keys.iter().find(|jwk| jwk.kid == kid).ok_or_else(|| {
+            ServerError::JwtVerification(format!("JWK {} is not well known", kid))
+        })?;
+
+    let algorithm = jwk_algorithm(jwk, allowed)?;
+    if header.alg != algorithm {
+        return Err(ServerError::JwtVerification(format!(
+            "Token alg {:?} does not match JWK alg {:?}",
+            header.alg, algorithm
+        )));
+    }
+
+    let decoding_key = decoding_key(jwk, algorithm)?;
 
-    let decoding_key = DecodingKey::from_rsa_components(&jwk.n, &jwk.e)
-        .map_err(|e| ServerError::JwtVerification(format!("Failed to get decoding key: {}", e)))?;
+    let mut validation = Validation::new(algorithm);
+    validation.set_audience(&[audience]);
+    validation.set_issuer(&[issuer]);
+    validation.leeway = leeway_secs;
 
-    let mut validation = Validation::new(Algorithm::RS256);
-    validation.set_audience(&[&CONFIG.auth0.audience]);
-    validation.set_issuer(&[&CONFIG.auth0.domain]);
+    // Expiry is reported so clients know to refresh, everything else stays opaque
+    decode::<serde_json::Value>(token, &decoding_key, &validation).map_err(|e| match e.kind() {
+        ErrorKind::ExpiredSignature => {
+            ServerError::Api(StatusCode::UNAUTHORIZED, "Token expired".into())
+        }
+        _ => ServerError::JwtVerification(format!("Failed to validate token: {}", e)),
+    })
+}
 
-    decode::<serde_json::Value>(token, &decoding_key, &validation)
-        .map_err(|e| ServerError::JwtVerification(format!("Failed to validate token: {}", e)))
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use chrono::Utc;
+    use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+    use serde_json::json;
+
+    use crate::models::{
+        auth::{Jwk, Jwks},
+        error::ServerError,
+    };
+
+    use super::{Credential, pick_credential, verify_jwt_with};
+
+    const TEST_KEY: &str = include_str!("../tests/fixtures/jwt_test_key.pem");
+    const TEST_N: &str = "211AeaXUrOIuqw4gtRJ6HFYjmXCefK_onXcByg6k0Q71rDcEluCo1K2odR-78lkAzw6HsZgzEEdTDZ9BJC84EMGWXFm4h2udpmD7P_5HAXS-p2IhghqwQ2v_QQMyw6PV2m0hNvSw-NKvExseiaD-1yq40sB-uVWxM_TVQm2hCXabRGqZjFxVMd_2h2bcJDC0d-_eyf9j3bv8fsQ41pBfsMfJ-A9vTCxAWlw_B8fy8hzRpST5T15USN74l1XrjGZVCOD8KwN-d8SXxFlsgxCRoF9tH3CTIaJRMVbnrHsuX0PQ4Ps83Fm8nUMrMItBcpzsnEcc0zqoV9ZxED1XtdddhQ";
+    const TEST_EC_KEY: &str = include_str!("../tests/fixtures/jwt_test_ec_key.pem");
+    const TEST_EC_X: &str = "ctgCCOX6aZ8YFX5JVbSQZSEJ7kfcCUYc1Rp2sHJYuU0";
+    const TEST_EC_Y: &str = "ZPTSdZizLJWazYrzaFpV9zn9On0jiWTF7aw2ROudslo";
+    const AUDIENCE: &str = "https://api.tero.com";
+    const ISSUER: &str = "https://tero.test/";
+    const LEEWAY: u64 = 30;
+
+    fn jwks(alg: &str) -> Jwks {
+        let jwk = |kid: &str| Jwk {
+            kid: kid.into(),
+            n: TEST_N.into(),
+            e: "AQAB".into(),
+            crv: String::new(),
+            x: String::new(),
+            y: String::new(),
+            kty: "RSA".into(),
+            alg: alg.into(),
+            use_: "sig".into(),
+        };
+
+        Jwks {
+            keys: vec![jwk("test"), jwk("other")],
+        }
+    }
+
+    fn ec_jwks(alg: &str) -> Jwks {
+        Jwks {
+            keys: vec![Jwk {
+                kid: "test".into(),
+                n: String::new(),
+                e: String::new(),
+                crv: "P-256".into(),
+                x: TEST_EC_X.into(),
+                y: TEST_EC_Y.into(),
+                kty: "EC".into(),
+                alg: alg.into(),
+                use_: "sig".into(),
+            }],
+        }
+    }
+
+    fn sign_ec() -> String {
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some("test".into());
+        let claims = json!({
+            "aud": AUDIENCE,
+            "iss": ISSUER,
+            "sub": "auth0|user",
+            "exp": Utc::now().timestamp() + 60,
+        });
+
+        let key = EncodingKey::from_ec_pem(TEST_EC_KEY.as_bytes()).unwrap();
+        encode(&header, &claims, &key).unwrap()
+    }
+
+    fn sign(algorithm: Algorithm) -> String {
+        sign_expiring(algorithm, Utc::now().timestamp() + 60)
+    }
+
+    fn sign_expiring(algorithm: Algorithm, exp: i64) -> String {
+        let mut header = Header::new(algorithm);
+        header.kid = Some("test".into());
+        let claims = json!({
+            "aud": AUDIENCE,
+            "iss": ISSUER,
+            "sub": "auth0|user",
+            "exp": exp,
+        });
+
+        let key = EncodingKey::from_rsa_pem(TEST_KEY.as_bytes()).unwrap();
+        encode(&header, &claims, &key).unwrap()
+    }
+
+    #[test]
+    fn token_is_preferred_over_guest_header() {
+        let token = || Some(String::from("Bearer token"));
+        let guest = || Some(String::from("guest-id"));
+
+        assert_eq!(
+            pick_credential(None, token()),
+            Some(Credential::Token("Bearer token".into()))
+        );
+        assert_eq!(
+            pick_credential(guest(), None),
+            Some(Credential::Guest("guest-id".into()))
+        );
+        assert_eq!(
+            pick_credential(guest(), token()),
+            Some(Credential::Token("Bearer token".into()))
+        );
+        assert_eq!(pick_credential(None, None), None);
+    }
+
+    #[test]
+    fn verifies_rs384_when_configured() {
+        let token = sign(Algorithm::RS384);
+        let allowed = [Algorithm::RS256, Algorithm::RS384];
+
+        let data =
+            verify_jwt_with(&token, &jwks("RS384"), &allowed, AUDIENCE, ISSUER, LEEWAY).unwrap();
+        assert_eq!(data.claims["sub"], "auth0|user");
+    }
+
+    #[test]
+    fn rejects_algorithm_outside_allow_list() {
+        let token = sign(Algorithm::RS384);
+        let allowed = [Algorithm::RS256];
+
+        assert!(
+            verify_jwt_with(&token, &jwks("RS384"), &allowed, AUDIENCE, ISSUER, LEEWAY).is_err()
+        );
+    }
+
+    #[test]
+    fn rejects_hmac_even_when_allowed() {
+        let token = sign(Algorithm::RS256);
+        let allowed = [Algorithm::HS256];
+
+        assert!(
+            verify_jwt_with(&token, &jwks("HS256"), &allowed, AUDIENCE, ISSUER, LEEWAY).is_err()
+        );
+    }
+
+    #[test]
+    fn verifies_es256_against_ec_jwk() {
+        let allowed = [Algorithm::RS256, Algorithm::ES256];
+
+        for alg in ["ES256", ""] {
+            let data = verify_jwt_with(
+                &sign_ec(),
+                &ec_jwks(alg),
+                &allowed,
+                AUDIENCE,
+                ISSUER,
+                LEEWAY,
+            )
+            .unwrap();
+            assert_eq!(data.claims["sub"], "auth0|user");
+        }
+    }
+
+    #[test]
+    fn rejects_token_alg_that_differs_from_jwk() {
+        let allowed = [Algorithm::RS256, Algorithm::ES256];
+
+        // An RSA token presented under an EC key, and the other way around
+        assert!(
+            verify_jwt_with(
+                &sign(Algorithm::RS256),
+                &ec_jwks("ES256"),
+                &allowed,
+                AUDIENCE,
+                ISSUER,
+                LEEWAY
+            )
+            .is_err()
+        );
+        assert!(
+            verify_jwt_with(
+                &sign_ec(),
+                &jwks("RS256"),
+                &allowed,
+                AUDIENCE,
+                ISSUER,
+                LEEWAY
+            )
+            .is_err()
+        );
+        // An EC key mislabelled as RSA is not trusted either
+        assert!(
+            verify_jwt_with(
+                &sign_ec(),
+                &ec_jwks("RS256"),
+                &allowed,
+                AUDIENCE,
+                ISSUER,
+                LEEWAY
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn expiry_within_leeway_is_accepted() {
+        let token = sign_expiring(Algorithm::RS256, Utc::now().timestamp() - 10);
+        let allowed = [Algorithm::RS256];
+
+        assert!(
+            verify_jwt_with(&token, &jwks("RS256"), &allowed, AUDIENCE, ISSUER, LEEWAY).is_ok()
+        );
+    }
+
+    #[test]
+    fn expired_token_is_reported_as_expired() {
+        let token = sign_expiring(Algorithm::RS256, Utc::now().timestamp() - 120);
+        let allowed = [Algorithm::RS256];
+
+        let result = verify_jwt_with(&token, &jwks("RS256"), &allowed, AUDIENCE, ISSUER, LEEWAY);
+        assert!(matches!(
+            result,
+            Err(ServerError::Api(StatusCode::UNAUTHORIZED, ref msg)) if msg == "Token expired"
+        ));
+    }
 }
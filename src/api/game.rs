@@ -3,17 +3,27 @@ use std::sync::Arc;
 use axum::{
     Extension, Json, Router,
     extract::{Path, Query, State},
-    response::IntoResponse,
-    routing::{delete, get, patch, post},
+    response::{IntoResponse, Response},
+    routing::{delete, get, patch, post, put},
 };
 
 use crate::{
+    api::validation::ValidatedJson,
     app_state::AppState,
+    config::app_config::CONFIG,
     db::{
-        game_base::{get_random_rounds, increment_times_played},
+        game_base::{
+            get_game_base, get_random_game_base, get_random_rounds, import_games,
+            increment_times_played, patch_game_base, record_game_play, search_game_page,
+        },
         imposter_game::get_imposter_game_by_id,
     },
-    models::game_base::{CreateStaticGameRequest, GamePagedRequest},
+    models::game_base::{
+        BulkSaveRequest, CreateStaticGameRequest, GameImport, GamePagedRequest, PersistGameQuery,
+        PlayerCountRequest, RandomGameQuery, SavedGameContent, SavedStatusRequest,
+        SearchGamesRequest, UpdateGameRequest,
+    },
+    service::{player_count::ensure_joinable, util::parse_game_key},
 };
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
@@ -23,29 +33,45 @@ use uuid::Uuid;
 use tracing::{debug, error, info, warn};
 
 use crate::{
-    api::gs_client::{InteractiveGameResponse, JoinGameResponse},
+    api::{
+        gs_client::{InteractiveGameResponse, JoinGameResponse},
+        require::{Require, policy},
+        subject::{AnyUser, Integration, RegisteredUser, allowed_integrations},
+    },
     db::{
         game_base::{
-            create_game_base, delete_saved_game, get_game_page, get_saved_games_page, save_game,
+            apply_saved_games, create_game_base, delete_game, delete_saved_game,
+            get_created_games_page, get_game_page, get_saved_games_page, get_saved_status,
+            is_game_saved, save_game, set_game_creator,
         },
         imposter_game::create_imposter_game,
         quiz_game::{create_quiz_game, get_quiz_game_by_id},
         spin_game::{create_spin_game, get_spin_game_by_id},
     },
     models::{
-        auth::Claims,
-        error::ServerError,
+        error::{OptionalExt, ServerError, SqlxResultExt},
         game_base::{
-            GameBase, GameCacheKey, GameType, InitiateGameRequest, InteractiveGameEnvelope,
-            JsonConverter, ResponseWrapper,
+            GameBase, GameCacheKey, GameKeyValidation, GameType, InitiateGameRequest,
+            InteractiveGameEnvelope, JsonConverter, ResponseWrapper,
         },
         imposter_game::ImposterSession,
-        quiz_game::QuizSession,
-        spin_game::SpinSession,
-        user::{Permission, SubjectId},
+        integration::IntegrationName,
+        quiz_game::{QuizQuestion, QuizSession, open_questions},
+        spin_game::{SpinGame, SpinSession},
+        system_log::{LogAction, LogCeverity},
+        user::SubjectId,
     },
 };
 
+const MAX_IMPORT_BATCH: usize = 500;
+
+fn persisted_response(status: StatusCode, game: Option<GameBase>) -> Response {
+    match game {
+        Some(game) => (status, Json(game)).into_response(),
+        None => status.into_response(),
+    }
+}
+
 async fn _get_random_name(client: &reqwest::Client) -> String {
     let mut rng = ChaCha8Rng::from_os_rng();
     let len = rng.random_range(4..=8);
@@ -87,10 +113,21 @@ async fn _get_random_name(client: &reqwest::Client) -> String {
 pub fn game_routes(state: Arc<AppState>) -> Router {
     let general_routes = Router::new()
         .route("/page", get(get_games))
+        .route("/random/{game_type}", get(get_random_game))
+        .route("/search", post(search_games))
+        .route("/import-batch", post(import_game_batch))
         .route("/free-key/{game_key}", patch(free_game_key))
         .route("/save/{game_id}", post(user_save_game))
         .route("/unsave/{game_id}", delete(user_usaved_game))
         .route("/saved", get(get_saved_games))
+        .route("/saved/bulk", post(bulk_save_games))
+        .route("/saved/status", post(get_saved_games_status))
+        .route("/saved/{game_id}/content", get(get_saved_game_content))
+        .route("/mine", get(get_my_games))
+        .route(
+            "/{game_type}/{game_id}",
+            patch(patch_game).delete(delete_game_admin),
+        )
         .with_state(state.clone());
 
     let static_routes = Router::new()
@@ -113,6 +150,8 @@ pub fn game_routes(state: Arc<AppState>) -> Router {
             post(initiate_random_interactive_session),
         )
         .route("/join/{game_id}", post(join_interactive_game))
+        .route("/validate/{key_word}", get(validate_game_key))
+        .route("/players/{game_id}", put(report_player_count))
         .route("/{game_type}/create", post(create_game_session))
         .with_state(state.clone());
 
@@ -124,24 +163,15 @@ pub fn game_routes(state: Arc<AppState>) -> Router {
 
 async fn join_interactive_game(
     State(state): State<Arc<AppState>>,
-    Extension(subject_id): Extension<SubjectId>,
+    _user: AnyUser,
     Path(key_word): Path<String>,
 ) -> Result<impl IntoResponse, ServerError> {
-    if let SubjectId::Integration(id) = subject_id {
-        warn!("Integration {} attempted to access user-only endpoint", id);
-        return Err(ServerError::AccessDenied);
-    }
-
-    let words: Vec<&str> = key_word.trim().split(" ").collect();
-    let tuple = match (words.first(), words.get(1)) {
-        (Some(p), Some(s)) => (p.to_string(), s.to_string()),
-        _ => {
-            warn!("Key word in invalid format");
-            return Err(ServerError::Api(
-                StatusCode::NOT_FOUND,
-                "Game with game key does not exist".into(),
-            ));
-        }
+    let Ok(tuple) = parse_game_key(&key_word) else {
+        warn!("Key word in invalid format");
+        return Err(ServerError::Api(
+            StatusCode::NOT_FOUND,
+            "Game with game key does not exist".into(),
+        ));
     };
 
     let Some((game_type, is_draft, game_id)) = state.get_vault().key_active(&tuple) else {
@@ -151,9 +181,16 @@ async fn join_interactive_game(
         ));
     };
 
+    ensure_joinable(
+        &CONFIG.server.enabled_game_types,
+        state.get_player_counts(),
+        game_type,
+        game_id,
+    )?;
+
     let response = JoinGameResponse {
         game_key: key_word,
-        hub_name: game_type.as_str().to_string(),
+        hub_name: game_type.hub_name().to_string(),
         game_id,
         game_type,
         is_draft,
@@ -162,16 +199,35 @@ async fn join_interactive_game(
     Ok((StatusCode::OK, Json(response)))
 }
 
-async fn create_game_session(
+/// Cheap enough to call on every keystroke, only the vault is consulted
+async fn validate_game_key(
     State(state): State<Arc<AppState>>,
-    Extension(subject_id): Extension<SubjectId>,
-    Path(game_type): Path<GameType>,
+    AnyUser(user_id): AnyUser,
+    Path(key_word): Path<String>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let user_id = match subject_id {
-        SubjectId::PseudoUser(id) | SubjectId::BaseUser(id) => id,
-        _ => return Err(ServerError::AccessDenied),
+    state
+        .get_key_check_limiter()
+        .try_acquire(user_id)
+        .map_err(ServerError::RateLimited)?;
+
+    let game_type = parse_game_key(&key_word)
+        .ok()
+        .and_then(|key| state.get_vault().key_active(&key))
+        .map(|(game_type, _, _)| game_type);
+
+    let validation = GameKeyValidation {
+        valid: game_type.is_some(),
+        game_type,
     };
 
+    Ok((StatusCode::OK, Json(validation)))
+}
+
+async fn create_game_session(
+    State(state): State<Arc<AppState>>,
+    AnyUser(user_id): AnyUser,
+    Path(game_type): Path<GameType>,
+) -> Result<impl IntoResponse, ServerError> {
     let game_id = Uuid::new_v4();
     let value = match game_type {
         GameType::Roulette => SpinSession::new_roulette(user_id, game_id).to_json()?,
@@ -205,35 +261,43 @@ async fn create_game_session(
     Ok((StatusCode::CREATED, Json(response)))
 }
 
+/// Standalone play, the session lives on the client so no key is reserved
 async fn initiate_static_game(
     State(state): State<Arc<AppState>>,
-    Extension(subject_id): Extension<SubjectId>,
+    AnyUser(user_id): AnyUser,
     Path((game_type, game_id)): Path<(GameType, Uuid)>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let user_id = match subject_id {
-        SubjectId::BaseUser(id) | SubjectId::PseudoUser(id) => id,
-        _ => {
-            warn!("Integration tried accessing user endpoint");
-            return Err(ServerError::AccessDenied);
-        }
-    };
+    if !game_type.supports_standalone() {
+        return Err(unsupported_game_type(game_type, "standalone"));
+    }
 
     let wrapper = match game_type {
         GameType::Quiz => {
-            let game = get_quiz_game_by_id(state.get_pool(), game_id).await?;
+            let game = get_quiz_game_by_id(state.get_pool(), game_id)
+                .await
+                .not_found(format!("Game {} not found", game_id))?;
             let session = QuizSession::from_game(game);
             ResponseWrapper::Quiz(session)
         }
         GameType::Imposter => {
-            let game = get_imposter_game_by_id(state.get_pool(), game_id).await?;
+            let game = get_imposter_game_by_id(state.get_pool(), game_id)
+                .await
+                .not_found(format!("Game {} not found", game_id))?;
             let session = ImposterSession::from_game(user_id, game);
             ResponseWrapper::Imposter(session)
         }
-        _ => {
-            return Err(ServerError::Api(
-                StatusCode::BAD_REQUEST,
-                "This game does not have static support".into(),
-            ));
+        // Played on a single device, so the requesting user hosts
+        GameType::Roulette => {
+            let game = get_spin_game_by_id(state.get_pool(), game_id)
+                .await
+                .not_found(format!("Game {} not found", game_id))?;
+            ResponseWrapper::Spin(SpinSession::from_roulette(user_id, game))
+        }
+        GameType::Duel => {
+            let game = get_spin_game_by_id(state.get_pool(), game_id)
+                .await
+                .not_found(format!("Game {} not found", game_id))?;
+            ResponseWrapper::Spin(SpinSession::from_duel(user_id, game))
         }
     };
 
@@ -242,34 +306,58 @@ async fn initiate_static_game(
     Ok((StatusCode::OK, Json(wrapper)))
 }
 
+fn unsupported_game_type(game_type: GameType, mode: &str) -> ServerError {
+    ServerError::Api(
+        StatusCode::BAD_REQUEST,
+        format!(
+            "Game type {} does not have {} support",
+            game_type.as_str(),
+            mode
+        ),
+    )
+}
+
+/// Plain rounds are stored as open questions, so older clients can keep sending them
+fn quiz_questions(payload: &CreateStaticGameRequest) -> Result<Vec<QuizQuestion>, ServerError> {
+    if payload.questions.is_empty() {
+        return Ok(open_questions(&payload.rounds));
+    }
+
+    if let Some(position) = payload.questions.iter().position(|q| !q.is_valid()) {
+        return Err(ServerError::Api(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Question {} has a correct_index outside its options",
+                position + 1
+            ),
+        ));
+    }
+
+    Ok(payload.questions.clone())
+}
+
+/// Standalone play, the session lives on the client so no key is reserved
 async fn initiate_random_static_game(
     State(state): State<Arc<AppState>>,
-    Extension(subject_id): Extension<SubjectId>,
+    AnyUser(user_id): AnyUser,
     Path(game_type): Path<GameType>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let user_id = match subject_id {
-        SubjectId::BaseUser(id) | SubjectId::PseudoUser(id) => id,
-        _ => {
-            warn!("Integration tried accessing user endpoint");
-            return Err(ServerError::AccessDenied);
-        }
-    };
+    if !game_type.supports_standalone() {
+        return Err(unsupported_game_type(game_type, "standalone"));
+    }
 
     let game_id = Uuid::new_v4();
+    let rounds = get_random_rounds(state.get_pool(), game_type, 20).await?;
     let wrapper = match game_type {
-        GameType::Quiz => {
-            let rounds = get_random_rounds(state.get_pool(), game_type, 20).await?;
-            ResponseWrapper::Quiz(QuizSession::from_rounds(game_id, rounds))
-        }
+        GameType::Quiz => ResponseWrapper::Quiz(QuizSession::from_rounds(game_id, rounds)),
         GameType::Imposter => {
-            let rounds = get_random_rounds(state.get_pool(), game_type, 20).await?;
             ResponseWrapper::Imposter(ImposterSession::from_rounds(user_id, game_id, rounds))
         }
-        _ => {
-            return Err(ServerError::Api(
-                StatusCode::BAD_REQUEST,
-                "This game does not have static support".into(),
-            ));
+        GameType::Roulette => {
+            ResponseWrapper::Spin(SpinSession::from_roulette_rounds(user_id, game_id, rounds))
+        }
+        GameType::Duel => {
+            ResponseWrapper::Spin(SpinSession::from_duel_rounds(user_id, game_id, rounds))
         }
     };
 
@@ -278,13 +366,12 @@ async fn initiate_random_static_game(
 
 async fn initiate_interactive_game(
     State(state): State<Arc<AppState>>,
-    Extension(subject_id): Extension<SubjectId>,
+    AnyUser(user_id): AnyUser,
     Path((game_type, game_id)): Path<(GameType, Uuid)>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let user_id = match subject_id {
-        SubjectId::PseudoUser(id) | SubjectId::BaseUser(id) => id,
-        _ => return Err(ServerError::AccessDenied),
-    };
+    if !game_type.supports_session() {
+        return Err(unsupported_game_type(game_type, "session"));
+    }
 
     let gs_client = state.get_gs_client();
     let vault = state.get_vault();
@@ -292,23 +379,22 @@ async fn initiate_interactive_game(
 
     let (value, game_id) = match game_type {
         GameType::Roulette => {
-            let game = get_spin_game_by_id(pool, game_id).await?;
+            let game = get_spin_game_by_id(pool, game_id)
+                .await
+                .not_found(format!("Game {} not found", game_id))?;
             let game_id = game.id;
             let session = SpinSession::from_roulette(user_id, game);
             (session.to_json()?, game_id)
         }
         GameType::Duel => {
-            let game = get_spin_game_by_id(pool, game_id).await?;
+            let game = get_spin_game_by_id(pool, game_id)
+                .await
+                .not_found(format!("Game {} not found", game_id))?;
             let game_id = game.id;
             let session = SpinSession::from_duel(user_id, game);
             (session.to_json()?, game_id)
         }
-        _ => {
-            return Err(ServerError::Api(
-                StatusCode::BAD_REQUEST,
-                "This game does not have session support".into(),
-            ));
-        }
+        _ => return Err(unsupported_game_type(game_type, "session")),
     };
 
     let key = vault.create_key(pool, game_type, false, game_id)?;
@@ -337,14 +423,9 @@ async fn initiate_interactive_game(
 
 async fn initiate_random_interactive_session(
     State(state): State<Arc<AppState>>,
-    Extension(subject_id): Extension<SubjectId>,
+    AnyUser(user_id): AnyUser,
     Path(game_type): Path<GameType>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let user_id = match subject_id {
-        SubjectId::PseudoUser(id) | SubjectId::BaseUser(id) => id,
-        _ => return Err(ServerError::AccessDenied),
-    };
-
     let game_id = Uuid::new_v4();
     let rounds = get_random_rounds(state.get_pool(), game_type, 20).await?;
 
@@ -391,14 +472,9 @@ async fn initiate_random_interactive_session(
 
 async fn get_games(
     State(state): State<Arc<AppState>>,
-    Extension(subject_id): Extension<SubjectId>,
+    _user: AnyUser,
     Query(request): Query<GamePagedRequest>,
 ) -> Result<impl IntoResponse, ServerError> {
-    if let SubjectId::Integration(_) = subject_id {
-        warn!("Integration attempted to access game listing endpoint");
-        return Err(ServerError::AccessDenied);
-    }
-
     let cache = state.get_cache();
     let cache_key = GameCacheKey::from_request(&request);
     let pool = state.get_pool().clone();
@@ -413,41 +489,193 @@ async fn get_games(
     Ok((StatusCode::OK, Json(page)))
 }
 
+async fn search_games(
+    State(state): State<Arc<AppState>>,
+    _user: AnyUser,
+    ValidatedJson(request): ValidatedJson<SearchGamesRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    let page = search_game_page(state.get_pool(), &request).await?;
+    Ok((StatusCode::OK, Json(page)))
+}
+
+async fn import_game_batch(
+    State(state): State<Arc<AppState>>,
+    _user: RegisteredUser,
+    _: Require<policy::ImportGames>,
+    Json(imports): Json<Vec<GameImport>>,
+) -> Result<impl IntoResponse, ServerError> {
+    if imports.len() > MAX_IMPORT_BATCH {
+        return Err(ServerError::Api(
+            StatusCode::BAD_REQUEST,
+            format!("At most {} games can be imported at once", MAX_IMPORT_BATCH),
+        ));
+    }
+
+    let results = import_games(state.get_pool(), &imports).await?;
+
+    for (result, import) in results.iter().zip(&imports) {
+        if let Some(game_id) = result.game_id {
+            state.fill_rounds_pool(game_id, import.game_type).await;
+        }
+    }
+
+    // Imported games can land on any cached page
+    state.get_cache().clear();
+
+    info!(
+        "Imported {} of {} game(s)",
+        results.iter().filter(|r| r.game_id.is_some()).count(),
+        imports.len()
+    );
+    Ok((StatusCode::OK, Json(results)))
+}
+
+async fn delete_game_admin(
+    State(state): State<Arc<AppState>>,
+    RegisteredUser(user_id): RegisteredUser,
+    _: Require<policy::DeleteGame>,
+    Path((game_type, game_id)): Path<(GameType, Uuid)>,
+) -> Result<impl IntoResponse, ServerError> {
+    let Some(game) = get_game_base(state.get_pool(), game_id).await? else {
+        return Err(ServerError::NotFound(format!(
+            "Game {} does not exist",
+            game_id
+        )));
+    };
+
+    let result = delete_game(state.get_pool(), game_type, game_id).await?;
+    if !result.game_deleted {
+        return Err(ServerError::NotFound(format!(
+            "No {} game with id {}",
+            game_type.as_str(),
+            game_id
+        )));
+    }
+
+    state
+        .get_cache()
+        .invalidate(game_type, &game.category)
+        .await?;
+
+    info!("User {} deleted game {}", user_id, game_id);
+    Ok((StatusCode::OK, Json(result)))
+}
+
+async fn patch_game(
+    State(state): State<Arc<AppState>>,
+    RegisteredUser(user_id): RegisteredUser,
+    _: Require<policy::PatchGame>,
+    Path((game_type, game_id)): Path<(GameType, Uuid)>,
+    ValidatedJson(request): ValidatedJson<UpdateGameRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    if request == UpdateGameRequest::default() {
+        return Err(ServerError::Api(
+            StatusCode::BAD_REQUEST,
+            "No fields to update".into(),
+        ));
+    }
+
+    let Some(previous) = get_game_base(state.get_pool(), game_id).await? else {
+        return Err(ServerError::NotFound(format!(
+            "Game {} does not exist",
+            game_id
+        )));
+    };
+
+    let game = patch_game_base(state.get_pool(), game_type, game_id, request)
+        .await?
+        .not_found(format!("Game {} does not exist", game_id))?;
+
+    let cache = state.get_cache();
+    cache.invalidate(game_type, &previous.category).await?;
+    if game.category != previous.category {
+        cache.invalidate(game_type, &game.category).await?;
+    }
+
+    info!("User {} patched game {}", user_id, game_id);
+    Ok((StatusCode::OK, Json(game)))
+}
+
+async fn get_random_game(
+    State(state): State<Arc<AppState>>,
+    Extension(subject_id): Extension<SubjectId>,
+    Path(game_type): Path<GameType>,
+    Query(query): Query<RandomGameQuery>,
+) -> Result<impl IntoResponse, ServerError> {
+    let exclude_saved_by = match subject_id {
+        SubjectId::BaseUser(id) => Some(id),
+        SubjectId::PseudoUser(_) => None,
+        SubjectId::Integration(_) => {
+            warn!("Integration attempted to access random game endpoint");
+            return Err(ServerError::AccessDenied);
+        }
+    };
+
+    let game = get_random_game_base(
+        state.get_pool(),
+        game_type,
+        query.category,
+        exclude_saved_by,
+    )
+    .await?
+    .not_found("No game matches the filters")?;
+
+    Ok((StatusCode::OK, Json(game)))
+}
+
 /// Called by user
 pub async fn persist_static_game(
     State(state): State<Arc<AppState>>,
-    Extension(subject_id): Extension<SubjectId>,
+    _user: AnyUser,
     Path(game_type): Path<GameType>,
+    Query(query): Query<PersistGameQuery>,
     Json(payload): Json<CreateStaticGameRequest>,
 ) -> Result<impl IntoResponse, ServerError> {
-    if let SubjectId::Integration(id) = subject_id {
-        warn!("Integration {} attempted to store a static game", id);
-        return Err(ServerError::AccessDenied);
+    if !game_type.supports_standalone() {
+        return Err(unsupported_game_type(game_type, "standalone"));
     }
 
-    let game_base = GameBase::new(
-        Uuid::new_v4(),
-        payload.name,
-        game_type,
-        payload.category.clone(),
-        payload.rounds.len() as i32,
-    );
+    let questions = match game_type {
+        GameType::Quiz => quiz_questions(&payload)?,
+        _ => vec![],
+    };
+    let iterations = match game_type {
+        GameType::Quiz => questions.len(),
+        _ => payload.rounds.len(),
+    };
+
+    let game_base = GameBase {
+        description: payload.description,
+        ..GameBase::new(
+            Uuid::new_v4(),
+            payload.name,
+            game_type,
+            payload.category.clone(),
+            iterations as i32,
+        )
+    };
 
     let mut tx = state.get_pool().begin().await?;
     create_game_base(tx.as_mut(), &game_base).await?;
 
     match game_type {
-        GameType::Quiz => create_quiz_game(tx.as_mut(), game_base.id, &payload.rounds).await?,
+        GameType::Quiz => create_quiz_game(tx.as_mut(), game_base.id, &questions).await?,
         GameType::Imposter => {
             create_imposter_game(tx.as_mut(), game_base.id, &payload.rounds).await?
         }
-        _ => {
-            return Err(ServerError::Api(
-                StatusCode::BAD_REQUEST,
-                format!("Game type {} not supported", game_type.as_str()),
-            ));
+        GameType::Roulette | GameType::Duel => {
+            let game = SpinGame {
+                id: game_base.id,
+                rounds: payload.rounds,
+            };
+            create_spin_game(tx.as_mut(), &game).await?
         }
     };
+
+    let stored = match query.return_game {
+        true => get_game_base(tx.as_mut(), game_base.id).await?,
+        false => None,
+    };
     tx.commit().await?;
 
     state.fill_rounds_pool(game_base.id, game_type).await;
@@ -457,31 +685,94 @@ pub async fn persist_static_game(
         .await?;
 
     info!("Persisted standalone game");
-    Ok(StatusCode::CREATED)
+    Ok(persisted_response(StatusCode::CREATED, stored))
 }
 
-/// Only called by `tero.session`.
+/// Every interactive persist must come under a key the vault handed out for the
+/// same game type, a missing key is treated like one that is not held
+fn ensure_session_key(
+    state: &AppState,
+    subject_id: &SubjectId,
+    game_type: GameType,
+    game_key: Option<&str>,
+) -> Result<(), ServerError> {
+    let error_msg = match game_key {
+        Some(game_key) => {
+            let key = parse_game_key(game_key)?;
+            if state.get_vault().holds_key(&key, game_type) {
+                return Ok(());
+            }
+
+            format!(
+                "Session service persisted a {} game under key '{}' it does not hold",
+                game_type.as_str(),
+                game_key
+            )
+        }
+        None => format!(
+            "Session service persisted a {} game without a game key",
+            game_type.as_str()
+        ),
+    };
+
+    error!("{}", error_msg);
+    state
+        .syslog()
+        .subject(subject_id.clone())
+        .action(LogAction::Create)
+        .ceverity(LogCeverity::Critical)
+        .function("persist_interactive_game")
+        .description(&error_msg)
+        .log_async();
+
+    Err(ServerError::Api(
+        StatusCode::CONFLICT,
+        "Game key is not active for this game type".into(),
+    ))
+}
+
+/// Only called by `tero.session`. Every persist counts as one play of the game,
+/// a `play_token` only makes a retried persist count once.
 async fn persist_interactive_game(
     State(state): State<Arc<AppState>>,
     Extension(subject_id): Extension<SubjectId>,
-    Extension(claims): Extension<Claims>,
+    _: Require<policy::PersistInteractiveGame>,
     Path(game_type): Path<GameType>,
+    Query(query): Query<PersistGameQuery>,
     Json(payload): Json<InteractiveGameEnvelope>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let SubjectId::Integration(_) = subject_id else {
-        warn!("User attempted to persist game session");
-        return Err(ServerError::AccessDenied);
+    allowed_integrations(
+        &state,
+        &subject_id,
+        &[IntegrationName::Session],
+        "persist_interactive_game",
+    )?;
+
+    let game_id: Uuid = serde_json::from_value(payload.payload["game_id"].clone())?;
+    let mut tx = state.get_pool().begin().await?;
+
+    // Claimed first, so a concurrent retry blocks on the token and then backs off
+    let replayed = match payload.play_token {
+        Some(token) => !record_game_play(&mut tx, token, game_id).await?,
+        None => false,
     };
 
-    if let Some(missing) = claims.missing_permission([Permission::WriteGame]) {
-        return Err(ServerError::Permission(missing));
+    // A retry can arrive after the key was freed, it still gets the original outcome
+    if replayed {
+        info!(
+            "Skipping replayed persist for play token {:?}",
+            payload.play_token
+        );
+        return Ok(StatusCode::OK.into_response());
     }
 
-    let mut tx = state.get_pool().begin().await?;
+    // Rolling back on a rejected key also releases the claimed token
+    ensure_session_key(&state, &subject_id, game_type, payload.game_key.as_deref())?;
 
-    let game_id = match game_type {
+    let (game_id, host_id) = match game_type {
         GameType::Roulette | GameType::Duel => {
             let session: SpinSession = serde_json::from_value(payload.payload)?;
+            let host_id = session.host_id;
             let game_base = GameBase::new(
                 session.game_id,
                 payload.name,
@@ -491,7 +782,20 @@ async fn persist_interactive_game(
             );
             create_game_base(tx.as_mut(), &game_base).await?;
             create_spin_game(tx.as_mut(), &session.into()).await?;
-            game_base.id
+            (game_base.id, host_id)
+        }
+        GameType::Imposter => {
+            let session: ImposterSession = serde_json::from_value(payload.payload)?;
+            let game_base = GameBase::new(
+                session.game_id,
+                payload.name,
+                game_type,
+                payload.category.clone(),
+                session.rounds.len() as i32,
+            );
+            create_game_base(tx.as_mut(), &game_base).await?;
+            create_imposter_game(tx.as_mut(), game_base.id, &session.rounds).await?;
+            (game_base.id, session.host_id)
         }
         _ => {
             return Err(ServerError::Api(
@@ -500,6 +804,14 @@ async fn persist_interactive_game(
             ));
         }
     };
+
+    set_game_creator(tx.as_mut(), game_id, host_id).await?;
+    increment_times_played(tx.as_mut(), game_id).await?;
+
+    let stored = match query.return_game {
+        true => get_game_base(tx.as_mut(), game_id).await?,
+        false => None,
+    };
     tx.commit().await?;
 
     state.fill_rounds_pool(game_id, game_type).await;
@@ -509,83 +821,500 @@ async fn persist_interactive_game(
         .await?;
 
     info!("Persisted interactive game");
-    Ok(StatusCode::CREATED)
+    Ok(persisted_response(StatusCode::CREATED, stored))
 }
 
 /// Only called by `tero.session`.
 async fn free_game_key(
     State(state): State<Arc<AppState>>,
     Extension(subject_id): Extension<SubjectId>,
-    Extension(claims): Extension<Claims>,
+    _: Require<policy::FreeGameKey>,
     Path(game_key): Path<String>,
 ) -> Result<impl IntoResponse, ServerError> {
     info!("free_game_key endpoint called with key: '{}'", game_key);
     info!("Subject: {:?}", subject_id);
 
-    let SubjectId::Integration(_) = subject_id else {
-        warn!("Non-integration user attempted to free game keys");
-        return Err(ServerError::AccessDenied);
-    };
+    allowed_integrations(
+        &state,
+        &subject_id,
+        &[IntegrationName::Session],
+        "free_game_key",
+    )?;
 
-    if let Some(missing) = claims.missing_permission([Permission::WriteGame]) {
-        warn!("Missing permission: {:?}", missing);
-        return Err(ServerError::Permission(missing));
-    }
+    let tuple = parse_game_key(&game_key)?;
 
-    let words: Vec<&str> = game_key.split(" ").collect();
-    let tuple = match (words.first(), words.get(1)) {
-        (Some(prefix), Some(suffix)) => (prefix.to_string(), suffix.to_string()),
-        _ => {
-            return Err(ServerError::Api(
-                StatusCode::BAD_REQUEST,
-                "Key word in invalid format".into(),
-            ));
-        }
-    };
+    if let Some((_, _, game_id)) = state.get_vault().key_active(&tuple) {
+        state.get_player_counts().remove(game_id);
+    }
 
     info!("Game key released: {}", game_key);
     state.get_vault().remove_key(tuple);
     Ok(StatusCode::OK)
 }
 
-async fn user_save_game(
+/// Only called by `tero.session` when players join or leave a session.
+async fn report_player_count(
     State(state): State<Arc<AppState>>,
-    Extension(subject_id): Extension<SubjectId>,
+    _integration: Integration,
+    _: Require<policy::ReportPlayerCount>,
     Path(game_id): Path<Uuid>,
+    Json(request): Json<PlayerCountRequest>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let SubjectId::BaseUser(user_id) = subject_id else {
-        warn!("Unregistered user or integration tried saving a game");
-        return Err(ServerError::AccessDenied);
-    };
+    debug!("Game {} has {} player(s)", game_id, request.count);
+    state.get_player_counts().set(game_id, request.count);
+    Ok(StatusCode::OK)
+}
 
-    save_game(state.get_pool(), user_id, game_id).await?;
-    Ok(StatusCode::CREATED)
+async fn user_save_game(
+    State(state): State<Arc<AppState>>,
+    RegisteredUser(user_id): RegisteredUser,
+    Path(game_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ServerError> {
+    match save_game(state.get_pool(), user_id, game_id).await? {
+        true => Ok(StatusCode::CREATED),
+        false => Ok(StatusCode::OK),
+    }
 }
 
 async fn user_usaved_game(
     State(state): State<Arc<AppState>>,
-    Extension(subject_id): Extension<SubjectId>,
+    RegisteredUser(user_id): RegisteredUser,
     Path(game_id): Path<Uuid>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let SubjectId::BaseUser(user_id) = subject_id else {
-        warn!("Unregistered user or integration tried unsaving a game");
-        return Err(ServerError::AccessDenied);
-    };
-
-    delete_saved_game(state.get_pool(), user_id, game_id).await?;
+    // Unsaving is idempotent, a missing save still answers 204
+    if !delete_saved_game(state.get_pool(), user_id, game_id).await? {
+        debug!("User {} had not saved game {}", user_id, game_id);
+    }
     Ok(StatusCode::NO_CONTENT)
 }
 
+async fn bulk_save_games(
+    State(state): State<Arc<AppState>>,
+    RegisteredUser(user_id): RegisteredUser,
+    ValidatedJson(request): ValidatedJson<BulkSaveRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    let result = apply_saved_games(state.get_pool(), user_id, &request).await?;
+    Ok((StatusCode::OK, Json(result)))
+}
+
+async fn get_saved_games_status(
+    State(state): State<Arc<AppState>>,
+    RegisteredUser(user_id): RegisteredUser,
+    ValidatedJson(request): ValidatedJson<SavedStatusRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    let status = get_saved_status(state.get_pool(), user_id, &request.game_ids).await?;
+    Ok((StatusCode::OK, Json(status)))
+}
+
 async fn get_saved_games(
     State(state): State<Arc<AppState>>,
-    Extension(subject_id): Extension<SubjectId>,
+    RegisteredUser(user_id): RegisteredUser,
     Query(query): Query<GamePagedRequest>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let SubjectId::BaseUser(user_id) = subject_id else {
-        warn!("Unregistered user or integration tried fetching saved games");
+    let page = get_saved_games_page(state.get_pool(), user_id, query).await?;
+    Ok((StatusCode::OK, Json(page)))
+}
+
+/// Returns the rounds of a saved game directly, skipping the key minting of
+/// the initiate flow
+async fn get_saved_game_content(
+    State(state): State<Arc<AppState>>,
+    RegisteredUser(user_id): RegisteredUser,
+    Path(game_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ServerError> {
+    let pool = state.get_pool();
+    let Some(game) = get_game_base(pool, game_id).await? else {
+        return Err(ServerError::NotFound(format!("Game {} not found", game_id)));
+    };
+
+    if !is_game_saved(pool, user_id, game_id).await? {
+        warn!(
+            "User {} requested content of unsaved game {}",
+            user_id, game_id
+        );
         return Err(ServerError::AccessDenied);
+    }
+
+    let rounds = match game.game_type {
+        GameType::Quiz => {
+            get_quiz_game_by_id(pool, game_id)
+                .await
+                .not_found(format!("Game {} not found", game_id))?
+                .rounds
+        }
+        GameType::Imposter => {
+            get_imposter_game_by_id(pool, game_id)
+                .await
+                .not_found(format!("Game {} not found", game_id))?
+                .rounds
+        }
+        GameType::Roulette | GameType::Duel => {
+            get_spin_game_by_id(pool, game_id)
+                .await
+                .not_found(format!("Game {} not found", game_id))?
+                .rounds
+        }
     };
 
-    let page = get_saved_games_page(state.get_pool(), user_id, query).await?;
+    Ok((StatusCode::OK, Json(SavedGameContent { game, rounds })))
+}
+
+async fn get_my_games(
+    State(state): State<Arc<AppState>>,
+    RegisteredUser(user_id): RegisteredUser,
+    Query(query): Query<GamePagedRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    let page = get_created_games_page(state.get_pool(), user_id, query).await?;
     Ok((StatusCode::OK, Json(page)))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        Extension, Json,
+        body::to_bytes,
+        extract::{Path, Query, State},
+        response::IntoResponse,
+    };
+    use reqwest::{Client, StatusCode};
+    use sqlx::{Pool, Postgres, postgres::PgPoolOptions};
+    use uuid::Uuid;
+
+    use crate::{
+        api::{
+            gs_client::{GSClient, JoinGameResponse},
+            require::Require,
+            subject::AnyUser,
+        },
+        app_state::{AppState, AppStateBuilder},
+        models::{
+            auth::{Jwk, Jwks},
+            error::ServerError,
+            game_base::{
+                CreateStaticGameRequest, GameCategory, GameKeyValidation, GameType,
+                InteractiveGameEnvelope, PersistGameQuery,
+            },
+            integration::IntegrationName,
+            quiz_game::QuizQuestion,
+            spin_game::SpinSession,
+            user::SubjectId,
+        },
+        service::{
+            integration_registry::IntegrationRegistry, key_vault::KeyVault,
+            popup_manager::PopupManager, util::parse_game_key,
+        },
+        tests::db::dev_pool,
+    };
+
+    use super::{
+        ensure_session_key, initiate_interactive_game, initiate_random_static_game,
+        join_interactive_game, persist_interactive_game, quiz_questions, validate_game_key,
+    };
+
+    async fn build_state(pool: Pool<Postgres>) -> Arc<AppState> {
+        let vault = KeyVault::from_words(
+            vec![String::from("arg"), String::from("sur")],
+            vec![String::from("bil"), String::from("katt")],
+        )
+        .unwrap();
+        let jwk = Jwk {
+            kid: String::from("test"),
+            n: String::new(),
+            e: String::from("AQAB"),
+            crv: String::new(),
+            x: String::new(),
+            y: String::new(),
+            kty: String::from("RSA"),
+            alg: String::from("RS256"),
+            use_: String::from("sig"),
+        };
+
+        AppStateBuilder::new(pool)
+            .jwks(Jwks { keys: vec![jwk] })
+            .gs_client(GSClient::new("http://mock-session", Client::new()))
+            .key_vault(vault)
+            .popup_manager(PopupManager::new())
+            .integrations(IntegrationRegistry::new(&[]))
+            .build()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn joining_by_key_reports_the_game_type() {
+        // Joining only reads the vault, a lazy pool is never connected
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+        let state = build_state(pool.clone()).await;
+
+        let game_id = Uuid::new_v4();
+        let key = state
+            .get_vault()
+            .create_key(&pool, GameType::Quiz, false, game_id)
+            .unwrap();
+
+        let response = join_interactive_game(
+            State(state.clone()),
+            AnyUser(Uuid::new_v4()),
+            Path(key.clone()),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let joined: JoinGameResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(joined.game_key, key);
+        assert_eq!(joined.game_type, GameType::Quiz);
+        assert_eq!(joined.game_id, game_id);
+
+        let missing = join_interactive_game(
+            State(state),
+            AnyUser(Uuid::new_v4()),
+            Path(String::from("ukjent nøkkel")),
+        )
+        .await;
+        assert!(matches!(
+            missing,
+            Err(ServerError::Api(StatusCode::NOT_FOUND, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn validating_a_key_never_fails_on_unknown_keys() {
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+        let state = build_state(pool.clone()).await;
+        let user_id = Uuid::new_v4();
+
+        let key = state
+            .get_vault()
+            .create_key(&pool, GameType::Quiz, false, Uuid::new_v4())
+            .unwrap();
+
+        for (key_word, expected) in [
+            (key, Some(GameType::Quiz)),
+            (String::from("ukjent nøkkel"), None),
+            (String::from("tull"), None),
+        ] {
+            let response =
+                validate_game_key(State(state.clone()), AnyUser(user_id), Path(key_word))
+                    .await
+                    .unwrap()
+                    .into_response();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let validation: GameKeyValidation = serde_json::from_slice(&body).unwrap();
+            assert_eq!(
+                validation,
+                GameKeyValidation {
+                    valid: expected.is_some(),
+                    game_type: expected,
+                }
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_spin_game_is_not_found() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let state = build_state(pool).await;
+
+        let response = initiate_interactive_game(
+            State(state.clone()),
+            AnyUser(Uuid::new_v4()),
+            Path((GameType::Duel, Uuid::new_v4())),
+        )
+        .await
+        .map(IntoResponse::into_response)
+        .unwrap_or_else(IntoResponse::into_response);
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(state.get_vault().active_key_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn standalone_initiation_reserves_no_key() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let state = build_state(pool.clone()).await;
+
+        let marker = Uuid::new_v4();
+        let round_ids: Vec<i64> = sqlx::query_scalar(
+            r#"
+            INSERT INTO "round_pool" (game_type, round_json)
+            SELECT 'quiz', to_jsonb($1 || '_' || n)
+            FROM generate_series(1, 20) AS n
+            RETURNING id
+            "#,
+        )
+        .bind(marker.to_string())
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+
+        let response = initiate_random_static_game(
+            State(state.clone()),
+            AnyUser(Uuid::new_v4()),
+            Path(GameType::Quiz),
+        )
+        .await
+        .map(IntoResponse::into_response);
+
+        sqlx::query(r#"DELETE FROM "round_pool" WHERE id = ANY($1)"#)
+            .bind(&round_ids)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(response.unwrap().status(), StatusCode::OK);
+        assert_eq!(state.get_vault().active_key_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn persist_under_a_key_of_another_type_conflicts() {
+        // Only the vault is read, a lazy pool is never connected
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+        let state = build_state(pool.clone()).await;
+        let session = SubjectId::Integration(IntegrationName::Session);
+
+        let key = state
+            .get_vault()
+            .create_key(&pool, GameType::Duel, false, Uuid::new_v4())
+            .unwrap();
+
+        assert!(ensure_session_key(&state, &session, GameType::Duel, Some(&key)).is_ok());
+        assert!(matches!(
+            ensure_session_key(&state, &session, GameType::Imposter, Some(&key)),
+            Err(ServerError::Api(StatusCode::CONFLICT, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn persist_without_a_key_conflicts() {
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+        let state = build_state(pool).await;
+        let session = SubjectId::Integration(IntegrationName::Session);
+
+        assert!(matches!(
+            ensure_session_key(&state, &session, GameType::Duel, None),
+            Err(ServerError::Api(StatusCode::CONFLICT, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn replayed_persist_counts_once_after_the_key_is_freed() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let state = build_state(pool.clone()).await;
+
+        let game_id = Uuid::new_v4();
+        let play_token = Uuid::new_v4();
+        let key = state
+            .get_vault()
+            .create_key(&pool, GameType::Duel, false, game_id)
+            .unwrap();
+        let session =
+            SpinSession::from_duel_rounds(Uuid::new_v4(), game_id, vec![String::from("Runde")]);
+
+        let persist = || {
+            persist_interactive_game(
+                State(state.clone()),
+                Extension(SubjectId::Integration(IntegrationName::Session)),
+                Require::granted(),
+                Path(GameType::Duel),
+                Query(PersistGameQuery::default()),
+                Json(InteractiveGameEnvelope {
+                    name: String::from("Duell"),
+                    category: GameCategory::Mixed,
+                    payload: serde_json::to_value(&session).unwrap(),
+                    play_token: Some(play_token),
+                    game_key: Some(key.clone()),
+                }),
+            )
+        };
+
+        let first = persist().await.map(IntoResponse::into_response);
+        state.get_vault().remove_key(parse_game_key(&key).unwrap());
+        let retry = persist().await.map(IntoResponse::into_response);
+
+        let times_played: Option<i32> =
+            sqlx::query_scalar(r#"SELECT times_played FROM "game_base" WHERE id = $1"#)
+                .bind(game_id)
+                .fetch_optional(&pool)
+                .await
+                .unwrap();
+
+        for query in [
+            r#"DELETE FROM "game_play" WHERE game_id = $1"#,
+            r#"DELETE FROM "spin_game" WHERE id = $1"#,
+            r#"DELETE FROM "game_base" WHERE id = $1"#,
+        ] {
+            sqlx::query(query)
+                .bind(game_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(first.unwrap().status(), StatusCode::CREATED);
+        assert_eq!(retry.unwrap().status(), StatusCode::OK);
+        assert_eq!(times_played, Some(1));
+    }
+
+    fn quiz_request(rounds: Vec<&str>, questions: Vec<QuizQuestion>) -> CreateStaticGameRequest {
+        CreateStaticGameRequest {
+            name: String::from("Quiz"),
+            category: GameCategory::Mixed,
+            rounds: rounds.into_iter().map(String::from).collect(),
+            questions,
+            description: None,
+        }
+    }
+
+    #[test]
+    fn plain_rounds_become_open_questions() {
+        let questions = quiz_questions(&quiz_request(vec!["Who?", "Why?"], vec![])).unwrap();
+        assert_eq!(
+            questions,
+            vec![
+                QuizQuestion::open("Who?".into()),
+                QuizQuestion::open("Why?".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn correct_index_must_point_at_an_option() {
+        let question = |correct_index| QuizQuestion {
+            prompt: String::from("Capital of Norway?"),
+            options: vec![String::from("Bergen"), String::from("Oslo")],
+            correct_index,
+        };
+
+        assert!(quiz_questions(&quiz_request(vec![], vec![question(1)])).is_ok());
+
+        let result = quiz_questions(&quiz_request(vec![], vec![question(1), question(2)]));
+        assert!(matches!(
+            result,
+            Err(ServerError::Api(StatusCode::BAD_REQUEST, _))
+        ));
+    }
+}
@@ -1,24 +1,30 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
 use axum::{
-    Extension, Json, Router,
-    extract::{Query, State},
+    Json, Router,
+    extract::{ConnectInfo, Path, Query, State},
+    http::HeaderMap,
     response::IntoResponse,
-    routing::{get, post},
+    routing::{get, patch, post},
 };
 use reqwest::StatusCode;
 use tracing::{debug, warn};
+use uuid::Uuid;
 
 use crate::{
-    api::validation::ValidatedJson,
+    api::{
+        require::{Require, policy},
+        subject::RegisteredUser,
+        validation::ValidatedJson,
+    },
     app_state::AppState,
+    config::app_config::CONFIG,
     db,
     models::{
-        auth::Claims,
         error::ServerError,
-        game_tip::{CreateGameTipRequest, GameTipPageQuery},
-        user::{Permission, SubjectId},
+        game_tip::{CreateGameTipRequest, GameTipPageQuery, PatchGameTipRequest},
     },
+    service::util::client_ip,
 };
 
 pub fn public_game_tip_routes(state: Arc<AppState>) -> Router {
@@ -29,15 +35,46 @@ pub fn public_game_tip_routes(state: Arc<AppState>) -> Router {
 
 pub fn protected_game_tip_routes(state: Arc<AppState>) -> Router {
     Router::new()
+        .route("/mine", post(create_own_game_tip))
         .route("/admin", get(get_game_tips_admin))
+        .route("/admin/{tip_id}", patch(patch_game_tip_status))
         .with_state(state)
 }
 
 async fn create_game_tip(
     State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     ValidatedJson(request): ValidatedJson<CreateGameTipRequest>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let tip_id = db::game_tip::create_game_tip(state.get_pool(), &request).await?;
+    submit_game_tip(&state, &headers, peer, &request, None).await
+}
+
+/// Same as the public submission, but the tip is tied to the user so it shows up in their export
+async fn create_own_game_tip(
+    State(state): State<Arc<AppState>>,
+    RegisteredUser(user_id): RegisteredUser,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    ValidatedJson(request): ValidatedJson<CreateGameTipRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    submit_game_tip(&state, &headers, peer, &request, Some(user_id)).await
+}
+
+async fn submit_game_tip(
+    state: &AppState,
+    headers: &HeaderMap,
+    peer: SocketAddr,
+    request: &CreateGameTipRequest,
+    submitted_by: Option<Uuid>,
+) -> Result<(StatusCode, Json<serde_json::Value>), ServerError> {
+    let ip = client_ip(headers, peer, &CONFIG.server.trusted_proxies);
+    if let Err(limited) = state.get_tip_limiter().try_acquire(ip) {
+        warn!("Game tip submissions from {} were rate limited", ip);
+        return Err(ServerError::RateLimited(limited));
+    }
+
+    let tip_id = db::game_tip::create_game_tip(state.get_pool(), request, submitted_by).await?;
 
     Ok((
         StatusCode::CREATED,
@@ -47,21 +84,31 @@ async fn create_game_tip(
 
 async fn get_game_tips_admin(
     State(state): State<Arc<AppState>>,
-    Extension(subject_id): Extension<SubjectId>,
-    Extension(claims): Extension<Claims>,
+    _user: RegisteredUser,
+    _: Require<policy::GetGameTipsAdmin>,
     Query(query): Query<GameTipPageQuery>,
 ) -> Result<impl IntoResponse, ServerError> {
-    // Only admins can fetch game tips
-    let SubjectId::BaseUser(_) = subject_id else {
-        warn!("Unauthorized subject attempted to read game tips");
-        return Err(ServerError::AccessDenied);
-    };
+    debug!("Fetching game tips");
+    let page =
+        db::game_tip::get_game_tips_page(state.get_pool(), query.page_num, query.status).await?;
+    Ok((StatusCode::OK, Json(page)))
+}
 
-    if let Some(missing) = claims.missing_permission([Permission::ReadAdmin]) {
-        return Err(ServerError::Permission(missing));
+async fn patch_game_tip_status(
+    State(state): State<Arc<AppState>>,
+    _user: RegisteredUser,
+    _: Require<policy::PatchGameTip>,
+    Path(tip_id): Path<Uuid>,
+    Json(request): Json<PatchGameTipRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    let updated =
+        db::game_tip::update_game_tip_status(state.get_pool(), tip_id, request.status).await?;
+    if !updated {
+        return Err(ServerError::NotFound(format!(
+            "Game tip {} not found",
+            tip_id
+        )));
     }
 
-    debug!("Fetching game tips");
-    let page = db::game_tip::get_game_tips_page(state.get_pool(), query.page_num).await?;
-    Ok((StatusCode::OK, Json(page)))
+    Ok(StatusCode::NO_CONTENT)
 }
@@ -1,9 +1,14 @@
+use std::time::Duration;
+
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use tracing::error;
 use uuid::Uuid;
 
-use crate::models::game_base::{GameType, InitiateGameRequest};
+use crate::{
+    models::game_base::{GameType, InitiateGameRequest},
+    service::circuit_breaker::{BreakerState, CircuitBreaker},
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum GSClientError {
@@ -34,19 +39,80 @@ pub struct JoinGameResponse {
     pub is_draft: bool,
 }
 
+/// Shared outbound client, a timed out request surfaces as `GSClientError::Http`
+pub fn http_client(
+    timeout: Duration,
+    connect_timeout: Duration,
+) -> Result<reqwest::Client, reqwest::Error> {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .connect_timeout(connect_timeout)
+        .build()
+}
+
+impl GSClientError {
+    /// Failures that say something about the health of the session service
+    fn is_upstream_failure(&self) -> bool {
+        match self {
+            GSClientError::Http(_) => true,
+            GSClientError::ApiError(status, _) => status.is_server_error(),
+            GSClientError::Serialize(_) => false,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct GSClient {
     client: reqwest::Client,
     domain: String,
+    breaker: CircuitBreaker,
 }
 
 impl GSClient {
     pub fn new(domain: impl Into<String>, client: reqwest::Client) -> Self {
         let domain = domain.into();
-        Self { domain, client }
+        Self {
+            domain,
+            client,
+            breaker: CircuitBreaker::default(),
+        }
+    }
+
+    pub fn with_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.breaker = breaker;
+        self
+    }
+
+    pub fn breaker_state(&self) -> BreakerState {
+        self.breaker.state()
+    }
+
+    /// Fails fast while the breaker is open instead of waiting on a dead upstream
+    async fn guarded<T>(
+        &self,
+        request: impl Future<Output = Result<T, GSClientError>>,
+    ) -> Result<T, GSClientError> {
+        if !self.breaker.try_acquire() {
+            return Err(GSClientError::ApiError(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Game session service is unavailable".into(),
+            ));
+        }
+
+        let result = request.await;
+        match &result {
+            Err(e) if e.is_upstream_failure() => self.breaker.record_failure(),
+            _ => self.breaker.record_success(),
+        }
+
+        result
     }
 
     pub async fn health_check(&self) -> Result<(), GSClientError> {
+        self.guarded(self.send_health_check()).await
+    }
+
+    async fn send_health_check(&self) -> Result<(), GSClientError> {
         let response = self
             .client
             .get(format!("{}/health", self.domain))
@@ -67,7 +133,15 @@ impl GSClient {
         game_type: &GameType,
         payload: &InitiateGameRequest,
     ) -> Result<(), GSClientError> {
-        let url = format!("{}/session/initiate/{}", self.domain, game_type.as_str());
+        self.guarded(self.send_initiate(game_type, payload)).await
+    }
+
+    async fn send_initiate(
+        &self,
+        game_type: &GameType,
+        payload: &InitiateGameRequest,
+    ) -> Result<(), GSClientError> {
+        let url = format!("{}/{}", self.domain, game_type.session_path());
         let response = self
             .client
             .post(&url)
@@ -86,3 +160,58 @@ impl GSClient {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::{http::StatusCode, response::IntoResponse};
+    use tokio::net::TcpListener;
+
+    use crate::models::error::ServerError;
+
+    use crate::service::circuit_breaker::{BreakerState, CircuitBreaker};
+
+    use super::{GSClient, GSClientError, http_client};
+
+    #[tokio::test]
+    async fn hung_upstream_times_out_as_unavailable() {
+        // Accepts connections but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut open = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                open.push(socket);
+            }
+        });
+
+        let client = http_client(Duration::from_millis(200), Duration::from_millis(200)).unwrap();
+        let gs_client = GSClient::new(format!("http://{}", addr), client);
+
+        let error = gs_client.health_check().await.unwrap_err();
+        assert!(matches!(&error, GSClientError::Http(e) if e.is_timeout()));
+
+        let response = ServerError::from(error).into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn open_breaker_fails_fast_without_calling_upstream() {
+        // Nothing listens here, so every real call fails
+        let client = http_client(Duration::from_millis(200), Duration::from_millis(200)).unwrap();
+        let gs_client = GSClient::new("http://127.0.0.1:9", client)
+            .with_breaker(CircuitBreaker::new(1, Duration::from_secs(60)));
+
+        assert!(matches!(
+            gs_client.health_check().await,
+            Err(GSClientError::Http(_))
+        ));
+        assert_eq!(gs_client.breaker_state(), BreakerState::Open);
+
+        assert!(matches!(
+            gs_client.health_check().await,
+            Err(GSClientError::ApiError(StatusCode::SERVICE_UNAVAILABLE, _))
+        ));
+    }
+}
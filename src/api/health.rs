@@ -1,21 +1,25 @@
 use std::sync::Arc;
 
 use axum::{Json, Router, extract::State, response::IntoResponse, routing::get};
+use chrono::Utc;
 use reqwest::StatusCode;
-use serde_json::json;
+use serde_json::{Value, json};
+use sqlx::{Pool, Postgres};
 
 use crate::{
     app_state::AppState,
     db,
     models::{
         error::ServerError,
-        system_log::{LogAction, LogCeverity},
+        system_log::{LogAction, LogCeverity, LogMetadata},
     },
 };
 
 pub fn health_routes(state: Arc<AppState>) -> Router {
     Router::new()
         .route("/", get(health))
+        .route("/live", get(health))
+        .route("/ready", get(health_ready))
         .route("/detailed", get(health_detailed))
         .with_state(state.clone())
 }
@@ -24,9 +28,62 @@ async fn health() -> impl IntoResponse {
     "OK".into_response()
 }
 
+/// Served from a short lived cache so frequent polling does not turn into a
+/// database and session service round-trip per hit. Both checks are only run
+/// once per `health_cache_ttl_secs`.
 async fn health_detailed(
     State(state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, ServerError> {
+    let mut json = state
+        .get_health_cache()
+        .get_or(async { Ok(check_health(&state).await) })
+        .await?;
+
+    // Pool usage is read live, it is cheap and goes stale fast
+    json["pool"] = pool_stats(state.get_pool());
+
+    Ok((StatusCode::OK, Json(json)))
+}
+
+fn pool_stats(pool: &Pool<Postgres>) -> Value {
+    let size = pool.size();
+    let idle = pool.num_idle() as u32;
+
+    json!({
+        "size": size,
+        "idle": idle,
+        "in_use": size.saturating_sub(idle),
+        "max": pool.options().get_max_connections(),
+    })
+}
+
+/// Fails with 503 while the database or session service is unreachable, so
+/// orchestrators stop routing traffic here without restarting the process
+async fn health_ready(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, ServerError> {
+    let health = state
+        .get_health_cache()
+        .get_or(async { Ok(check_health(&state).await) })
+        .await?;
+
+    let failed: Vec<&str> = ["database", "session"]
+        .into_iter()
+        .filter(|dependency| health[*dependency] != Value::Bool(true))
+        .collect();
+
+    let status = match failed.is_empty() {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+
+    Ok((
+        status,
+        Json(json!({ "ready": failed.is_empty(), "failed": failed })),
+    ))
+}
+
+async fn check_health(state: &AppState) -> Value {
     let platform = true;
     let db_status = db::health::health_check(state.get_pool()).await.is_ok();
 
@@ -40,18 +97,20 @@ async fn health_detailed(
                 .ceverity(LogCeverity::Critical)
                 .function("health_check")
                 .description("Game session service (tero-session) health check failed")
-                .metadata(json!({"error": e.to_string()}))
+                .metadata(LogMetadata::error(e))
                 .log_async();
 
             false
         }
     };
 
-    let json = json!({
+    json!({
+        "checked_at": Utc::now(),
         "platform": platform,
         "database": db_status,
         "session": session_status,
-    });
-
-    Ok((StatusCode::OK, Json(json)))
+        "session_breaker": state.get_gs_client().breaker_state(),
+        "page_cache": state.get_cache().metrics(),
+        "background_tasks": state.get_tasks().metrics(),
+    })
 }
@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Json, Router,
+    extract::State,
+    response::IntoResponse,
+    routing::{get, post},
+};
+use reqwest::StatusCode;
+
+use crate::{
+    api::{
+        require::{Require, policy},
+        subject::RegisteredUser,
+        validation::ValidatedJson,
+    },
+    app_state::AppState,
+    models::{
+        error::ServerError,
+        integration::RegisterIntegrationRequest,
+        system_log::{LogAction, LogCeverity, LogMetadata},
+        user::SubjectId,
+    },
+};
+
+pub fn integration_routes(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(list_integrations))
+        .route("/", post(register_integration))
+        .with_state(state)
+}
+
+async fn list_integrations(
+    State(state): State<Arc<AppState>>,
+    _user: RegisteredUser,
+    _: Require<policy::ListIntegrations>,
+) -> Result<impl IntoResponse, ServerError> {
+    let integrations = state.get_integrations().list().await;
+    Ok((StatusCode::OK, Json(integrations)))
+}
+
+/// Takes effect for the next token presented by the client, no restart needed
+async fn register_integration(
+    State(state): State<Arc<AppState>>,
+    _user: RegisteredUser,
+    _: Require<policy::RegisterIntegration>,
+    Extension(subject_id): Extension<SubjectId>,
+    ValidatedJson(request): ValidatedJson<RegisterIntegrationRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    let integration = request.into_config();
+    state
+        .get_integrations()
+        .register(integration.clone())
+        .await?;
+
+    state
+        .syslog()
+        .subject(subject_id)
+        .action(LogAction::Create)
+        .ceverity(LogCeverity::Info)
+        .function("register_integration")
+        .description(&format!(
+            "Registered integration subject as {}",
+            integration.name
+        ))
+        .metadata(LogMetadata::entity(&integration.subject))
+        .log_async();
+
+    Ok((StatusCode::CREATED, Json(integration)))
+}
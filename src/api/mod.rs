@@ -3,6 +3,9 @@ pub mod game;
 pub mod game_tip;
 pub mod gs_client;
 pub mod health;
+pub mod integration;
+pub mod require;
+pub mod subject;
 pub mod system_log;
 pub mod user;
 pub mod validation;
@@ -0,0 +1,92 @@
+use std::marker::PhantomData;
+
+use axum::{extract::FromRequestParts, http::request::Parts};
+use tracing::warn;
+
+use crate::models::{
+    auth::Claims,
+    error::ServerError,
+    permission::{PERMISSION_POLICY, PolicyRoute},
+};
+
+/// Ties a marker type to the policy route it guards. Handlers that only need
+/// the permission for part of a request call `check` or `allows` directly.
+pub trait RoutePolicy {
+    const ROUTE: PolicyRoute;
+
+    fn check(claims: &Claims) -> Result<(), ServerError> {
+        PERMISSION_POLICY.check(Self::ROUTE, claims)
+    }
+
+    fn allows(claims: &Claims) -> bool {
+        PERMISSION_POLICY.allows(Self::ROUTE, claims)
+    }
+}
+
+/// Rejects with `ServerError::Permission` unless the caller's claims satisfy
+/// the configured policy for `R::ROUTE`, e.g. `_: Require<policy::ListAllUsers>`
+pub struct Require<R: RoutePolicy>(PhantomData<R>);
+
+impl<R: RoutePolicy> Require<R> {
+    /// Lets tests call a handler directly without going through the extractor
+    #[cfg(test)]
+    pub fn granted() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<S, R> FromRequestParts<S> for Require<R>
+where
+    S: Send + Sync,
+    R: RoutePolicy,
+{
+    type Rejection = ServerError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Some(claims) = parts.extensions.get::<Claims>() else {
+            warn!("Permission extractor used on a route without auth middleware");
+            return Err(ServerError::AccessDenied);
+        };
+
+        R::check(claims)?;
+        Ok(Self(PhantomData))
+    }
+}
+
+macro_rules! route_policies {
+    ($($route:ident),* $(,)?) => {
+        $(
+            pub struct $route;
+
+            impl super::RoutePolicy for $route {
+                const ROUTE: super::PolicyRoute = super::PolicyRoute::$route;
+            }
+        )*
+    };
+}
+
+/// Markers for `Require`, named after the `PolicyRoute` they check
+pub mod policy {
+    route_policies!(
+        ListAllUsers,
+        IncludeDeleted,
+        ExportUserData,
+        EraseUser,
+        GetUserActivityStats,
+        UpdateClientPopup,
+        GetSystemLogPage,
+        StreamSystemLogs,
+        CreateSystemLog,
+        GetLogCategoryCount,
+        ImportGames,
+        DeleteGame,
+        PatchGame,
+        PersistInteractiveGame,
+        FreeGameKey,
+        ReportPlayerCount,
+        GetGameTipsAdmin,
+        PatchGameTip,
+        ListIntegrations,
+        RegisterIntegration,
+    );
+}
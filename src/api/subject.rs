@@ -0,0 +1,204 @@
+use axum::{extract::FromRequestParts, http::request::Parts};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    models::{
+        error::ServerError,
+        integration::IntegrationName,
+        system_log::{LogAction, LogCeverity},
+        user::SubjectId,
+    },
+};
+
+/// A registered base user, guests and integrations are rejected
+#[derive(Debug, Clone, Copy)]
+pub struct RegisteredUser(pub Uuid);
+
+/// A machine client authenticated through the client credentials flow
+#[derive(Debug, Clone)]
+pub struct Integration(pub IntegrationName);
+
+/// Any human caller, guest or registered, integrations are rejected
+#[derive(Debug, Clone, Copy)]
+pub struct AnyUser(pub Uuid);
+
+/// The subject is set by `auth_mw`, a missing one means the route is not behind it
+fn subject(parts: &Parts) -> Result<&SubjectId, ServerError> {
+    parts.extensions.get::<SubjectId>().ok_or_else(|| {
+        warn!("Subject extractor used on a route without auth middleware");
+        ServerError::AccessDenied
+    })
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for RegisteredUser {
+    type Rejection = ServerError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        match subject(parts)? {
+            SubjectId::BaseUser(user_id) => Ok(Self(*user_id)),
+            _ => {
+                warn!(
+                    "Unregistered user or integration tried accessing {}",
+                    parts.uri.path()
+                );
+                Err(ServerError::AccessDenied)
+            }
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Integration {
+    type Rejection = ServerError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        match subject(parts)? {
+            SubjectId::Integration(name) => Ok(Self(name.clone())),
+            _ => {
+                warn!(
+                    "User tried accessing integration route {}",
+                    parts.uri.path()
+                );
+                Err(ServerError::AccessDenied)
+            }
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for AnyUser {
+    type Rejection = ServerError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        match subject(parts)? {
+            SubjectId::BaseUser(user_id) | SubjectId::PseudoUser(user_id) => Ok(Self(*user_id)),
+            SubjectId::Integration(_) => {
+                warn!(
+                    "Integration tried accessing user route {}",
+                    parts.uri.path()
+                );
+                Err(ServerError::AccessDenied)
+            }
+        }
+    }
+}
+
+/// Only lets the `allowed` integrations through. A valid token from another
+/// integration is logged, since it points at a misconfigured or leaked client.
+pub fn allowed_integrations(
+    state: &AppState,
+    subject_id: &SubjectId,
+    allowed: &[IntegrationName],
+    function: &str,
+) -> Result<IntegrationName, ServerError> {
+    let result = check_integration(subject_id, allowed);
+    match (&result, subject_id) {
+        (Err(_), SubjectId::Integration(name)) => {
+            warn!("Integration {} is not allowed to call {}", name, function);
+            state
+                .syslog()
+                .subject(subject_id.clone())
+                .action(LogAction::Other)
+                .ceverity(LogCeverity::Warning)
+                .function(function)
+                .description(&format!(
+                    "Integration {} called an endpoint reserved for other integrations",
+                    name
+                ))
+                .log_async();
+        }
+        (Err(_), _) => warn!("Non-integration subject attempted to call {}", function),
+        _ => {}
+    }
+
+    result
+}
+
+fn check_integration(
+    subject_id: &SubjectId,
+    allowed: &[IntegrationName],
+) -> Result<IntegrationName, ServerError> {
+    match subject_id {
+        SubjectId::Integration(name) if allowed.contains(name) => Ok(name.clone()),
+        _ => Err(ServerError::AccessDenied),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{extract::FromRequestParts, http::Request};
+    use uuid::Uuid;
+
+    use crate::models::{error::ServerError, integration::IntegrationName, user::SubjectId};
+
+    use super::{AnyUser, Integration, RegisteredUser, check_integration};
+
+    fn parts(subject: Option<SubjectId>) -> axum::http::request::Parts {
+        let mut request = Request::builder().uri("/games/saved").body(()).unwrap();
+        if let Some(subject) = subject {
+            request.extensions_mut().insert(subject);
+        }
+        request.into_parts().0
+    }
+
+    #[tokio::test]
+    async fn registered_user_rejects_guests_and_missing_subjects() {
+        let id = Uuid::new_v4();
+
+        let user =
+            RegisteredUser::from_request_parts(&mut parts(Some(SubjectId::BaseUser(id))), &())
+                .await
+                .unwrap();
+        assert_eq!(user.0, id);
+
+        let guest =
+            RegisteredUser::from_request_parts(&mut parts(Some(SubjectId::PseudoUser(id))), &())
+                .await;
+        assert!(matches!(guest, Err(ServerError::AccessDenied)));
+
+        let missing = RegisteredUser::from_request_parts(&mut parts(None), &()).await;
+        assert!(matches!(missing, Err(ServerError::AccessDenied)));
+    }
+
+    #[tokio::test]
+    async fn any_user_accepts_guests_but_not_integrations() {
+        let id = Uuid::new_v4();
+
+        let guest = AnyUser::from_request_parts(&mut parts(Some(SubjectId::PseudoUser(id))), &())
+            .await
+            .unwrap();
+        assert_eq!(guest.0, id);
+
+        let machine = SubjectId::Integration(IntegrationName::Session);
+        assert!(
+            AnyUser::from_request_parts(&mut parts(Some(machine.clone())), &())
+                .await
+                .is_err()
+        );
+        assert!(
+            Integration::from_request_parts(&mut parts(Some(machine)), &())
+                .await
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn wrong_integration_is_rejected() {
+        let session = SubjectId::Integration(IntegrationName::Session);
+        assert!(matches!(
+            check_integration(&session, &[IntegrationName::Auth0]),
+            Err(ServerError::AccessDenied)
+        ));
+        assert_eq!(
+            check_integration(
+                &session,
+                &[IntegrationName::Auth0, IntegrationName::Session]
+            )
+            .unwrap(),
+            IntegrationName::Session
+        );
+
+        let user = SubjectId::BaseUser(Uuid::new_v4());
+        assert!(check_integration(&user, &[IntegrationName::Session]).is_err());
+    }
+}
@@ -1,24 +1,35 @@
-use std::sync::Arc;
+use std::{str::FromStr, sync::Arc};
 
-use tracing::{info, warn};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
 
 use axum::{
     Extension, Json, Router,
     extract::{Query, State},
-    response::IntoResponse,
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
     routing::{get, post},
 };
 use reqwest::StatusCode;
 
 use crate::{
+    api::{
+        require::{Require, policy},
+        subject::{Integration, RegisteredUser},
+    },
     app_state::AppState,
     db,
     models::{
-        auth::Claims,
         error::ServerError,
-        system_log::{CreateClientLogRequest, CreateSyslogRequest, LogAction, SyslogPageQuery},
-        user::{Permission, SubjectId},
+        system_log::{
+            CreateClientLogRequest, CreateSyslogRequest, LogAction, LogCountQuery, LogCursor,
+            LogGroupBy, LogStreamQuery, SyslogPageQuery,
+        },
+        user::SubjectId,
     },
+    service::log_stream,
 };
 
 pub fn log_routes(state: Arc<AppState>) -> Router {
@@ -26,50 +37,67 @@ pub fn log_routes(state: Arc<AppState>) -> Router {
         .route("/", post(create_system_log).get(get_system_log_page))
         .route("/count", get(get_log_category_count))
         .route("/client", post(create_client_log))
+        .route("/stream", get(stream_system_logs))
         .with_state(state)
 }
 
+/// Pushes new system logs to the client as they are created.
+/// A disconnecting client drops the stream, which unsubscribes its receiver.
+async fn stream_system_logs(
+    RegisteredUser(user_id): RegisteredUser,
+    _: Require<policy::StreamSystemLogs>,
+    Query(query): Query<LogStreamQuery>,
+) -> Result<impl IntoResponse, ServerError> {
+    debug!("User {} subscribed to the system log stream", user_id);
+    let receiver = log_stream::subscribe();
+
+    let stream = futures::stream::unfold((receiver, query), |(mut receiver, query)| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(log) if query.matches(&log) => {
+                    let event = Event::default().json_data(&log);
+                    return Some((event, (receiver, query)));
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("System log stream lagged, skipped {} entries", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
 async fn get_system_log_page(
     State(state): State<Arc<AppState>>,
-    Extension(subject_id): Extension<SubjectId>,
-    Extension(claims): Extension<Claims>,
+    _user: RegisteredUser,
+    _: Require<policy::GetSystemLogPage>,
     Query(query): Query<SyslogPageQuery>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let SubjectId::BaseUser(_) = subject_id else {
-        warn!("Unauthorized subject attempted to read system logs");
-        return Err(ServerError::AccessDenied);
-    };
+    query.validate_range()?;
 
-    if let Some(missing) = claims.missing_permission([Permission::ReadAdmin]) {
-        return Err(ServerError::Permission(missing));
-    }
+    let before = query
+        .before
+        .as_deref()
+        .map(LogCursor::from_str)
+        .transpose()
+        .map_err(|e| ServerError::Api(StatusCode::BAD_REQUEST, e))?;
 
-    let page = db::system_log::get_system_log_page(state.get_pool(), query).await?;
+    let page = db::system_log::get_system_log_page(state.get_pool(), query, before).await?;
     Ok((StatusCode::OK, Json(page)))
 }
 
 async fn create_system_log(
     State(state): State<Arc<AppState>>,
+    Integration(int_name): Integration,
+    _: Require<policy::CreateSystemLog>,
     Extension(subject_id): Extension<SubjectId>,
-    Extension(claims): Extension<Claims>,
     Json(request): Json<CreateSyslogRequest>,
 ) -> Result<impl IntoResponse, ServerError> {
-    match &subject_id {
-        SubjectId::PseudoUser(id) | SubjectId::BaseUser(id) => {
-            warn!(
-                "User {} attempted to write a system log without permission",
-                id
-            );
-            return Err(ServerError::AccessDenied);
-        }
-        SubjectId::Integration(int_name) => {
-            if let Some(missing) = claims.missing_permission([Permission::WriteSystemLog]) {
-                return Err(ServerError::Permission(missing));
-            }
-
-            info!("Integration {} is creating a system log entry", int_name);
-        }
-    };
+    info!("Integration {} is creating a system log entry", int_name);
 
     let mut builder = state.syslog().subject(subject_id);
 
@@ -126,20 +154,25 @@ async fn create_client_log(
     Ok(StatusCode::CREATED)
 }
 
+/// Severity totals by default, `group_by=action|subject` breaks counts down further
 async fn get_log_category_count(
     State(state): State<Arc<AppState>>,
-    Extension(subject_id): Extension<SubjectId>,
-    Extension(claims): Extension<Claims>,
-) -> Result<impl IntoResponse, ServerError> {
-    let SubjectId::BaseUser(_) = subject_id else {
-        warn!("Unauthorized subject attempted to read log category counts");
-        return Err(ServerError::AccessDenied);
+    _user: RegisteredUser,
+    _: Require<policy::GetLogCategoryCount>,
+    Query(query): Query<LogCountQuery>,
+) -> Result<Response, ServerError> {
+    let pool = state.get_pool();
+    let response = match query.group_by {
+        LogGroupBy::Severity => {
+            Json(db::system_log::get_log_category_count(pool).await?).into_response()
+        }
+        LogGroupBy::Action => {
+            Json(db::system_log::get_log_action_count(pool).await?).into_response()
+        }
+        LogGroupBy::Subject => {
+            Json(db::system_log::get_log_subject_count(pool).await?).into_response()
+        }
     };
 
-    if let Some(missing) = claims.missing_permission([Permission::ReadAdmin]) {
-        return Err(ServerError::Permission(missing));
-    }
-
-    let counts = db::system_log::get_log_category_count(state.get_pool()).await?;
-    Ok((StatusCode::OK, Json(counts)))
+    Ok(response)
 }
@@ -1,18 +1,30 @@
-use std::{str::FromStr, sync::Arc};
+use std::sync::Arc;
+
+use chrono::Utc;
 
 use axum::{
     Extension, Json, Router,
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{StatusCode, header::CONTENT_DISPOSITION},
     response::{IntoResponse, Response},
     routing::{delete, get, patch, post, put},
 };
 
 use crate::{
-    api::validation::ValidatedJson,
+    api::{
+        require::{Require, RoutePolicy, policy},
+        subject::{RegisteredUser, allowed_integrations},
+        validation::{ValidatedJson, validate_username},
+    },
     app_state::AppState,
     config::app_config::CONFIG,
-    models::user::{DeleteUserQuery, ListUsersQuery, ResetPasswordRequest},
+    models::{
+        game_base::GamePagedRequest,
+        user::{
+            ActivityStatsQuery, DeleteUserQuery, ExportUserQuery, ListUsersQuery,
+            ResetPasswordRequest, UserDataExport,
+        },
+    },
 };
 use serde_json::json;
 use tracing::{debug, error, info, warn};
@@ -21,19 +33,25 @@ use uuid::Uuid;
 use crate::{
     db::{
         self,
+        game_base::{get_created_games_page, get_saved_game_ids},
+        game_tip::get_game_tips_by_user,
         user::{
-            create_base_user, create_pseudo_user, delete_base_user, get_base_user_by_id,
+            create_pseudo_user, delete_base_user, get_base_user_by_id, get_or_create_base_user,
             link_pseudo_to_base_user, list_base_users, patch_base_user_by_id, pseudo_user_exists,
-            update_pseudo_user_activity,
+            soft_delete_base_user, username_taken,
         },
     },
     models::{
         auth::Claims,
-        error::ServerError,
-        system_log::{LogAction, LogCeverity},
-        user::{Auth0User, EnsureUserQuery, PatchUserRequest, Permission, SubjectId, UserRole},
+        error::{OptionalExt, ServerError},
+        integration::IntegrationName,
+        system_log::{LogAction, LogCeverity, LogMetadata},
+        user::{
+            Auth0User, EnsureUserQuery, PatchUserRequest, Permission, SubjectId, UserRole,
+            UsernameAvailability, UsernameQuery,
+        },
     },
-    service::popup_manager::ClientPopup,
+    service::{popup_manager::ClientPopup, util::parse_guest_id},
 };
 
 pub fn public_auth_routes(state: Arc<AppState>) -> Router {
@@ -47,6 +65,8 @@ pub fn protected_auth_routes(state: Arc<AppState>) -> Router {
     Router::new()
         .route("/", get(list_all_users))
         .route("/me", get(get_base_user_from_subject))
+        .route("/me/export", get(export_user_data))
+        .route("/username-available", get(username_available))
         .route("/activity-stats", get(get_user_activity_stats))
         .route("/popups", put(update_client_popup))
         .route("/reset-password", post(reset_password))
@@ -55,6 +75,45 @@ pub fn protected_auth_routes(state: Arc<AppState>) -> Router {
         .with_state(state)
 }
 
+async fn username_available(
+    State(state): State<Arc<AppState>>,
+    Extension(subject_id): Extension<SubjectId>,
+    Query(query): Query<UsernameQuery>,
+) -> Result<impl IntoResponse, ServerError> {
+    let (user_id, own_id) = match subject_id {
+        SubjectId::PseudoUser(id) => (id, None),
+        SubjectId::BaseUser(id) => (id, Some(id)),
+        SubjectId::Integration(_) => {
+            warn!("Integration attempted to check username availability");
+            return Err(ServerError::AccessDenied);
+        }
+    };
+
+    state
+        .get_username_limiter()
+        .try_acquire(user_id)
+        .map_err(ServerError::RateLimited)?;
+
+    let availability = match validate_username(&query.username) {
+        Err(e) => UsernameAvailability {
+            available: false,
+            reason: e.message.map(|m| m.to_string()),
+        },
+        Ok(()) => match username_taken(state.get_pool(), &query.username, own_id).await? {
+            true => UsernameAvailability {
+                available: false,
+                reason: Some("Username is taken".into()),
+            },
+            false => UsernameAvailability {
+                available: true,
+                reason: None,
+            },
+        },
+    };
+
+    Ok((StatusCode::OK, Json(availability)))
+}
+
 async fn get_base_user_from_subject(
     State(state): State<Arc<AppState>>,
     Extension(subject_id): Extension<SubjectId>,
@@ -93,6 +152,76 @@ async fn get_base_user_from_subject(
     Ok((StatusCode::OK, Json(wrapped)))
 }
 
+/// Self-service data export, admins may pass `user_id` to export someone else
+async fn export_user_data(
+    State(state): State<Arc<AppState>>,
+    Extension(subject_id): Extension<SubjectId>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<ExportUserQuery>,
+) -> Result<impl IntoResponse, ServerError> {
+    let SubjectId::BaseUser(own_id) = subject_id else {
+        return Err(ServerError::AccessDenied);
+    };
+
+    let user_id = query.user_id.unwrap_or(own_id);
+    if user_id != own_id {
+        policy::ExportUserData::check(&claims)?;
+    }
+
+    let pool = state.get_pool();
+    let user = get_base_user_by_id(pool, user_id)
+        .await?
+        .not_found(format!("User {} not found", user_id))?;
+
+    let saved_game_ids = get_saved_game_ids(pool, user_id).await?;
+    let game_tips = get_game_tips_by_user(pool, user_id).await?;
+
+    let mut created_games = Vec::new();
+    let mut page_num = 0;
+    loop {
+        let request = GamePagedRequest {
+            page_num: Some(page_num),
+            page_size: None,
+            game_type: None,
+            category: None,
+            categories: None,
+            sort: None,
+        };
+
+        let page = get_created_games_page(pool, user_id, request).await?;
+        created_games.extend(page.items);
+        if !page.has_next {
+            break;
+        }
+        page_num += 1;
+    }
+
+    state
+        .syslog()
+        .subject(subject_id)
+        .action(LogAction::Read)
+        .ceverity(LogCeverity::Info)
+        .function("export_user_data")
+        .description("User data exported")
+        .metadata(LogMetadata::entity(user_id))
+        .log_async();
+
+    let export = UserDataExport {
+        exported_at: Utc::now(),
+        user,
+        saved_game_ids,
+        created_games,
+        game_tips,
+    };
+
+    let disposition = format!("attachment; filename=\"tero-export-{}.json\"", user_id);
+    Ok((
+        StatusCode::OK,
+        [(CONTENT_DISPOSITION, disposition)],
+        Json(export),
+    ))
+}
+
 async fn ensure_pseudo_user(
     State(state): State<Arc<AppState>>,
     Query(query): Query<EnsureUserQuery>,
@@ -110,24 +239,7 @@ async fn ensure_pseudo_user(
         }
     };
 
-    let pool = state.get_pool().clone();
-    tokio::spawn(async move {
-        if let Err(e) = update_pseudo_user_activity(&pool, pseudo_id).await {
-            error!(
-                "Failed to update pseudo user activity for {}: {}",
-                pseudo_id, e
-            );
-            _ = state
-                .syslog()
-                .action(LogAction::Update)
-                .ceverity(LogCeverity::Warning)
-                .function("ensure_pseudo_user")
-                .description("Failed to update pseudo user last activity timestamp")
-                .metadata(json!({"pseudo_id": pseudo_id, "error": e.to_string()}))
-                .log()
-                .await;
-        };
-    });
+    state.get_pseudo_activity().record(pseudo_id);
 
     let status = if created {
         StatusCode::CREATED
@@ -184,11 +296,21 @@ async fn delete_user(
         return Err(ServerError::AccessDenied);
     }
 
-    let user = get_base_user_by_id(state.get_pool(), query.user_id)
-        .await?
-        .ok_or_else(|| ServerError::NotFound(format!("User {} not found", query.user_id)))?;
+    let erase = query.erase.unwrap_or(false);
+    if erase {
+        policy::EraseUser::check(&claims)?;
+    }
 
-    if let Some(auth0_id) = &user.auth0_id {
+    // Tombstoned users are hidden from lookups but can still be erased
+    let user = get_base_user_by_id(state.get_pool(), query.user_id).await?;
+    if user.is_none() && !erase {
+        return Err(ServerError::NotFound(format!(
+            "User {} not found",
+            query.user_id
+        )));
+    }
+
+    if let Some(auth0_id) = user.as_ref().and_then(|user| user.auth0_id.as_ref()) {
         let token_payload = serde_json::json!({
             "grant_type": "client_credentials",
             "client_id": CONFIG.auth0.mgmt_client_id,
@@ -244,7 +366,10 @@ async fn delete_user(
         }
     }
 
-    let deleted = delete_base_user(state.get_pool(), query.user_id).await?;
+    let deleted = match erase {
+        true => delete_base_user(state.get_pool(), query.user_id).await?,
+        false => soft_delete_base_user(state.get_pool(), query.user_id).await?,
+    };
     if !deleted {
         return Err(ServerError::NotFound(format!(
             "User {} not found",
@@ -259,7 +384,7 @@ async fn delete_user(
         .ceverity(LogCeverity::Info)
         .function("delete_user")
         .description("User account deleted")
-        .metadata(json!({"deleted_user_id": query.user_id}))
+        .metadata(json!({"deleted_user_id": query.user_id, "erased": erase}))
         .log_async();
 
     Ok(StatusCode::OK)
@@ -271,9 +396,12 @@ pub async fn auth0_trigger_endpoint(
     Path(pseudo_id): Path<String>,
     Json(auth0_user): Json<Auth0User>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let SubjectId::Integration(_intname) = &subject_id else {
-        return Err(ServerError::AccessDenied);
-    };
+    allowed_integrations(
+        &state,
+        &subject_id,
+        &[IntegrationName::Auth0],
+        "auth0_trigger_endpoint",
+    )?;
 
     debug!("Recieved pseudo id from auth0: {}", pseudo_id);
     info!(
@@ -281,15 +409,53 @@ pub async fn auth0_trigger_endpoint(
         auth0_user.email.clone().unwrap_or("[no email]".to_string())
     );
 
-    let pseudo_id = Uuid::from_str(&pseudo_id)
-        .map_err(|_| ServerError::Api(StatusCode::BAD_REQUEST, "Invalid pseudo_id".into()))?;
+    // A malformed id means the Auth0 action is misconfigured, not a client error
+    let Ok(pseudo_id) = parse_guest_id(&pseudo_id) else {
+        state
+            .syslog()
+            .subject(subject_id)
+            .action(LogAction::Create)
+            .ceverity(LogCeverity::Warning)
+            .function("auth0_trigger_endpoint")
+            .description("Auth0 post-registration trigger sent a malformed pseudo id")
+            .log_async();
+
+        return Err(ServerError::Api(
+            StatusCode::BAD_REQUEST,
+            "Invalid pseudo id".into(),
+        ));
+    };
 
     let mut tx = state.get_pool().begin().await?;
-    let base_user_id = create_base_user(&mut tx, &auth0_user).await?;
+    let (base_user_id, created) = get_or_create_base_user(&mut tx, &auth0_user).await?;
     tx.commit().await?;
 
+    if !created {
+        info!(
+            "Auth0 trigger retried for already registered user {}",
+            base_user_id
+        );
+        return Ok((StatusCode::OK, Json(base_user_id)));
+    }
+
+    let missing_fields = auth0_user.missing_fields();
+    if !missing_fields.is_empty() {
+        warn!(
+            "Auth0 user {} registered without {:?}",
+            auth0_user.auth0_id, missing_fields
+        );
+        state
+            .syslog()
+            .action(LogAction::Create)
+            .ceverity(LogCeverity::Warning)
+            .function("auth0_trigger_endpoint")
+            .description("Auth0 registration is missing profile fields, they are stored as empty")
+            .metadata(json!({"auth0_id": auth0_user.auth0_id, "missing": missing_fields}))
+            .log_async();
+    }
+
     let pool = state.get_pool().clone();
-    tokio::spawn(async move {
+    state.get_tasks().spawn(async move {
         match link_pseudo_to_base_user(&pool, pseudo_id, base_user_id).await {
             Ok(_) => info!(
                 "Linked pseudo user {} to base user {} on registration",
@@ -316,57 +482,47 @@ pub async fn auth0_trigger_endpoint(
 
 async fn list_all_users(
     State(state): State<Arc<AppState>>,
-    Extension(subject_id): Extension<SubjectId>,
+    _user: RegisteredUser,
+    _: Require<policy::ListAllUsers>,
     Extension(claims): Extension<Claims>,
     Query(query): Query<ListUsersQuery>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let SubjectId::BaseUser(_) = subject_id else {
-        return Err(ServerError::AccessDenied);
-    };
-
-    if let Some(missing) = claims.missing_permission([Permission::ReadAdmin]) {
-        return Err(ServerError::Permission(missing));
-    }
+    let include_deleted =
+        query.include_deleted.unwrap_or(false) && policy::IncludeDeleted::allows(&claims);
 
-    let users = list_base_users(state.get_pool(), query).await?;
+    let users = list_base_users(state.get_pool(), query, include_deleted).await?;
     Ok((StatusCode::OK, Json(users)))
 }
 
 async fn get_user_activity_stats(
     State(state): State<Arc<AppState>>,
-    Extension(subject_id): Extension<SubjectId>,
-    Extension(claims): Extension<Claims>,
+    _user: RegisteredUser,
+    _: Require<policy::GetUserActivityStats>,
+    Query(query): Query<ActivityStatsQuery>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let SubjectId::BaseUser(_) = subject_id else {
-        warn!("Unauthorized guest user or integration attempted to access admin endpoint");
-        return Err(ServerError::AccessDenied);
+    // Only the default windows are cached, ranges are rare and always fresh
+    let stats = match query.range()? {
+        Some((from, to)) => {
+            db::user::get_user_activity_stats_between(state.get_pool(), from, to).await?
+        }
+        None => {
+            state
+                .get_stats_cache()
+                .get_or(db::user::get_user_activity_stats(state.get_pool()))
+                .await?
+        }
     };
-
-    if let Some(missing) = claims.missing_permission([Permission::ReadAdmin]) {
-        warn!("User without admin permissions attempted to access admin endpoint");
-        return Err(ServerError::Permission(missing));
-    }
-
-    let stats = db::user::get_user_activity_stats(state.get_pool()).await?;
     Ok((StatusCode::OK, Json(stats)))
 }
 
 async fn update_client_popup(
     State(state): State<Arc<AppState>>,
-    Extension(subject_id): Extension<SubjectId>,
-    Extension(claims): Extension<Claims>,
+    _user: RegisteredUser,
+    _: Require<policy::UpdateClientPopup>,
     Json(payload): Json<ClientPopup>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let SubjectId::BaseUser(_user_id) = subject_id else {
-        return Err(ServerError::AccessDenied);
-    };
-
-    if let Some(missing) = claims.missing_permission([Permission::WriteAdmin]) {
-        return Err(ServerError::Permission(missing));
-    }
-
     let manager = state.get_popup_manager();
-    let popup = manager.update(payload).await;
+    let popup = manager.update(payload).await?;
     debug!("Popup updated successfully");
 
     Ok((StatusCode::OK, Json(popup)))
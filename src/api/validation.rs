@@ -1,10 +1,11 @@
 use axum::{Json, extract::FromRequest};
+use chrono::{NaiveDate, Utc};
 use reqwest::StatusCode;
 use serde::de::DeserializeOwned;
 use tracing::{debug, info};
 use validator::{Validate, ValidationError};
 
-use crate::models::error::ServerError;
+use crate::{config::app_config::CONFIG, models::error::ServerError};
 
 #[derive(Debug)]
 pub struct ValidatedJson<T>(pub T);
@@ -55,7 +56,7 @@ where
 }
 
 /// Format validation errors into a user-friendly message
-fn format_validation_errors(errors: &validator::ValidationErrors) -> String {
+pub fn format_validation_errors(errors: &validator::ValidationErrors) -> String {
     let mut messages = Vec::new();
 
     for (field, field_errors) in errors.field_errors() {
@@ -109,6 +110,29 @@ pub fn validate_username(username: &str) -> Result<(), ValidationError> {
     Ok(())
 }
 
+/// Validate birth date: not in the future and at least `min_user_age` years ago
+pub fn validate_birth_date(birth_date: &NaiveDate) -> Result<(), ValidationError> {
+    check_min_age(
+        *birth_date,
+        Utc::now().date_naive(),
+        CONFIG.server.min_user_age,
+    )
+}
+
+fn check_min_age(
+    birth_date: NaiveDate,
+    today: NaiveDate,
+    min_age: u32,
+) -> Result<(), ValidationError> {
+    match today.years_since(birth_date) {
+        None => Err(ValidationError::new("birth_date_in_future")
+            .with_message("Birth date cannot be in the future".into())),
+        Some(age) if age < min_age => Err(ValidationError::new("birth_date_too_young")
+            .with_message(format!("You must be at least {} years old", min_age).into())),
+        Some(_) => Ok(()),
+    }
+}
+
 /// Validate person name (given_name, family_name): 1-50 chars, letters, spaces, and common name chars
 pub fn validate_person_name(name: &str) -> Result<(), ValidationError> {
     let len = name.trim().len();
@@ -157,3 +181,33 @@ pub fn validate_game_name(name: &str) -> Result<(), ValidationError> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::check_min_age;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn future_birth_date_is_rejected() {
+        let today = date(2026, 6, 1);
+        assert!(check_min_age(date(2026, 6, 2), today, 13).is_err());
+    }
+
+    #[test]
+    fn exactly_minimum_age_is_accepted() {
+        let today = date(2026, 6, 1);
+        assert!(check_min_age(date(2013, 6, 1), today, 13).is_ok());
+    }
+
+    #[test]
+    fn under_minimum_age_is_rejected() {
+        let today = date(2026, 6, 1);
+        let error = check_min_age(date(2013, 6, 2), today, 13).unwrap_err();
+        assert_eq!(error.code, "birth_date_too_young");
+    }
+}
@@ -1,5 +1,14 @@
-use axum::{body::Body, extract::Request, middleware::Next, response::Response};
+use axum::{
+    body::{Body, to_bytes},
+    extract::Request,
+    middleware::Next,
+    response::Response,
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
 use reqwest::StatusCode;
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
 use tracing::{info, warn};
 
 use crate::{
@@ -9,14 +18,32 @@ use crate::{
 };
 
 static AUTH0_WEBHOOK_KEY: &str = "Auth0-Webhook-Key";
+static AUTH0_WEBHOOK_SIGNATURE: &str = "Auth0-Webhook-Signature";
+static AUTH0_WEBHOOK_TIMESTAMP: &str = "Auth0-Webhook-Timestamp";
+
+/// Webhook payloads are small user records, anything larger is rejected unread
+const MAX_WEBHOOK_BODY_BYTES: usize = 64 * 1024;
 
 pub async fn webhook_mw(mut req: Request<Body>, next: Next) -> Result<Response, ServerError> {
+    req = match CONFIG.auth0.webhook_signing_secret.as_deref() {
+        Some(secret) => verify_signed_request(req, secret).await?,
+        None => verify_static_key(req)?,
+    };
+
+    let subject = SubjectId::Integration(IntegrationName::Auth0);
+    info!("Request by subject: {:?}", subject);
+    req.extensions_mut().insert(subject);
+
+    Ok(next.run(req).await)
+}
+
+fn verify_static_key(req: Request<Body>) -> Result<Request<Body>, ServerError> {
     let webhook_header = extract_header(AUTH0_WEBHOOK_KEY, req.headers()).ok_or_else(|| {
         ServerError::Api(StatusCode::UNAUTHORIZED, "Webhook key not present".into())
     })?;
 
-    let valid_key = CONFIG.auth0.webhook_key.to_string();
-    if valid_key != webhook_header {
+    let valid_key = CONFIG.auth0.webhook_key.as_bytes();
+    if !bool::from(valid_key.ct_eq(webhook_header.as_bytes())) {
         warn!("Received request with invalid webhook key");
         return Err(ServerError::Api(
             StatusCode::UNAUTHORIZED,
@@ -24,9 +51,134 @@ pub async fn webhook_mw(mut req: Request<Body>, next: Next) -> Result<Response,
         ));
     }
 
-    let subject = SubjectId::Integration(IntegrationName::Auth0);
-    info!("Request by subject: {:?}", subject);
-    req.extensions_mut().insert(subject);
+    Ok(req)
+}
 
-    Ok(next.run(req).await)
+/// Buffers the body to check its signature, then hands an identical request on
+async fn verify_signed_request(
+    req: Request<Body>,
+    secret: &str,
+) -> Result<Request<Body>, ServerError> {
+    let signature = extract_header(AUTH0_WEBHOOK_SIGNATURE, req.headers()).ok_or_else(|| {
+        ServerError::Api(
+            StatusCode::UNAUTHORIZED,
+            "Webhook signature not present".into(),
+        )
+    })?;
+    let timestamp = extract_header(AUTH0_WEBHOOK_TIMESTAMP, req.headers()).ok_or_else(|| {
+        ServerError::Api(
+            StatusCode::UNAUTHORIZED,
+            "Webhook timestamp not present".into(),
+        )
+    })?;
+
+    let (parts, body) = req.into_parts();
+    let bytes = to_bytes(body, MAX_WEBHOOK_BODY_BYTES).await.map_err(|_| {
+        ServerError::Api(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "Webhook body too large".into(),
+        )
+    })?;
+
+    if let Err(reason) = verify_signature(
+        secret.as_bytes(),
+        &timestamp,
+        Utc::now().timestamp(),
+        &bytes,
+        &signature,
+        CONFIG.auth0.webhook_max_skew_secs,
+    ) {
+        warn!("Rejected signed webhook: {}", reason);
+        return Err(ServerError::Api(
+            StatusCode::UNAUTHORIZED,
+            "Invalid webhook signature".into(),
+        ));
+    }
+
+    Ok(Request::from_parts(parts, Body::from(bytes)))
+}
+
+/// Checks a hex encoded HMAC-SHA256 over `{timestamp}.{body}`. The timestamp is
+/// part of the signed message so it cannot be swapped to dodge the replay window.
+fn verify_signature(
+    secret: &[u8],
+    timestamp: &str,
+    now: i64,
+    body: &[u8],
+    signature: &str,
+    max_skew_secs: u64,
+) -> Result<(), &'static str> {
+    let sent_at = timestamp
+        .trim()
+        .parse::<i64>()
+        .map_err(|_| "timestamp is not a number")?;
+    if now.abs_diff(sent_at) > max_skew_secs {
+        return Err("timestamp outside the accepted window");
+    }
+
+    let signature = hex::decode(signature.trim()).map_err(|_| "signature is not hex")?;
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).map_err(|_| "invalid signing secret")?;
+    mac.update(timestamp.trim().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    // verify_slice compares in constant time
+    mac.verify_slice(&signature)
+        .map_err(|_| "signature does not match")
+}
+
+#[cfg(test)]
+mod tests {
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    use super::verify_signature;
+
+    const SECRET: &[u8] = b"test-signing-secret";
+    const NOW: i64 = 1_700_000_000;
+    const BODY: &[u8] = br#"{"user_id":"auth0|abc"}"#;
+
+    fn sign(timestamp: i64, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET).unwrap();
+        mac.update(format!("{}.", timestamp).as_bytes());
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    #[test]
+    fn valid_signature_is_accepted() {
+        let signature = sign(NOW - 10, BODY);
+        let result = verify_signature(SECRET, &(NOW - 10).to_string(), NOW, BODY, &signature, 300);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn tampered_body_is_rejected() {
+        let signature = sign(NOW, BODY);
+        let tampered = br#"{"user_id":"auth0|xyz"}"#;
+        let result = verify_signature(SECRET, &NOW.to_string(), NOW, tampered, &signature, 300);
+        assert_eq!(result, Err("signature does not match"));
+    }
+
+    #[test]
+    fn swapped_timestamp_is_rejected() {
+        let signature = sign(NOW - 1000, BODY);
+        let result = verify_signature(SECRET, &NOW.to_string(), NOW, BODY, &signature, 300);
+        assert_eq!(result, Err("signature does not match"));
+    }
+
+    #[test]
+    fn stale_signature_is_rejected() {
+        let sent_at = NOW - 301;
+        let signature = sign(sent_at, BODY);
+        let result = verify_signature(SECRET, &sent_at.to_string(), NOW, BODY, &signature, 300);
+        assert_eq!(result, Err("timestamp outside the accepted window"));
+    }
+
+    #[test]
+    fn malformed_headers_are_rejected() {
+        assert!(verify_signature(SECRET, "yesterday", NOW, BODY, &sign(NOW, BODY), 300).is_err());
+        assert!(verify_signature(SECRET, &NOW.to_string(), NOW, BODY, "not-hex", 300).is_err());
+    }
 }
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{net::IpAddr, sync::Arc};
 
 use chrono::{Datelike, TimeZone, Utc};
 use chrono_tz::Europe::Oslo;
@@ -7,74 +7,256 @@ use serde_json::json;
 use reqwest::Client;
 use sqlx::{Pool, Postgres};
 use tokio::sync::{Mutex, mpsc};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
 use crate::{
-    api::gs_client::GSClient,
+    api::gs_client::{GSClient, http_client},
     config::app_config::CONFIG,
     db::{
         game_base::{delete_stale_games, fill_rounds_pool},
         imposter_game::get_imposter_game_by_id,
         quiz_game::get_quiz_game_by_id,
         spin_game::get_spin_game_by_id,
+        system_log::purge_old_logs,
     },
     models::{
         auth::Jwks,
         error::ServerError,
         game_base::{GameBase, GameType, PagedResponse},
-        system_log::{LogAction, LogCeverity},
+        system_log::{LogAction, LogCeverity, LogMetadata},
+        user::ActivityStats,
     },
     service::{
-        cache::GustCache, key_vault::KeyVault, popup_manager::PopupManager,
-        system_log_builder::SystemLogBuilder,
+        activity_batcher::ActivityBatcher, cache::GustCache, circuit_breaker::CircuitBreaker,
+        in_flight::InFlightSet, integration_registry::IntegrationRegistry, jwks_store::JwksStore,
+        key_vault::KeyVault, log_buffer::LogBuffer, player_count::PlayerCounts,
+        popup_manager::PopupManager, rate_limiter::RateLimiter, recently_seen::RecentlySeen,
+        stats_cache::StatsCache, system_log_builder::SystemLogBuilder, task_tracker::TaskTracker,
     },
 };
 
+static JWKS_REFRESH_SECS: u64 = 6 * 60 * 60;
+static ACTIVITY_FLUSH_SECS: u64 = 5;
+
 type RoundPoolSender = Arc<Mutex<Option<mpsc::UnboundedSender<(Uuid, GameType)>>>>;
 
 #[derive(Clone)]
 pub struct AppState {
     pool: Pool<Postgres>,
-    jwks: Jwks,
+    jwks: JwksStore,
     client: Client,
     gs_client: GSClient,
     page_cache: Arc<GustCache<PagedResponse<GameBase>>>,
+    stats_cache: StatsCache<ActivityStats>,
+    health_cache: StatsCache<serde_json::Value>,
     key_vault: Arc<KeyVault>,
     popup_manager: PopupManager,
 
+    /// Machine client subjects, editable at runtime through `/integrations`
+    integrations: IntegrationRegistry,
+
+    /// Pseudo user ids with an `ensure_pseudo_user` upsert in progress
+    pseudo_in_flight: InFlightSet<Uuid>,
+
+    /// Pseudo ids upserted within the last minute, these skip the upsert entirely
+    recent_pseudo_ids: RecentlySeen<Uuid>,
+
+    /// Guest `last_active` bumps, written in batches by `spawn_activity_flush`
+    pseudo_activity: ActivityBatcher,
+
+    /// Background writes that are drained on shutdown
+    tasks: TaskTracker,
+
+    /// Batches system log inserts from `syslog().log_async()`
+    log_buffer: LogBuffer,
+
+    /// Players per interactive session, used to turn away joins when full
+    player_counts: PlayerCounts,
+
+    /// Throttles username availability checks per user to curb enumeration
+    username_limiter: RateLimiter<Uuid>,
+
+    /// Caps how often a single guest id may trigger a pseudo user upsert
+    pseudo_upsert_limiter: RateLimiter<Uuid>,
+
+    /// Throttles game key checks per subject so keys can not be enumerated
+    key_check_limiter: RateLimiter<Uuid>,
+
+    /// Throttles public game tip submissions per client IP
+    tip_limiter: RateLimiter<IpAddr>,
+
     /// Channel used to queue up a new game to write its rounds to the round pool
     round_pool_sender: RoundPoolSender,
+
+    /// Cancelled on shutdown to stop the spawned cron jobs
+    shutdown: CancellationToken,
 }
 
-impl AppState {
-    pub async fn from_pool(pool: Pool<Postgres>) -> Result<Arc<Self>, ServerError> {
-        let client = Client::new();
-        let gs_client = GSClient::new(&CONFIG.server.gs_domain, client.clone());
+/// Assembles an `AppState`, falling back to the production component for
+/// anything that was not injected. Injection is only available to tests.
+pub struct AppStateBuilder {
+    pool: Pool<Postgres>,
+    jwks: Option<Jwks>,
+    client: Option<Client>,
+    gs_client: Option<GSClient>,
+    page_cache: Option<Arc<GustCache<PagedResponse<GameBase>>>>,
+    key_vault: Option<Arc<KeyVault>>,
+    popup_manager: Option<PopupManager>,
+    integrations: Option<IntegrationRegistry>,
+}
+
+impl AppStateBuilder {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self {
+            pool,
+            jwks: None,
+            client: None,
+            gs_client: None,
+            page_cache: None,
+            key_vault: None,
+            popup_manager: None,
+            integrations: None,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn jwks(mut self, jwks: Jwks) -> Self {
+        self.jwks = Some(jwks);
+        self
+    }
+
+    #[cfg(test)]
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    #[cfg(test)]
+    pub fn gs_client(mut self, gs_client: GSClient) -> Self {
+        self.gs_client = Some(gs_client);
+        self
+    }
+
+    #[cfg(test)]
+    pub fn page_cache(mut self, page_cache: GustCache<PagedResponse<GameBase>>) -> Self {
+        self.page_cache = Some(Arc::new(page_cache));
+        self
+    }
+
+    #[cfg(test)]
+    pub fn key_vault(mut self, key_vault: KeyVault) -> Self {
+        self.key_vault = Some(Arc::new(key_vault));
+        self
+    }
+
+    #[cfg(test)]
+    pub fn popup_manager(mut self, popup_manager: PopupManager) -> Self {
+        self.popup_manager = Some(popup_manager);
+        self
+    }
+
+    #[cfg(test)]
+    pub fn integrations(mut self, integrations: IntegrationRegistry) -> Self {
+        self.integrations = Some(integrations);
+        self
+    }
+
+    pub async fn build(self) -> Result<Arc<AppState>, ServerError> {
+        let client = match self.client {
+            Some(client) => client,
+            None => http_client(
+                std::time::Duration::from_millis(CONFIG.server.gs_request_timeout_ms),
+                std::time::Duration::from_millis(CONFIG.server.gs_connect_timeout_ms),
+            )?,
+        };
+
+        let gs_client = match self.gs_client {
+            Some(gs_client) => gs_client,
+            None => GSClient::new(&CONFIG.server.gs_domain, client.clone()).with_breaker(
+                CircuitBreaker::new(
+                    CONFIG.server.gs_breaker_failure_threshold,
+                    std::time::Duration::from_secs(CONFIG.server.gs_breaker_cooldown_secs),
+                ),
+            ),
+        };
 
         let jwks_url = format!("{}.well-known/jwks.json", CONFIG.auth0.domain);
-        let response = client.get(jwks_url).send().await?;
-        let jwks = response.json::<Jwks>().await?;
-        let page_cache = Arc::new(GustCache::from_ttl(120));
-        let key_vault = Arc::new(KeyVault::load_words(&pool).await?);
-        let popup_manager = PopupManager::new();
+        let jwks = match self.jwks {
+            Some(jwks) => jwks,
+            None => JwksStore::fetch(&client, &jwks_url).await?,
+        };
+        jwks.ensure_not_empty()?;
+        let jwks = JwksStore::new(jwks, client.clone(), jwks_url);
+
+        let page_cache = self
+            .page_cache
+            .unwrap_or_else(|| Arc::new(GustCache::from_ttl(120)));
+
+        let key_vault = match self.key_vault {
+            Some(key_vault) => key_vault,
+            None => Arc::new(KeyVault::load_words(&self.pool).await?),
+        };
+
+        let popup_manager = match self.popup_manager {
+            Some(popup_manager) => popup_manager,
+            None => PopupManager::load(&self.pool).await,
+        };
+
+        let integrations = match self.integrations {
+            Some(integrations) => integrations,
+            None => IntegrationRegistry::load(&self.pool).await,
+        };
         let round_pool_sender = Arc::new(Mutex::new(None));
+        let log_buffer = LogBuffer::spawn(self.pool.clone());
 
-        Ok(Arc::new(Self {
-            pool,
+        Ok(Arc::new(AppState {
+            pool: self.pool,
             jwks,
             client,
             gs_client,
             page_cache,
+            stats_cache: StatsCache::new(std::time::Duration::from_secs(
+                CONFIG.server.stats_cache_ttl_secs,
+            )),
+            health_cache: StatsCache::new(std::time::Duration::from_secs(
+                CONFIG.server.health_cache_ttl_secs,
+            )),
             key_vault,
             popup_manager,
+            integrations,
+            pseudo_in_flight: InFlightSet::new(),
+            recent_pseudo_ids: RecentlySeen::new(std::time::Duration::from_secs(60), 100_000),
+            pseudo_activity: ActivityBatcher::new(),
+            tasks: TaskTracker::with_limit(CONFIG.server.max_background_tasks),
+            log_buffer,
+            player_counts: PlayerCounts::new(CONFIG.server.max_players),
+            username_limiter: RateLimiter::new(20, std::time::Duration::from_secs(3)),
+            pseudo_upsert_limiter: RateLimiter::new(5, std::time::Duration::from_secs(600)),
+            key_check_limiter: RateLimiter::new(30, std::time::Duration::from_secs(2)),
+            tip_limiter: RateLimiter::new(
+                CONFIG.server.tip_submissions_per_hour,
+                std::time::Duration::from_secs(3600) / CONFIG.server.tip_submissions_per_hour,
+            ),
             round_pool_sender,
+            shutdown: CancellationToken::new(),
         }))
     }
+}
+
+impl AppState {
+    pub async fn from_pool(pool: Pool<Postgres>) -> Result<Arc<Self>, ServerError> {
+        AppStateBuilder::new(pool).build().await
+    }
 
     #[cfg(test)]
     pub async fn from_connection_string(connection_string: &str) -> Result<Arc<Self>, ServerError> {
-        let pool = Pool::<Postgres>::connect(connection_string).await?;
+        let pool = CONFIG
+            .database
+            .pool_options()
+            .connect(connection_string)
+            .await?;
         Self::from_pool(pool).await
     }
 
@@ -82,7 +264,12 @@ impl AppState {
         &self.pool
     }
 
-    pub fn get_jwks(&self) -> &Jwks {
+    /// Snapshot of the current signing keys, these may be replaced by a refresh
+    pub fn get_jwks(&self) -> Jwks {
+        self.jwks.snapshot()
+    }
+
+    pub fn get_jwks_store(&self) -> &JwksStore {
         &self.jwks
     }
 
@@ -90,6 +277,14 @@ impl AppState {
         &self.page_cache
     }
 
+    pub fn get_stats_cache(&self) -> &StatsCache<ActivityStats> {
+        &self.stats_cache
+    }
+
+    pub fn get_health_cache(&self) -> &StatsCache<serde_json::Value> {
+        &self.health_cache
+    }
+
     pub fn get_client(&self) -> &Client {
         &self.client
     }
@@ -99,7 +294,7 @@ impl AppState {
     }
 
     pub fn syslog(&self) -> SystemLogBuilder {
-        SystemLogBuilder::new(self.get_pool())
+        SystemLogBuilder::new(self.get_pool()).buffered(&self.log_buffer)
     }
 
     pub fn get_vault(&self) -> &KeyVault {
@@ -110,20 +305,152 @@ impl AppState {
         &self.popup_manager
     }
 
+    pub fn get_integrations(&self) -> &IntegrationRegistry {
+        &self.integrations
+    }
+
+    pub fn get_pseudo_in_flight(&self) -> &InFlightSet<Uuid> {
+        &self.pseudo_in_flight
+    }
+
+    pub fn get_player_counts(&self) -> &PlayerCounts {
+        &self.player_counts
+    }
+
+    pub fn get_username_limiter(&self) -> &RateLimiter<Uuid> {
+        &self.username_limiter
+    }
+
+    pub fn get_pseudo_upsert_limiter(&self) -> &RateLimiter<Uuid> {
+        &self.pseudo_upsert_limiter
+    }
+
+    pub fn get_key_check_limiter(&self) -> &RateLimiter<Uuid> {
+        &self.key_check_limiter
+    }
+
+    pub fn get_tip_limiter(&self) -> &RateLimiter<IpAddr> {
+        &self.tip_limiter
+    }
+
+    pub fn get_recent_pseudo_ids(&self) -> &RecentlySeen<Uuid> {
+        &self.recent_pseudo_ids
+    }
+
+    pub fn get_pseudo_activity(&self) -> &ActivityBatcher {
+        &self.pseudo_activity
+    }
+
+    pub fn get_tasks(&self) -> &TaskTracker {
+        &self.tasks
+    }
+
+    /// Stops the cron jobs and the key vault cleanup, in-flight requests and
+    /// tracked background writes are left to `drain_tasks`
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+        self.key_vault.shutdown();
+    }
+
+    /// Writes any system logs still sitting in the buffer
+    pub async fn flush_logs(&self) {
+        self.log_buffer.flush().await;
+    }
+
+    /// Writes guest activity recorded since the last periodic flush
+    pub async fn flush_activity(&self) {
+        if let Err(e) = self.pseudo_activity.flush(&self.pool).await {
+            warn!("Failed to flush pseudo user activity on shutdown: {}", e);
+        }
+    }
+
+    /// Waits for tracked background writes before the process exits
+    pub async fn drain_tasks(&self) {
+        let pending = self.tasks.running();
+        if pending == 0 {
+            return;
+        }
+
+        info!("Draining {} background task(s) before shutdown", pending);
+        let timeout = std::time::Duration::from_secs(CONFIG.server.shutdown_drain_secs);
+        if !self.tasks.drain(timeout).await {
+            warn!(
+                "Shutdown drain timed out with {} task(s) still running",
+                self.tasks.running()
+            );
+        }
+    }
+
+    /// Picks up rotated keys even if no token with a new kid shows up
+    pub fn spawn_jwks_refresh(&self) {
+        let jwks = self.jwks.clone();
+        let shutdown = self.shutdown.clone();
+
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(std::time::Duration::from_secs(JWKS_REFRESH_SECS)) => {}
+                }
+                jwks.refresh().await;
+            }
+        });
+    }
+
+    pub fn spawn_activity_flush(&self) {
+        self.pseudo_activity.spawn(
+            self.pool.clone(),
+            std::time::Duration::from_secs(ACTIVITY_FLUSH_SECS),
+            self.shutdown.clone(),
+        );
+    }
+
     pub fn spawn_game_cleanup(&self) {
         let pool = self.get_pool().clone();
+        let page_cache = self.page_cache.clone();
+        let player_counts = self.player_counts.clone();
+        let shutdown = self.shutdown.clone();
 
         tokio::spawn(async move {
             loop {
-                let delay_secs = secs_until_0500_oslo();
-                tokio::time::sleep(std::time::Duration::from_secs(delay_secs)).await;
+                let delay_secs = CONFIG
+                    .server
+                    .game_cleanup_interval_secs
+                    .unwrap_or_else(secs_until_0500_oslo);
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(std::time::Duration::from_secs(delay_secs)) => {}
+                }
+
+                // Abandoned sessions are never freed, so their counts go here
+                let expired = player_counts.purge_stale();
+                if expired > 0 {
+                    info!("Game cleanup: dropped {} stale player count(s)", expired);
+                }
 
                 let retention_days = CONFIG.server.active_game_retention;
                 match delete_stale_games(&pool, retention_days).await {
-                    Ok(n) => info!(
-                        "Game cleanup: purged {} stale game(s) (retention {}d)",
-                        n, retention_days
-                    ),
+                    Ok(n) => {
+                        info!(
+                            "Game cleanup: purged {} stale game(s) (retention {}d)",
+                            n, retention_days
+                        );
+                        SystemLogBuilder::new(&pool)
+                            .action(LogAction::Delete)
+                            .ceverity(LogCeverity::Info)
+                            .function("spawn_game_cleanup")
+                            .description("Purged stale games from database")
+                            .metadata(LogMetadata::count_with(
+                                n as i64,
+                                json!({"retention_days": retention_days}),
+                            ))
+                            .log_async();
+
+                        // Purged games can sit on any cached page
+                        if n > 0 {
+                            page_cache.clear();
+                        }
+                    }
                     Err(e) => {
                         warn!("Game cleanup failed: {}", e);
                         let _ = SystemLogBuilder::new(&pool)
@@ -131,7 +458,53 @@ impl AppState {
                             .ceverity(LogCeverity::Warning)
                             .function("spawn_game_cleanup")
                             .description("Failed to purge stale games from database")
-                            .metadata(json!({"error": e.to_string()}))
+                            .metadata(LogMetadata::error(e))
+                            .log()
+                            .await;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Purges system logs past their severity's retention, daily at 05:00 Oslo time
+    pub fn spawn_log_cleanup(&self) {
+        let pool = self.get_pool().clone();
+        let shutdown = self.shutdown.clone();
+
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(std::time::Duration::from_secs(secs_until_0500_oslo())) => {}
+                }
+
+                let retention = &CONFIG.log_retention;
+                match purge_old_logs(&pool, retention, Utc::now()).await {
+                    Ok(purged) => {
+                        info!(
+                            "Log cleanup: purged {} info, {} warning and {} critical log(s)",
+                            purged.info, purged.warning, purged.critical
+                        );
+                        SystemLogBuilder::new(&pool)
+                            .action(LogAction::Delete)
+                            .ceverity(LogCeverity::Info)
+                            .function("spawn_log_cleanup")
+                            .description("Purged system logs past their retention")
+                            .metadata(LogMetadata::count_with(
+                                purged.info + purged.warning + purged.critical,
+                                json!({"purged": purged, "retention": retention}),
+                            ))
+                            .log_async();
+                    }
+                    Err(e) => {
+                        warn!("Log cleanup failed: {}", e);
+                        let _ = SystemLogBuilder::new(&pool)
+                            .action(LogAction::Delete)
+                            .ceverity(LogCeverity::Warning)
+                            .function("spawn_log_cleanup")
+                            .description("Failed to purge old system logs")
+                            .metadata(LogMetadata::error(e))
                             .log()
                             .await;
                     }
@@ -171,17 +544,26 @@ impl AppState {
     pub fn spawn_round_pool_job(&self) {
         let pool = self.get_pool().clone();
         let sender = self.round_pool_sender.clone();
+        let shutdown = self.shutdown.clone();
 
         tokio::spawn(async move {
             loop {
                 let worker_pool = pool.clone();
                 let worker_sender = sender.clone();
 
-                let worker = tokio::spawn(async move {
+                let mut worker = tokio::spawn(async move {
                     Self::run_round_pool_supervisor(&worker_pool, worker_sender).await;
                 });
 
-                match worker.await {
+                let result = tokio::select! {
+                    _ = shutdown.cancelled() => {
+                        worker.abort();
+                        break;
+                    }
+                    result = &mut worker => result,
+                };
+
+                match result {
                     Ok(()) => {
                         warn!("Round pool supervisor task exited; restarting");
                         SystemLogBuilder::new(&pool)
@@ -200,7 +582,7 @@ impl AppState {
                             .description(
                                 "Round pool supervisor task panicked and is being restarted",
                             )
-                            .metadata(json!({"error": e.to_string(), "panic": e.is_panic()}))
+                            .metadata(LogMetadata::task_failed(&e))
                             .log_async();
                     }
                 }
@@ -221,7 +603,7 @@ impl AppState {
                     .ceverity(LogCeverity::Warning)
                     .function("run_round_pool_supervisor")
                     .description("Round pool worker failed and is being restarted")
-                    .metadata(json!({"error": e.to_string()}))
+                    .metadata(LogMetadata::error(e))
                     .log()
                     .await
                 {
@@ -322,3 +704,68 @@ fn secs_until_0500_oslo() -> u64 {
 
     next_run.signed_duration_since(now_utc).num_seconds().max(0) as u64
 }
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Client;
+    use sqlx::postgres::PgPoolOptions;
+
+    use crate::{
+        api::gs_client::GSClient,
+        models::auth::{Jwk, Jwks},
+        service::{
+            cache::GustCache, integration_registry::IntegrationRegistry, key_vault::KeyVault,
+            popup_manager::PopupManager,
+        },
+    };
+
+    use super::AppStateBuilder;
+
+    fn test_jwk(kid: &str) -> Jwk {
+        Jwk {
+            kid: kid.to_string(),
+            n: String::new(),
+            e: String::from("AQAB"),
+            crv: String::new(),
+            x: String::new(),
+            y: String::new(),
+            kty: String::from("RSA"),
+            alg: String::from("RS256"),
+            use_: String::from("sig"),
+        }
+    }
+
+    #[tokio::test]
+    async fn builder_uses_injected_session_client() {
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+        let vault =
+            KeyVault::from_words(vec![String::from("arg")], vec![String::from("bil")]).unwrap();
+        let jwks = Jwks {
+            keys: vec![test_jwk("first"), test_jwk("second")],
+        };
+        let gs_client = GSClient::new("http://mock-session", Client::new());
+
+        let state = AppStateBuilder::new(pool)
+            .client(Client::new())
+            .page_cache(GustCache::from_ttl(5))
+            .jwks(jwks)
+            .gs_client(gs_client)
+            .key_vault(vault)
+            .popup_manager(PopupManager::new())
+            .integrations(IntegrationRegistry::new(&[]))
+            .build()
+            .await
+            .unwrap();
+
+        assert!(format!("{:?}", state.get_gs_client()).contains("http://mock-session"));
+        assert_eq!(state.get_jwks().keys[0].kid, "first");
+        assert!(
+            state
+                .get_vault()
+                .key_active(&(String::from("arg"), String::from("bil")))
+                .is_none()
+        );
+    }
+}
@@ -1,12 +1,21 @@
 use core::fmt;
-use std::env;
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    net::IpAddr,
+    time::Duration,
+};
 
 use config::{Config, ConfigError, Environment, File};
+use jsonwebtoken::Algorithm;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPoolOptions;
 use tracing::debug;
 
-use crate::models::integration::IntegrationConfig;
+use crate::models::{
+    game_base::GameType, integration::IntegrationConfig, permission::PolicyRoute, user::Permission,
+};
 
 pub static CONFIG: Lazy<AppConfig> =
     Lazy::new(|| AppConfig::load().unwrap_or_else(|e| panic!("{}", e)));
@@ -41,7 +50,96 @@ pub struct AppConfig {
     pub server: ServerConfig,
     pub auth0: Auth0Config,
     pub database_url: String,
+    #[serde(default)]
+    pub database: DatabaseConfig,
+    #[serde(default)]
+    pub log_retention: LogRetentionConfig,
     pub integrations: Vec<IntegrationConfig>,
+    /// Per-route overrides of the default permission requirements
+    #[serde(default)]
+    pub permissions: HashMap<PolicyRoute, HashSet<Permission>>,
+}
+
+fn default_max_connections() -> u32 {
+    10
+}
+
+fn default_acquire_timeout_secs() -> u64 {
+    5
+}
+
+fn default_idle_timeout_secs() -> u64 {
+    600
+}
+
+/// Connection pool sizing, the url itself stays in `database_url`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabaseConfig {
+    #[serde(default = "default_max_connections")]
+    pub max_connections: u32,
+    /// Connections kept open even when idle
+    #[serde(default)]
+    pub min_connections: u32,
+    /// How long a request waits for a free connection before failing
+    #[serde(default = "default_acquire_timeout_secs")]
+    pub acquire_timeout_secs: u64,
+    /// Idle connections above `min_connections` are closed after this long
+    #[serde(default = "default_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: default_max_connections(),
+            min_connections: 0,
+            acquire_timeout_secs: default_acquire_timeout_secs(),
+            idle_timeout_secs: default_idle_timeout_secs(),
+        }
+    }
+}
+
+impl DatabaseConfig {
+    pub fn pool_options(&self) -> PgPoolOptions {
+        PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(Duration::from_secs(self.acquire_timeout_secs))
+            .idle_timeout(Duration::from_secs(self.idle_timeout_secs))
+    }
+}
+
+fn default_info_log_days() -> u16 {
+    14
+}
+
+fn default_warning_log_days() -> u16 {
+    60
+}
+
+fn default_critical_log_days() -> u16 {
+    180
+}
+
+/// Days system logs of each severity are kept before the nightly purge
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogRetentionConfig {
+    #[serde(default = "default_info_log_days")]
+    pub info_days: u16,
+    #[serde(default = "default_warning_log_days")]
+    pub warning_days: u16,
+    #[serde(default = "default_critical_log_days")]
+    pub critical_days: u16,
+}
+
+impl Default for LogRetentionConfig {
+    fn default() -> Self {
+        Self {
+            info_days: default_info_log_days(),
+            warning_days: default_warning_log_days(),
+            critical_days: default_critical_log_days(),
+        }
+    }
 }
 
 fn default_address() -> String {
@@ -56,6 +154,22 @@ fn default_page_size() -> u16 {
     20
 }
 
+fn default_max_page_size() -> u16 {
+    100
+}
+
+fn default_jwt_algorithms() -> Vec<Algorithm> {
+    vec![Algorithm::RS256]
+}
+
+fn default_jwt_leeway_secs() -> u64 {
+    60
+}
+
+fn default_webhook_max_skew_secs() -> u64 {
+    300
+}
+
 fn default_runtime() -> Runtime {
     Runtime::Dev
 }
@@ -64,6 +178,54 @@ fn default_active_game_retention() -> u16 {
     21
 }
 
+fn default_shutdown_drain_secs() -> u64 {
+    10
+}
+
+fn default_enabled_game_types() -> Vec<GameType> {
+    GameType::ALL.to_vec()
+}
+
+fn default_max_players() -> u16 {
+    30
+}
+
+fn default_max_background_tasks() -> usize {
+    512
+}
+
+fn default_stats_cache_ttl_secs() -> u64 {
+    30
+}
+
+fn default_health_cache_ttl_secs() -> u64 {
+    5
+}
+
+fn default_tip_submissions_per_hour() -> u32 {
+    5
+}
+
+fn default_min_user_age() -> u32 {
+    13
+}
+
+fn default_gs_request_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_gs_connect_timeout_ms() -> u64 {
+    2000
+}
+
+fn default_gs_breaker_failure_threshold() -> u32 {
+    5
+}
+
+fn default_gs_breaker_cooldown_secs() -> u64 {
+    30
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ServerConfig {
     #[serde(default = "default_address")]
@@ -73,8 +235,52 @@ pub struct ServerConfig {
     pub gs_domain: String,
     #[serde(default = "default_page_size")]
     pub page_size: u16,
+    /// Largest `page_size` a client may ask for, larger requests are clamped
+    #[serde(default = "default_max_page_size")]
+    pub max_page_size: u16,
+    /// Days since a game was last played before the cleanup job purges it
     #[serde(default = "default_active_game_retention")]
     pub active_game_retention: u16,
+    /// Seconds between cleanup runs, unset runs it daily at 05:00 Oslo time
+    #[serde(default)]
+    pub game_cleanup_interval_secs: Option<u64>,
+    /// How long shutdown waits for tracked background tasks
+    #[serde(default = "default_shutdown_drain_secs")]
+    pub shutdown_drain_secs: u64,
+    /// Game types players may join, all of them unless configured
+    #[serde(default = "default_enabled_game_types")]
+    pub enabled_game_types: Vec<GameType>,
+    #[serde(default = "default_max_players")]
+    pub max_players: u16,
+    /// Cap on skippable background tasks such as pseudo user upserts
+    #[serde(default = "default_max_background_tasks")]
+    pub max_background_tasks: usize,
+    /// How long admin activity stats are served from cache, must be nonzero
+    #[serde(default = "default_stats_cache_ttl_secs")]
+    pub stats_cache_ttl_secs: u64,
+    /// How long the detailed health result is reused, including its database check
+    #[serde(default = "default_health_cache_ttl_secs")]
+    pub health_cache_ttl_secs: u64,
+    /// Game tips a single client IP may submit per hour, must be nonzero
+    #[serde(default = "default_tip_submissions_per_hour")]
+    pub tip_submissions_per_hour: u32,
+    /// Proxies allowed to set `X-Forwarded-For`, the header is ignored when empty
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
+    /// Youngest age a birth date may put a user at
+    #[serde(default = "default_min_user_age")]
+    pub min_user_age: u32,
+    /// Total time allowed for an outbound request, e.g. to the session service
+    #[serde(default = "default_gs_request_timeout_ms")]
+    pub gs_request_timeout_ms: u64,
+    #[serde(default = "default_gs_connect_timeout_ms")]
+    pub gs_connect_timeout_ms: u64,
+    /// Consecutive session service failures before calls fail fast
+    #[serde(default = "default_gs_breaker_failure_threshold")]
+    pub gs_breaker_failure_threshold: u32,
+    /// How long the breaker stays open before a single probe is let through
+    #[serde(default = "default_gs_breaker_cooldown_secs")]
+    pub gs_breaker_cooldown_secs: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -87,6 +293,18 @@ pub struct Auth0Config {
     pub mgmt_client_secret: String,
     #[serde(default = "default_runtime")]
     pub runtime: Runtime,
+    /// Signing algorithms accepted for access tokens, only RSA and ECDSA ones are honored
+    #[serde(default = "default_jwt_algorithms")]
+    pub jwt_algorithms: Vec<Algorithm>,
+    /// Clock skew tolerated on `exp` and `nbf` when validating tokens
+    #[serde(default = "default_jwt_leeway_secs")]
+    pub jwt_leeway_secs: u64,
+    /// When set, webhooks must carry an HMAC-SHA256 signature and `webhook_key` is ignored
+    #[serde(default)]
+    pub webhook_signing_secret: Option<String>,
+    /// Oldest signed webhook timestamp accepted, guards against replays
+    #[serde(default = "default_webhook_max_skew_secs")]
+    pub webhook_max_skew_secs: u64,
 }
 
 impl AppConfig {
@@ -99,6 +317,8 @@ impl AppConfig {
             .build()?
             .try_deserialize()?;
 
+        config.validate()?;
+
         debug!(
             "Loaded config: {}",
             serde_json::to_string_pretty(&config).unwrap()
@@ -106,4 +326,53 @@ impl AppConfig {
 
         Ok(config)
     }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.database.max_connections == 0
+            || self.database.min_connections > self.database.max_connections
+        {
+            return Err(ConfigError::Message(
+                "database.max_connections must be nonzero and at least database.min_connections"
+                    .into(),
+            ));
+        }
+
+        let retention = &self.log_retention;
+        if retention.info_days == 0 || retention.warning_days == 0 || retention.critical_days == 0 {
+            return Err(ConfigError::Message(
+                "log_retention windows must be greater than zero".into(),
+            ));
+        }
+
+        if self
+            .auth0
+            .webhook_signing_secret
+            .as_ref()
+            .is_some_and(|secret| secret.is_empty())
+        {
+            return Err(ConfigError::Message(
+                "auth0.webhook_signing_secret must not be empty when set".into(),
+            ));
+        }
+
+        if self.server.page_size == 0 || self.server.page_size > self.server.max_page_size {
+            return Err(ConfigError::Message(
+                "server.page_size must be nonzero and at most server.max_page_size".into(),
+            ));
+        }
+
+        if self.server.stats_cache_ttl_secs == 0 {
+            return Err(ConfigError::Message(
+                "server.stats_cache_ttl_secs must be greater than zero".into(),
+            ));
+        }
+
+        if self.server.tip_submissions_per_hour == 0 {
+            return Err(ConfigError::Message(
+                "server.tip_submissions_per_hour must be greater than zero".into(),
+            ));
+        }
+
+        Ok(())
+    }
 }
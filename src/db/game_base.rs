@@ -1,32 +1,45 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use chrono::{Duration, Utc};
 use serde::{Serialize, de::DeserializeOwned};
-use sqlx::{Executor, Pool, Postgres, QueryBuilder, types::Json};
+use sqlx::{Executor, Pool, Postgres, QueryBuilder, Transaction, types::Json};
 use tracing::warn;
 use uuid::Uuid;
+use validator::Validate;
 
 use crate::{
+    api::validation::format_validation_errors,
     config::app_config::CONFIG,
+    db::{
+        effective_page_size, imposter_game::create_imposter_game, page_window, page_window_with,
+        quiz_game::create_quiz_game, spin_game::create_spin_game,
+    },
     models::{
         error::ServerError,
-        game_base::{GameBase, GamePagedRequest, GameType, PagedResponse},
+        game_base::{
+            BulkSaveRequest, BulkSaveResult, DeleteGameResult, GameBase, GameCategory, GameImport,
+            GamePagedRequest, GameType, ImportResult, PagedResponse, SearchGamesRequest,
+            UpdateGameRequest,
+        },
+        quiz_game::open_questions,
+        spin_game::SpinGame,
     },
 };
 
-pub async fn increment_times_played(
-    pool: &Pool<Postgres>,
-    game_id: Uuid,
-) -> Result<(), ServerError> {
-    let row = sqlx::query!(
+/// Also refreshes `last_played`, which is what keeps a game out of the stale purge
+pub async fn increment_times_played<'e, E>(executor: E, game_id: Uuid) -> Result<(), ServerError>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let row = sqlx::query(
         r#"
         UPDATE "game_base"
-        SET times_played = times_played + 1
+        SET times_played = times_played + 1, last_played = NOW()
         WHERE id = $1
         "#,
-        game_id
     )
-    .execute(pool)
+    .bind(game_id)
+    .execute(executor)
     .await?;
 
     if row.rows_affected() == 0 {
@@ -39,24 +52,54 @@ pub async fn increment_times_played(
     Ok(())
 }
 
+/// Claims `play_token` for the game, returns false when it was already recorded.
+/// A concurrent claim of the same token waits on this row until the transaction
+/// ends, so only one of them sees true. The foreign key to `game_base` is
+/// deferred, so the token can be claimed before the game row is written.
+pub async fn record_game_play(
+    tx: &mut Transaction<'_, Postgres>,
+    play_token: Uuid,
+    game_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query(
+        r#"
+        INSERT INTO "game_play" (token, game_id)
+        VALUES ($1, $2)
+        ON CONFLICT (token) DO NOTHING
+        "#,
+    )
+    .bind(play_token)
+    .bind(game_id)
+    .execute(&mut **tx)
+    .await?;
+
+    if row.rows_affected() == 0 {
+        warn!("Play token {} already recorded", play_token);
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
 pub async fn create_game_base<'e, E>(executor: E, game: &GameBase) -> Result<(), sqlx::Error>
 where
     E: Executor<'e, Database = Postgres>,
 {
     let times_played = 0;
-    let row = sqlx::query!(
+    let row = sqlx::query(
         r#"
-        INSERT INTO "game_base" (id, name, game_type, category, iterations, times_played, last_played)
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        INSERT INTO "game_base" (id, name, game_type, category, iterations, times_played, last_played, description)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
         "#,
-        game.id,
-        game.name,
-        game.game_type as _,
-        game.category as _,
-        game.iterations,
-        times_played,
-        game.last_played
     )
+    .bind(game.id)
+    .bind(&game.name)
+    .bind(game.game_type)
+    .bind(&game.category)
+    .bind(game.iterations)
+    .bind(times_played)
+    .bind(game.last_played)
+    .bind(&game.description)
     .execute(executor)
     .await?;
 
@@ -67,87 +110,365 @@ where
     Ok(())
 }
 
-pub async fn delete_stale_games(
+/// Links the game to its host, a no-op unless the host is a registered user
+pub async fn set_game_creator<'e, E>(
+    executor: E,
+    game_id: Uuid,
+    host_id: Uuid,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    sqlx::query(
+        r#"
+        UPDATE "game_base" base
+        SET created_by = usr.id
+        FROM "base_user" usr
+        WHERE base.id = $1 AND usr.id = $2
+        "#,
+    )
+    .bind(game_id)
+    .bind(host_id)
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Inserts every valid entry in one transaction. Invalid entries are reported
+/// and skipped, while a database error rolls back the whole batch.
+pub async fn import_games(
     pool: &Pool<Postgres>,
-    retention_days: u16,
-) -> Result<u64, sqlx::Error> {
-    let cutoff = Utc::now() - Duration::days(retention_days as i64);
+    imports: &[GameImport],
+) -> Result<Vec<ImportResult>, ServerError> {
+    let mut results = Vec::with_capacity(imports.len());
     let mut tx = pool.begin().await?;
-    let result = sqlx::query!(r#"DELETE FROM "game_base" WHERE last_played < $1"#, cutoff)
-        .execute(&mut *tx)
-        .await?;
+
+    for (index, import) in imports.iter().enumerate() {
+        if let Err(e) = import.validate() {
+            results.push(ImportResult::failed(index, format_validation_errors(&e)));
+            continue;
+        }
+
+        let game = GameBase::new(
+            Uuid::new_v4(),
+            import.name.clone(),
+            import.game_type,
+            import.category.clone(),
+            import.rounds.len() as i32,
+        );
+        create_game_base(tx.as_mut(), &game).await?;
+
+        match import.game_type {
+            GameType::Quiz => {
+                create_quiz_game(tx.as_mut(), game.id, &open_questions(&import.rounds)).await?
+            }
+            GameType::Imposter => {
+                create_imposter_game(tx.as_mut(), game.id, &import.rounds).await?
+            }
+            GameType::Roulette | GameType::Duel => {
+                let spin_game = SpinGame {
+                    id: game.id,
+                    rounds: import.rounds.clone(),
+                };
+                create_spin_game(tx.as_mut(), &spin_game).await?
+            }
+        }
+
+        results.push(ImportResult::imported(index, game.id));
+    }
+
     tx.commit().await?;
-    Ok(result.rows_affected())
+    Ok(results)
 }
 
-pub async fn get_game_page(
+pub async fn get_game_base<'e, E>(
+    executor: E,
+    game_id: Uuid,
+) -> Result<Option<GameBase>, sqlx::Error>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    sqlx::query_as::<_, GameBase>(
+        r#"
+        SELECT id, name, game_type, category, iterations, times_played, last_played, description
+        FROM "game_base"
+        WHERE id = $1
+        "#,
+    )
+    .bind(game_id)
+    .fetch_optional(executor)
+    .await
+}
+
+/// Picks one game at random, skipping games `exclude_saved_by` has saved
+pub async fn get_random_game_base(
     pool: &Pool<Postgres>,
-    request: &GamePagedRequest,
+    game_type: GameType,
+    category: Option<GameCategory>,
+    exclude_saved_by: Option<Uuid>,
+) -> Result<Option<GameBase>, sqlx::Error> {
+    let mut query = QueryBuilder::<Postgres>::new(
+        r#"
+        SELECT id, name, game_type, category, iterations, times_played, last_played, description
+        FROM "game_base" g
+        WHERE g.game_type = "#,
+    );
+    query.push_bind(game_type);
+
+    if let Some(category) = category {
+        query.push(" AND g.category = ").push_bind(category);
+    }
+
+    if let Some(user_id) = exclude_saved_by {
+        query
+            .push(r#" AND NOT EXISTS (SELECT 1 FROM "saved_game" s WHERE s.base_id = g.id AND s.user_id = "#)
+            .push_bind(user_id)
+            .push(")");
+    }
+
+    query.push(" ORDER BY random() LIMIT 1");
+
+    query
+        .build_query_as::<GameBase>()
+        .fetch_optional(pool)
+        .await
+}
+
+/// Matches names containing the query or similar to it by trigrams,
+/// best matches first and then the most played.
+pub async fn search_game_page(
+    pool: &Pool<Postgres>,
+    request: &SearchGamesRequest,
 ) -> Result<PagedResponse<GameBase>, sqlx::Error> {
     let page_size = CONFIG.server.page_size;
-    let limit = page_size + 1;
-    let page_num = request.page_num.unwrap_or(0);
-    let offset = page_size * page_num;
+    let page_num = request.page_num;
+    let (limit, offset) = page_window(page_num);
+    let search = request.query.trim();
+    let pattern = format!(
+        "%{}%",
+        search
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
 
-    let mut where_clause = Vec::new();
+    let mut query = QueryBuilder::<Postgres>::new(
+        r#"
+        SELECT id, name, game_type, category, iterations, times_played, last_played, description
+        FROM "game_base"
+        WHERE (name ILIKE "#,
+    );
+    query
+        .push_bind(pattern)
+        .push(" OR name % ")
+        .push_bind(search)
+        .push(")");
 
-    if let Some(category) = request.category.clone() {
-        where_clause.push(format!("category = '{}'", category));
+    if let Some(game_type) = request.game_type {
+        query.push(" AND game_type = ").push_bind(game_type);
     }
 
-    if let Some(game_type) = request.game_type {
-        where_clause.push(format!("game_type = '{}'", game_type.as_str()));
+    query
+        .push(" ORDER BY similarity(name, ")
+        .push_bind(search)
+        .push(") DESC, times_played DESC LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
+
+    let mut games = query.build_query_as::<GameBase>().fetch_all(pool).await?;
+
+    let has_next = games.len() > page_size as usize;
+    if has_next {
+        games.pop();
     }
 
-    let mut query = r#"
-        SELECT 
-            id,
-            name,
-            game_type,
-            category,
-            iterations,
-            times_played,
-            last_played
-        FROM "game_base"
-        "#
-    .to_string();
+    Ok(PagedResponse::new(page_num, games, has_next))
+}
+
+/// Applies the provided fields, returns `None` when no game matches
+pub async fn patch_game_base(
+    pool: &Pool<Postgres>,
+    game_type: GameType,
+    game_id: Uuid,
+    request: UpdateGameRequest,
+) -> Result<Option<GameBase>, sqlx::Error> {
+    let mut builder: QueryBuilder<'_, Postgres> = QueryBuilder::new(r#"UPDATE "game_base" SET "#);
+    let mut separator = builder.separated(", ");
 
-    if !where_clause.is_empty() {
-        query.push_str(&format!("WHERE {}", where_clause.join(" AND ")));
+    if let Some(name) = request.name {
+        separator.push("name = ").push_bind_unseparated(name);
     }
 
-    query.push_str(&format!(
-        r#"
-        ORDER BY times_played DESC
-        LIMIT {}
-        OFFSET {}
-    "#,
-        limit, offset
-    ));
-
-    let mut games = sqlx::query_as::<_, GameBase>(&query)
-        .fetch_all(pool)
+    if let Some(category) = request.category {
+        separator
+            .push("category = ")
+            .push_bind_unseparated(category);
+    }
+
+    if let Some(description) = request.description {
+        separator
+            .push("description = ")
+            .push_bind_unseparated(description);
+    }
+
+    builder.push(" WHERE id = ").push_bind(game_id);
+    builder.push(" AND game_type = ").push_bind(game_type);
+    builder.push(
+        " RETURNING id, name, game_type, category, iterations, times_played, last_played, description",
+    );
+
+    builder.build_query_as().fetch_optional(pool).await
+}
+
+/// Removes the rounds, saves and base row of a game atomically, nothing is
+/// touched unless the base row exists with the given type
+pub async fn delete_game(
+    pool: &Pool<Postgres>,
+    game_type: GameType,
+    game_id: Uuid,
+) -> Result<DeleteGameResult, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let rounds = delete_game_rounds(&mut tx, game_type, &[game_id]).await?;
+
+    let saves = sqlx::query(r#"DELETE FROM "saved_game" WHERE base_id = $1"#)
+        .bind(game_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let base = sqlx::query(r#"DELETE FROM "game_base" WHERE id = $1 AND game_type = $2"#)
+        .bind(game_id)
+        .bind(game_type)
+        .execute(&mut *tx)
         .await?;
 
+    if base.rows_affected() == 0 {
+        tx.rollback().await?;
+        return Ok(DeleteGameResult::default());
+    }
+
+    tx.commit().await?;
+    Ok(DeleteGameResult {
+        game_deleted: true,
+        rounds_deleted: rounds,
+        saves_removed: saves.rows_affected(),
+    })
+}
+
+/// The game type tables have no cascade from `game_base`, so every delete clears them itself
+async fn delete_game_rounds(
+    tx: &mut Transaction<'_, Postgres>,
+    game_type: GameType,
+    game_ids: &[Uuid],
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(&format!(
+        r#"DELETE FROM "{}" WHERE id = ANY($1)"#,
+        game_type.table_name()
+    ))
+    .bind(game_ids)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Purges games not played within the last `retention_days` days. Games
+/// someone has saved are kept indefinitely so saved lists never dangle
+pub async fn delete_stale_games(
+    pool: &Pool<Postgres>,
+    retention_days: u16,
+) -> Result<u64, sqlx::Error> {
+    let cutoff = Utc::now() - Duration::days(retention_days as i64);
+    let mut tx = pool.begin().await?;
+    let purged: Vec<(Uuid, GameType)> = sqlx::query_as(
+        r#"
+        DELETE FROM "game_base"
+        WHERE last_played < $1
+        AND NOT EXISTS (SELECT 1 FROM "saved_game" WHERE base_id = game_base.id)
+        RETURNING id, game_type
+        "#,
+    )
+    .bind(cutoff)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for game_type in GameType::ALL {
+        let game_ids: Vec<Uuid> = purged
+            .iter()
+            .filter(|(_, purged_type)| *purged_type == game_type)
+            .map(|(id, _)| *id)
+            .collect();
+
+        if !game_ids.is_empty() {
+            delete_game_rounds(&mut tx, game_type, &game_ids).await?;
+        }
+    }
+
+    tx.commit().await?;
+    Ok(purged.len() as u64)
+}
+
+fn push_game_filters(query: &mut QueryBuilder<'_, Postgres>, request: &GamePagedRequest) {
+    let categories = request.category_filter();
+    if !categories.is_empty() {
+        query
+            .push(" AND category = ANY(")
+            .push_bind(categories)
+            .push(")");
+    }
+
+    if let Some(game_type) = request.game_type {
+        query.push(" AND game_type = ").push_bind(game_type);
+    }
+}
+
+pub async fn get_game_page(
+    pool: &Pool<Postgres>,
+    request: &GamePagedRequest,
+) -> Result<PagedResponse<GameBase>, sqlx::Error> {
+    let page_size = effective_page_size(request.page_size);
+    let page_num = request.page_num.unwrap_or(0);
+    let (limit, offset) = page_window_with(page_num, page_size);
+
+    let mut query = QueryBuilder::<Postgres>::new(
+        r#"
+        SELECT id, name, game_type, category, iterations, times_played, last_played, description
+        FROM "game_base"
+        WHERE TRUE"#,
+    );
+    push_game_filters(&mut query, request);
+    query
+        .push(request.sort.unwrap_or_default().order_by())
+        .push(" LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
+
+    let mut count_query =
+        QueryBuilder::<Postgres>::new(r#"SELECT COUNT(*) FROM "game_base" WHERE TRUE"#);
+    push_game_filters(&mut count_query, request);
+
+    let (games, total) = tokio::join!(
+        query.build_query_as::<GameBase>().fetch_all(pool),
+        count_query.build_query_scalar::<i64>().fetch_one(pool)
+    );
+    let mut games = games?;
+
     let has_next = games.len() > page_size as usize;
     if has_next {
         games.pop();
     }
-    let page = PagedResponse {
-        page_num,
-        items: games,
-        has_next,
-        has_prev: page_num > 0,
-    };
-
-    Ok(page)
+    Ok(PagedResponse::with_total(page_num, games, has_next, total?))
 }
 
+/// Returns false if the user had already saved the game
 pub async fn save_game(
     pool: &Pool<Postgres>,
     user_id: Uuid,
     game_id: Uuid,
-) -> Result<(), ServerError> {
+) -> Result<bool, ServerError> {
     let id = Uuid::new_v4();
     let row = sqlx::query!(
         r#"
@@ -162,18 +483,50 @@ pub async fn save_game(
     .execute(pool)
     .await?;
 
-    if row.rows_affected() == 0 {
-        warn!("User has already saved this game or game does not exist");
-    }
+    Ok(row.rows_affected() > 0)
+}
 
-    Ok(())
+pub async fn is_game_saved(
+    pool: &Pool<Postgres>,
+    user_id: Uuid,
+    game_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM "saved_game"
+            WHERE user_id = $1 AND base_id = $2
+        )
+        "#,
+    )
+    .bind(user_id)
+    .bind(game_id)
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn get_saved_game_ids(
+    pool: &Pool<Postgres>,
+    user_id: Uuid,
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT base_id FROM "saved_game"
+        WHERE user_id = $1
+        ORDER BY base_id
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
 }
 
+/// Returns false if there was no save to remove, which is not an error
 pub async fn delete_saved_game(
     pool: &Pool<Postgres>,
     user_id: Uuid,
     game_id: Uuid,
-) -> Result<(), ServerError> {
+) -> Result<bool, ServerError> {
     let row = sqlx::query!(
         r#"
         DELETE FROM "saved_game"
@@ -185,14 +538,72 @@ pub async fn delete_saved_game(
     .execute(pool)
     .await?;
 
-    if row.rows_affected() == 0 {
-        return Err(ServerError::NotFound(format!(
-            "Saved game for user {} and game {} not found",
-            user_id, game_id
-        )));
-    }
+    Ok(row.rows_affected() > 0)
+}
 
-    Ok(())
+/// Whether the user has saved each of the given games, in a single lookup
+pub async fn get_saved_status(
+    pool: &Pool<Postgres>,
+    user_id: Uuid,
+    game_ids: &[Uuid],
+) -> Result<HashMap<Uuid, bool>, sqlx::Error> {
+    let saved: HashSet<Uuid> = sqlx::query_scalar(
+        r#"
+        SELECT base_id FROM "saved_game"
+        WHERE user_id = $1 AND base_id = ANY($2)
+        "#,
+    )
+    .bind(user_id)
+    .bind(game_ids)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .collect();
+
+    Ok(game_ids
+        .iter()
+        .map(|id| (*id, saved.contains(id)))
+        .collect())
+}
+
+/// Applies removals and then additions in one transaction, so an id in both
+/// lists ends up saved. Ids of missing or deleted games are skipped.
+pub async fn apply_saved_games(
+    pool: &Pool<Postgres>,
+    user_id: Uuid,
+    request: &BulkSaveRequest,
+) -> Result<BulkSaveResult, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let removed = sqlx::query(
+        r#"
+        DELETE FROM "saved_game"
+        WHERE user_id = $1 AND base_id = ANY($2)
+        "#,
+    )
+    .bind(user_id)
+    .bind(&request.remove)
+    .execute(tx.as_mut())
+    .await?
+    .rows_affected();
+
+    let added = sqlx::query(
+        r#"
+        INSERT INTO "saved_game" (id, user_id, base_id)
+        SELECT uuid_generate_v4(), $1, base.id
+        FROM "game_base" base
+        WHERE base.id = ANY($2)
+        ON CONFLICT (user_id, base_id) DO NOTHING
+        "#,
+    )
+    .bind(user_id)
+    .bind(&request.add)
+    .execute(tx.as_mut())
+    .await?
+    .rows_affected();
+
+    tx.commit().await?;
+    Ok(BulkSaveResult { added, removed })
 }
 
 pub async fn get_saved_games_page(
@@ -200,17 +611,11 @@ pub async fn get_saved_games_page(
     user_id: Uuid,
     request: GamePagedRequest,
 ) -> Result<PagedResponse<GameBase>, ServerError> {
-    let page_size = CONFIG.server.page_size;
-    let limit = page_size + 1;
+    let page_size = effective_page_size(request.page_size);
     let page_num = request.page_num.unwrap_or(0);
-    let offset = page_num * page_size;
+    let (limit, offset) = page_window_with(page_num, page_size);
 
-    let game_type = match request.game_type {
-        Some(game_type) => format!(" AND base.game_type = '{}'", game_type.as_str()),
-        None => String::new(),
-    };
-
-    let query = format!(
+    let mut query = QueryBuilder::<Postgres>::new(
         r#"
         SELECT
             base.id,
@@ -219,34 +624,78 @@ pub async fn get_saved_games_page(
             base.category,
             base.iterations,
             base.times_played,
-            base.last_played
+            base.last_played,
+            base.description
         FROM "game_base" base
         JOIN "saved_game" saved
         ON base.id = saved.base_id
-        WHERE saved.user_id = $1 {}
-        LIMIT {} OFFSET {}
-        "#,
-        game_type, limit, offset
+        WHERE saved.user_id = "#,
     );
+    query.push_bind(user_id);
 
-    let mut games = sqlx::query_as::<_, GameBase>(&query)
-        .bind(user_id)
-        .bind(game_type)
-        .fetch_all(pool)
-        .await?;
+    if let Some(game_type) = request.game_type {
+        query.push(" AND base.game_type = ").push_bind(game_type);
+    }
+
+    query
+        .push(" ORDER BY base.id LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
 
-    let has_next = games.len() > limit as usize;
+    let mut games = query.build_query_as::<GameBase>().fetch_all(pool).await?;
+
+    // The extra row fetched past the page is only a sentinel
+    let has_next = games.len() > page_size as usize;
     if has_next {
         games.pop();
     }
-    let page = PagedResponse {
-        page_num,
-        items: games,
-        has_next,
-        has_prev: page_num > 0,
-    };
+    Ok(PagedResponse::new(page_num, games, has_next))
+}
+
+pub async fn get_created_games_page(
+    pool: &Pool<Postgres>,
+    user_id: Uuid,
+    request: GamePagedRequest,
+) -> Result<PagedResponse<GameBase>, sqlx::Error> {
+    let page_size = effective_page_size(request.page_size);
+    let page_num = request.page_num.unwrap_or(0);
+    let (limit, offset) = page_window_with(page_num, page_size);
+
+    let mut query = QueryBuilder::<Postgres>::new(
+        r#"
+        SELECT id, name, game_type, category, iterations, times_played, last_played, description
+        FROM "game_base"
+        WHERE created_by = "#,
+    );
+    query.push_bind(user_id);
+
+    if let Some(game_type) = request.game_type {
+        query.push(" AND game_type = ").push_bind(game_type);
+    }
+
+    let categories = request.category_filter();
+    if !categories.is_empty() {
+        query
+            .push(" AND category = ANY(")
+            .push_bind(categories)
+            .push(")");
+    }
+
+    query
+        .push(" ORDER BY last_played DESC, id LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
 
-    Ok(page)
+    let mut games = query.build_query_as::<GameBase>().fetch_all(pool).await?;
+
+    let has_next = games.len() > page_size as usize;
+    if has_next {
+        games.pop();
+    }
+
+    Ok(PagedResponse::new(page_num, games, has_next))
 }
 
 pub async fn get_random_rounds<T>(
@@ -334,23 +783,56 @@ where
 
 #[cfg(test)]
 mod tests {
-    use std::env;
-
-    use dotenvy::dotenv;
     use serde::{Deserialize, Serialize};
-    use sqlx::{Pool, Postgres, types::Json};
+    use sqlx::{Pool, Postgres, QueryBuilder, types::Json};
 
-    use crate::models::game_base::GameType;
+    use uuid::Uuid;
 
-    use super::{fill_rounds_pool, get_random_rounds};
+    use crate::config::app_config::CONFIG;
+    use crate::models::game_base::{
+        BulkSaveRequest, BulkSaveResult, GameBase, GameCategory, GameImport, GamePagedRequest,
+        GameType, SearchGamesRequest, UpdateGameRequest,
+    };
 
-    async fn setup_pool() -> Pool<Postgres> {
-        dotenv().ok();
-        let url = env::var("TERO__DATABASE_URL").expect("TERO__DATABASE_URL not set");
-        sqlx::postgres::PgPoolOptions::new()
-            .connect(&url)
-            .await
-            .unwrap()
+    use crate::tests::db::dev_pool;
+
+    use super::{
+        apply_saved_games, create_game_base, delete_game, delete_saved_game, delete_stale_games,
+        fill_rounds_pool, get_created_games_page, get_game_base, get_game_page,
+        get_random_game_base, get_random_rounds, get_saved_games_page, get_saved_status,
+        import_games, increment_times_played, is_game_saved, patch_game_base, push_game_filters,
+        record_game_play, save_game, search_game_page, set_game_creator,
+    };
+
+    #[test]
+    fn game_filters_bind_every_value() {
+        let request = GamePagedRequest {
+            page_num: None,
+            page_size: None,
+            game_type: Some(GameType::Quiz),
+            category: Some(GameCategory::Girls),
+            categories: Some(vec![GameCategory::Boys]),
+            sort: None,
+        };
+
+        let mut query = QueryBuilder::<Postgres>::new(r#"SELECT id FROM "game_base" WHERE TRUE"#);
+        push_game_filters(&mut query, &request);
+
+        assert_eq!(
+            query.sql(),
+            r#"SELECT id FROM "game_base" WHERE TRUE AND category = ANY($1) AND game_type = $2"#
+        );
+
+        let mut unfiltered =
+            QueryBuilder::<Postgres>::new(r#"SELECT id FROM "game_base" WHERE TRUE"#);
+        let request = GamePagedRequest {
+            game_type: None,
+            category: None,
+            categories: None,
+            ..request
+        };
+        push_game_filters(&mut unfiltered, &request);
+        assert!(!unfiltered.sql().contains('$'));
     }
 
     async fn seed<T: Serialize>(pool: &Pool<Postgres>, game_type: GameType, items: &[T]) {
@@ -372,6 +854,353 @@ mod tests {
             .unwrap();
     }
 
+    #[tokio::test]
+    async fn only_stale_games_are_purged() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+        let stale_id = Uuid::new_v4();
+        let fresh_id = Uuid::new_v4();
+
+        for (id, days_ago) in [(stale_id, 30), (fresh_id, 1)] {
+            sqlx::query(
+                r#"
+                INSERT INTO "game_base" (id, name, game_type, last_played)
+                VALUES ($1, 'Retention', 'quiz', NOW() - make_interval(days => $2))
+                "#,
+            )
+            .bind(id)
+            .bind(days_ago)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        sqlx::query(r#"INSERT INTO "quiz_game" (id, rounds) VALUES ($1, ARRAY['q1'])"#)
+            .bind(stale_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let purged = delete_stale_games(&pool, 21).await.unwrap();
+        assert!(purged >= 1);
+
+        let rounds_left: i64 =
+            sqlx::query_scalar(r#"SELECT COUNT(*) FROM "quiz_game" WHERE id = $1"#)
+                .bind(stale_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(rounds_left, 0);
+
+        let remaining: Vec<Uuid> =
+            sqlx::query_scalar(r#"SELECT id FROM "game_base" WHERE id = ANY($1)"#)
+                .bind(vec![stale_id, fresh_id])
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(remaining, vec![fresh_id]);
+
+        sqlx::query(r#"DELETE FROM "game_base" WHERE id = $1"#)
+            .bind(fresh_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn delete_game_removes_base_rounds_and_saves() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+        let game_id = Uuid::new_v4();
+
+        sqlx::query(
+            r#"INSERT INTO "game_base" (id, name, game_type) VALUES ($1, 'Doomed', 'quiz')"#,
+        )
+        .bind(game_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(r#"INSERT INTO "quiz_game" (id, rounds) VALUES ($1, ARRAY['q1', 'q2'])"#)
+            .bind(game_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        save_game(&pool, Uuid::new_v4(), game_id).await.unwrap();
+
+        let wrong_type = delete_game(&pool, GameType::Roulette, game_id)
+            .await
+            .unwrap();
+        assert!(!wrong_type.game_deleted);
+
+        let result = delete_game(&pool, GameType::Quiz, game_id).await.unwrap();
+        assert!(result.game_deleted);
+        assert_eq!(result.rounds_deleted, 1);
+        assert_eq!(result.saves_removed, 1);
+
+        let left: i64 = sqlx::query_scalar(
+            r#"
+            SELECT (SELECT COUNT(*) FROM "game_base" WHERE id = $1)
+                + (SELECT COUNT(*) FROM "quiz_game" WHERE id = $1)
+                + (SELECT COUNT(*) FROM "saved_game" WHERE base_id = $1)
+            "#,
+        )
+        .bind(game_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(left, 0);
+    }
+
+    #[tokio::test]
+    async fn every_game_type_round_trips_through_postgres() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        for game_type in GameType::ALL {
+            let (stored, text): (GameType, String) =
+                sqlx::query_as("SELECT $1::game_type, $1::game_type::text")
+                    .bind(game_type)
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap();
+
+            assert_eq!(stored, game_type);
+            assert_eq!(text, game_type.as_str());
+        }
+    }
+
+    #[tokio::test]
+    async fn game_page_filters_by_bound_category() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+        let request = GamePagedRequest {
+            page_num: Some(0),
+            page_size: None,
+            game_type: Some(GameType::Quiz),
+            category: Some(GameCategory::Girls),
+            categories: None,
+            sort: None,
+        };
+
+        let page = get_game_page(&pool, &request).await.unwrap();
+        assert!(page.items.iter().all(|game| {
+            game.category == GameCategory::Girls && game.game_type == GameType::Quiz
+        }));
+        assert!(page.total.unwrap() >= page.items.len() as i64);
+    }
+
+    #[tokio::test]
+    async fn game_page_filters_by_several_categories() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let mut game_ids = Vec::new();
+        for category in [GameCategory::Girls, GameCategory::Boys, GameCategory::Mixed] {
+            let game_id = Uuid::new_v4();
+            sqlx::query(
+                r#"
+                INSERT INTO "game_base" (id, name, game_type, category, times_played)
+                VALUES ($1, 'Category filter', 'quiz', $2, 1000000)
+                "#,
+            )
+            .bind(game_id)
+            .bind(category)
+            .execute(&pool)
+            .await
+            .unwrap();
+            game_ids.push(game_id);
+        }
+
+        let request = GamePagedRequest {
+            page_num: Some(0),
+            page_size: None,
+            game_type: Some(GameType::Quiz),
+            category: None,
+            categories: Some(vec![GameCategory::Girls, GameCategory::Boys]),
+            sort: None,
+        };
+        let page = get_game_page(&pool, &request).await.unwrap();
+
+        assert!(page.items.iter().any(|game| game.id == game_ids[0]));
+        assert!(page.items.iter().any(|game| game.id == game_ids[1]));
+        assert!(
+            page.items
+                .iter()
+                .all(|game| { matches!(game.category, GameCategory::Girls | GameCategory::Boys) })
+        );
+
+        sqlx::query(r#"DELETE FROM "game_base" WHERE id = ANY($1)"#)
+            .bind(game_ids)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn saving_twice_reports_existing_save() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+        let user_id = Uuid::new_v4();
+        let game_id = Uuid::new_v4();
+        sqlx::query(
+            r#"INSERT INTO "game_base" (id, name, game_type) VALUES ($1, 'Saved twice', 'quiz')"#,
+        )
+        .bind(game_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert!(save_game(&pool, user_id, game_id).await.unwrap());
+        assert!(!save_game(&pool, user_id, game_id).await.unwrap());
+
+        assert!(delete_saved_game(&pool, user_id, game_id).await.unwrap());
+        assert!(!delete_saved_game(&pool, user_id, game_id).await.unwrap());
+
+        sqlx::query(r#"DELETE FROM "game_base" WHERE id = $1"#)
+            .bind(game_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn bulk_save_adds_and_removes_in_one_go() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+        let user_id = Uuid::new_v4();
+        let game_ids = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+        for game_id in game_ids {
+            sqlx::query(
+                r#"INSERT INTO "game_base" (id, name, game_type) VALUES ($1, 'Bulk save', 'quiz')"#,
+            )
+            .bind(game_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        save_game(&pool, user_id, game_ids[0]).await.unwrap();
+
+        let request = BulkSaveRequest {
+            add: vec![game_ids[0], game_ids[1], game_ids[2], Uuid::new_v4()],
+            remove: vec![game_ids[0]],
+        };
+        let result = apply_saved_games(&pool, user_id, &request).await.unwrap();
+        assert_eq!(
+            result,
+            BulkSaveResult {
+                added: 3,
+                removed: 1
+            }
+        );
+
+        let request = BulkSaveRequest {
+            add: Vec::new(),
+            remove: vec![game_ids[1], game_ids[2]],
+        };
+        let result = apply_saved_games(&pool, user_id, &request).await.unwrap();
+        assert_eq!(
+            result,
+            BulkSaveResult {
+                added: 0,
+                removed: 2
+            }
+        );
+        assert!(is_game_saved(&pool, user_id, game_ids[0]).await.unwrap());
+
+        let status = get_saved_status(&pool, user_id, &game_ids).await.unwrap();
+        assert_eq!(status.len(), 3);
+        assert!(status[&game_ids[0]]);
+        assert!(!status[&game_ids[1]]);
+
+        sqlx::query(r#"DELETE FROM "game_base" WHERE id = ANY($1)"#)
+            .bind(&game_ids[..])
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn saved_games_page_reports_next_page() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+        let user_id = Uuid::new_v4();
+        let count = CONFIG.server.page_size as usize + 5;
+
+        let mut game_ids = Vec::with_capacity(count);
+        for _ in 0..count {
+            let game_id = Uuid::new_v4();
+            sqlx::query(
+                r#"INSERT INTO "game_base" (id, name, game_type) VALUES ($1, 'Saved page', 'quiz')"#,
+            )
+            .bind(game_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+            save_game(&pool, user_id, game_id).await.unwrap();
+            game_ids.push(game_id);
+        }
+
+        let request = GamePagedRequest {
+            page_num: Some(0),
+            page_size: None,
+            game_type: None,
+            category: None,
+            categories: None,
+            sort: None,
+        };
+        let page = get_saved_games_page(&pool, user_id, request).await.unwrap();
+        assert!(page.has_next);
+        assert_eq!(page.items.len(), CONFIG.server.page_size as usize);
+
+        sqlx::query(r#"DELETE FROM "game_base" WHERE id = ANY($1)"#)
+            .bind(game_ids)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn saved_stale_game_survives_purge() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+        let game_id = Uuid::new_v4();
+        let user_id = Uuid::new_v4();
+
+        sqlx::query(
+            r#"
+            INSERT INTO "game_base" (id, name, game_type, last_played)
+            VALUES ($1, 'Saved', 'quiz', NOW() - INTERVAL '90 days')
+            "#,
+        )
+        .bind(game_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+        save_game(&pool, user_id, game_id).await.unwrap();
+
+        delete_stale_games(&pool, 21).await.unwrap();
+        assert!(get_game_base(&pool, game_id).await.unwrap().is_some());
+
+        sqlx::query(r#"DELETE FROM "saved_game" WHERE base_id = $1"#)
+            .bind(game_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(r#"DELETE FROM "game_base" WHERE id = $1"#)
+            .bind(game_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
     struct TestRound {
         value: i32,
@@ -380,10 +1209,9 @@ mod tests {
 
     #[tokio::test]
     async fn random_rounds_string() {
-        if env::var("ENVIRONMENT").unwrap_or_default() != "dev" {
+        let Some(pool) = dev_pool().await else {
             return;
-        }
-        let pool = setup_pool().await;
+        };
         let game_type = GameType::Quiz;
         cleanup(&pool, game_type).await;
 
@@ -406,10 +1234,9 @@ mod tests {
 
     #[tokio::test]
     async fn random_rounds_int() {
-        if env::var("ENVIRONMENT").unwrap_or_default() != "dev" {
+        let Some(pool) = dev_pool().await else {
             return;
-        }
-        let pool = setup_pool().await;
+        };
         let game_type = GameType::Duel;
         cleanup(&pool, game_type).await;
 
@@ -432,10 +1259,9 @@ mod tests {
 
     #[tokio::test]
     async fn random_rounds_struct() {
-        if env::var("ENVIRONMENT").unwrap_or_default() != "dev" {
+        let Some(pool) = dev_pool().await else {
             return;
-        }
-        let pool = setup_pool().await;
+        };
         let game_type = GameType::Roulette;
         cleanup(&pool, game_type).await;
 
@@ -463,11 +1289,10 @@ mod tests {
 
     #[tokio::test]
     async fn fill_rounds_pool_skips_duplicates() {
-        if env::var("ENVIRONMENT").unwrap_or_default() != "dev" {
+        let Some(pool) = dev_pool().await else {
             return;
-        }
+        };
 
-        let pool = setup_pool().await;
         let game_type = GameType::Imposter;
         cleanup(&pool, game_type).await;
 
@@ -493,4 +1318,449 @@ mod tests {
 
         cleanup(&pool, game_type).await;
     }
+
+    #[tokio::test]
+    async fn created_games_are_listed_for_registered_hosts_only() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let user_id = Uuid::new_v4();
+        sqlx::query(r#"INSERT INTO "base_user" (id, username) VALUES ($1, 'host')"#)
+            .bind(user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let owned = GameBase::new(
+            Uuid::new_v4(),
+            "Owned".into(),
+            GameType::Duel,
+            GameCategory::Mixed,
+            2,
+        );
+        let anonymous = GameBase::new(
+            Uuid::new_v4(),
+            "Anonymous".into(),
+            GameType::Duel,
+            GameCategory::Mixed,
+            2,
+        );
+        create_game_base(&pool, &owned).await.unwrap();
+        create_game_base(&pool, &anonymous).await.unwrap();
+
+        set_game_creator(&pool, owned.id, user_id).await.unwrap();
+        set_game_creator(&pool, anonymous.id, Uuid::new_v4())
+            .await
+            .unwrap();
+
+        let request = GamePagedRequest {
+            page_num: None,
+            page_size: None,
+            game_type: None,
+            category: None,
+            categories: None,
+            sort: None,
+        };
+        let page = get_created_games_page(&pool, user_id, request)
+            .await
+            .unwrap();
+        let ids: Vec<Uuid> = page.items.iter().map(|g| g.id).collect();
+        assert_eq!(ids, vec![owned.id]);
+
+        sqlx::query(r#"DELETE FROM "game_base" WHERE id = ANY($1)"#)
+            .bind(vec![owned.id, anonymous.id])
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(r#"DELETE FROM "base_user" WHERE id = $1"#)
+            .bind(user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn saved_check_is_scoped_to_the_saving_user() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let saver = Uuid::new_v4();
+        let other = Uuid::new_v4();
+        for user_id in [saver, other] {
+            sqlx::query(r#"INSERT INTO "base_user" (id, username) VALUES ($1, 'saver')"#)
+                .bind(user_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let game = GameBase::new(
+            Uuid::new_v4(),
+            "Favorite".into(),
+            GameType::Quiz,
+            GameCategory::Mixed,
+            1,
+        );
+        create_game_base(&pool, &game).await.unwrap();
+        save_game(&pool, saver, game.id).await.unwrap();
+
+        assert!(is_game_saved(&pool, saver, game.id).await.unwrap());
+        assert!(!is_game_saved(&pool, other, game.id).await.unwrap());
+
+        sqlx::query(r#"DELETE FROM "saved_game" WHERE base_id = $1"#)
+            .bind(game.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(r#"DELETE FROM "game_base" WHERE id = $1"#)
+            .bind(game.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(r#"DELETE FROM "base_user" WHERE id = ANY($1)"#)
+            .bind(vec![saver, other])
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn import_reports_invalid_entries_and_inserts_the_rest() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let import = |name: &str, game_type, rounds: &[&str]| GameImport {
+            name: name.into(),
+            game_type,
+            category: GameCategory::Mixed,
+            rounds: rounds.iter().map(|r| r.to_string()).collect(),
+        };
+
+        let imports = vec![
+            import("Quizzy", GameType::Quiz, &["What?", "Why?"]),
+            import("x", GameType::Quiz, &["Too short name"]),
+            import("Spinner", GameType::Roulette, &[]),
+            import("Duelist", GameType::Duel, &["Arm wrestle"]),
+        ];
+
+        let results = import_games(&pool, &imports).await.unwrap();
+        assert_eq!(results.len(), 4);
+
+        let imported: Vec<_> = results.iter().filter_map(|r| r.game_id).collect();
+        assert_eq!(imported.len(), 2);
+        assert!(results[1].error.is_some());
+        assert!(results[2].error.is_some());
+
+        for id in &imported {
+            assert!(get_game_base(&pool, *id).await.unwrap().is_some());
+        }
+
+        sqlx::query(r#"DELETE FROM "game_base" WHERE id = ANY($1)"#)
+            .bind(imported)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn search_finds_games_by_partial_name() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let marker = Uuid::new_v4().simple().to_string()[..10].to_string();
+        let game = GameBase::new(
+            Uuid::new_v4(),
+            format!("Vorspiel {}", marker),
+            GameType::Roulette,
+            GameCategory::Mixed,
+            2,
+        );
+        create_game_base(&pool, &game).await.unwrap();
+
+        let request = |game_type| SearchGamesRequest {
+            query: marker.to_uppercase(),
+            game_type,
+            page_num: 0,
+        };
+
+        let found = search_game_page(&pool, &request(Some(GameType::Roulette)))
+            .await
+            .unwrap();
+        assert_eq!(found.items.first().map(|g| g.id), Some(game.id));
+
+        let other_type = search_game_page(&pool, &request(Some(GameType::Quiz)))
+            .await
+            .unwrap();
+        assert!(other_type.items.iter().all(|g| g.id != game.id));
+
+        sqlx::query(r#"DELETE FROM "game_base" WHERE id = $1"#)
+            .bind(game.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn patch_game_base_updates_only_given_fields() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let game = GameBase::new(
+            Uuid::new_v4(),
+            String::from("Before"),
+            GameType::Quiz,
+            GameCategory::Mixed,
+            4,
+        );
+        create_game_base(&pool, &game).await.unwrap();
+
+        let request = UpdateGameRequest {
+            name: Some(String::from("After")),
+            category: None,
+            description: Some(String::from("Renamed by an admin")),
+        };
+        let patched = patch_game_base(&pool, GameType::Quiz, game.id, request)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(patched.name, "After");
+        assert_eq!(patched.category, GameCategory::Mixed);
+        assert_eq!(patched.description.as_deref(), Some("Renamed by an admin"));
+
+        let wrong_type = patch_game_base(
+            &pool,
+            GameType::Duel,
+            game.id,
+            UpdateGameRequest {
+                name: Some(String::from("Nope")),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(wrong_type.is_none());
+
+        sqlx::query(r#"DELETE FROM "game_base" WHERE id = $1"#)
+            .bind(game.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn random_game_skips_games_saved_by_user() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let user_id = Uuid::new_v4();
+        let game = GameBase::new(
+            Uuid::new_v4(),
+            String::from("Surprise"),
+            GameType::Imposter,
+            GameCategory::InnerCircle,
+            1,
+        );
+        create_game_base(&pool, &game).await.unwrap();
+        save_game(&pool, user_id, game.id).await.unwrap();
+
+        for _ in 0..10 {
+            let picked = get_random_game_base(
+                &pool,
+                GameType::Imposter,
+                Some(GameCategory::InnerCircle),
+                Some(user_id),
+            )
+            .await
+            .unwrap();
+            assert!(picked.is_none_or(|picked| picked.id != game.id));
+        }
+
+        let picked = get_random_game_base(&pool, GameType::Imposter, None, None)
+            .await
+            .unwrap();
+        assert!(picked.is_some());
+
+        sqlx::query(r#"DELETE FROM "saved_game" WHERE user_id = $1"#)
+            .bind(user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(r#"DELETE FROM "game_base" WHERE id = $1"#)
+            .bind(game.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn get_game_base_returns_persisted_row() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let game = GameBase::new(
+            Uuid::new_v4(),
+            String::from("Returned"),
+            GameType::Quiz,
+            GameCategory::Girls,
+            3,
+        );
+
+        let mut tx = pool.begin().await.unwrap();
+        create_game_base(tx.as_mut(), &game).await.unwrap();
+        let stored = get_game_base(tx.as_mut(), game.id).await.unwrap().unwrap();
+        tx.commit().await.unwrap();
+
+        let row: (String, GameCategory, i32, i32, chrono::DateTime<chrono::Utc>) = sqlx::query_as(
+            r#"SELECT name, category, iterations, times_played, last_played FROM "game_base" WHERE id = $1"#,
+        )
+        .bind(game.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        assert_eq!(stored.id, game.id);
+        assert_eq!(stored.game_type.as_str(), GameType::Quiz.as_str());
+        assert_eq!(
+            (
+                stored.name.clone(),
+                stored.category.clone(),
+                stored.iterations,
+                stored.times_played,
+                stored.last_played
+            ),
+            row
+        );
+
+        sqlx::query(r#"DELETE FROM "game_base" WHERE id = $1"#)
+            .bind(game.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn record_game_play_claims_each_token_once() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let game = GameBase::new(
+            Uuid::new_v4(),
+            String::from("Replayed"),
+            GameType::Duel,
+            GameCategory::Mixed,
+            0,
+        );
+        let play_token = Uuid::new_v4();
+
+        // Claimed before the game row exists, the foreign key is checked on commit
+        let mut first = pool.begin().await.unwrap();
+        assert!(
+            record_game_play(&mut first, play_token, game.id)
+                .await
+                .unwrap()
+        );
+        create_game_base(first.as_mut(), &game).await.unwrap();
+
+        // A concurrent retry waits on the claimed token and sees it taken
+        let retry = {
+            let pool = pool.clone();
+            let game_id = game.id;
+            tokio::spawn(async move {
+                let mut tx = pool.begin().await.unwrap();
+                let claimed = record_game_play(&mut tx, play_token, game_id)
+                    .await
+                    .unwrap();
+                tx.rollback().await.unwrap();
+                claimed
+            })
+        };
+
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        first.commit().await.unwrap();
+        assert!(!retry.await.unwrap());
+
+        let plays: i64 = sqlx::query_scalar(r#"SELECT COUNT(*) FROM "game_play" WHERE token = $1"#)
+            .bind(play_token)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(plays, 1);
+
+        sqlx::query(r#"DELETE FROM "game_base" WHERE id = $1"#)
+            .bind(game.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn increment_times_played_counts_every_call() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let mut game = GameBase::new(
+            Uuid::new_v4(),
+            String::from("Incremented"),
+            GameType::Quiz,
+            GameCategory::Mixed,
+            0,
+        );
+        game.last_played = chrono::Utc::now() - chrono::Duration::days(30);
+        create_game_base(&pool, &game).await.unwrap();
+
+        increment_times_played(&pool, game.id).await.unwrap();
+        increment_times_played(&pool, game.id).await.unwrap();
+
+        let (times_played, last_played): (i32, chrono::DateTime<chrono::Utc>) =
+            sqlx::query_as(r#"SELECT times_played, last_played FROM "game_base" WHERE id = $1"#)
+                .bind(game.id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+
+        assert_eq!(times_played, 2);
+        assert!(last_played > game.last_played);
+        assert!(increment_times_played(&pool, Uuid::new_v4()).await.is_err());
+
+        sqlx::query(r#"DELETE FROM "game_base" WHERE id = $1"#)
+            .bind(game.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn unknown_category_decodes_to_fallback() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let game: GameBase = sqlx::query_as(
+            r#"
+            SELECT
+                gen_random_uuid() AS id,
+                'Drifted' AS name,
+                'quiz'::game_type AS game_type,
+                'vors'::text AS category,
+                0 AS iterations,
+                0 AS times_played,
+                NOW() AS last_played
+            "#,
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        assert_eq!(game.category, GameCategory::Unknown);
+    }
 }
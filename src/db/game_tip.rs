@@ -1,73 +1,256 @@
 use chrono::Utc;
-use sqlx::{Pool, Postgres};
+use sqlx::{Pool, Postgres, QueryBuilder};
 use uuid::Uuid;
 
 use crate::{
     config::app_config::CONFIG,
+    db::page_window,
     models::{
         error::ServerError,
         game_base::PagedResponse,
-        game_tip::{CreateGameTipRequest, GameTip},
+        game_tip::{CreateGameTipRequest, GameTip, TipStatus},
     },
 };
 
+/// `submitted_by` is only set for tips sent in by a signed in user
 pub async fn create_game_tip(
     pool: &Pool<Postgres>,
     request: &CreateGameTipRequest,
+    submitted_by: Option<Uuid>,
 ) -> Result<Uuid, ServerError> {
     let id = Uuid::new_v4();
     let created_at = Utc::now();
 
-    sqlx::query!(
+    sqlx::query(
         r#"
-        INSERT INTO "game_tip" (id, header, mobile_phone, description, created_at)
-        VALUES ($1, $2, $3, $4, $5)
+        INSERT INTO "game_tip" (id, header, mobile_phone, description, created_at, submitted_by)
+        VALUES ($1, $2, $3, $4, $5, $6)
         "#,
-        id,
-        &request.header,
-        &request.mobile_phone,
-        &request.description,
-        created_at
     )
+    .bind(id)
+    .bind(&request.header)
+    .bind(&request.mobile_phone)
+    .bind(&request.description)
+    .bind(created_at)
+    .bind(submitted_by)
     .execute(pool)
     .await?;
 
     Ok(id)
 }
 
-pub async fn get_game_tips_page(
+pub async fn get_game_tips_by_user(
     pool: &Pool<Postgres>,
-    page_num: u16,
-) -> Result<PagedResponse<GameTip>, sqlx::Error> {
-    let page_size = CONFIG.server.page_size;
-    let offset = (page_size * page_num) as i64;
-    let limit = (page_size + 1) as i64;
-
-    let mut tips = sqlx::query_as!(
-        GameTip,
+    user_id: Uuid,
+) -> Result<Vec<GameTip>, sqlx::Error> {
+    sqlx::query_as::<_, GameTip>(
         r#"
-        SELECT id, header, mobile_phone, description, created_at
-        FROM game_tip
+        SELECT id, header, mobile_phone, description, status, created_at
+        FROM "game_tip"
+        WHERE submitted_by = $1
         ORDER BY created_at DESC
-        LIMIT $1 OFFSET $2
         "#,
-        limit,
-        offset
     )
+    .bind(user_id)
     .fetch_all(pool)
-    .await?;
+    .await
+}
+
+pub async fn get_game_tips_page(
+    pool: &Pool<Postgres>,
+    page_num: u16,
+    status: Option<TipStatus>,
+) -> Result<PagedResponse<GameTip>, sqlx::Error> {
+    let page_size = CONFIG.server.page_size;
+    let (limit, offset) = page_window(page_num);
 
-    let has_next = tips.len() >= page_size as usize;
+    let mut tips_query = QueryBuilder::<Postgres>::new(
+        "SELECT id, header, mobile_phone, description, status, created_at FROM game_tip",
+    );
+    let mut total_query = QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM game_tip");
+
+    if let Some(status) = status {
+        tips_query.push(" WHERE status = ").push_bind(status);
+        total_query.push(" WHERE status = ").push_bind(status);
+    }
+
+    tips_query
+        .push(" ORDER BY created_at DESC LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
+
+    let tips_fut = tips_query.build_query_as::<GameTip>().fetch_all(pool);
+    let total_fut = total_query.build_query_scalar::<i64>().fetch_one(pool);
+
+    let (tips, total) = tokio::join!(tips_fut, total_fut);
+    let mut tips = tips?;
+
+    let has_next = tips.len() > page_size as usize;
     if has_next {
         tips.pop();
     }
 
-    let page = PagedResponse {
-        page_num,
-        items: tips,
-        has_prev: page_num > 0,
-        has_next,
+    Ok(PagedResponse::with_total(page_num, tips, has_next, total?))
+}
+
+/// Returns false if no tip has the given id
+pub async fn update_game_tip_status(
+    pool: &Pool<Postgres>,
+    tip_id: Uuid,
+    status: TipStatus,
+) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query(r#"UPDATE "game_tip" SET status = $1 WHERE id = $2"#)
+        .bind(status)
+        .bind(tip_id)
+        .execute(pool)
+        .await?;
+
+    Ok(row.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::{
+        config::app_config::CONFIG,
+        models::game_tip::{CreateGameTipRequest, TipStatus},
+        tests::db::dev_pool,
+    };
+
+    use super::{
+        create_game_tip, get_game_tips_by_user, get_game_tips_page, update_game_tip_status,
     };
 
-    Ok(page)
+    #[tokio::test]
+    async fn reviewed_tips_leave_the_new_inbox() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let request = CreateGameTipRequest {
+            header: "Status test".into(),
+            mobile_phone: "12345678".into(),
+            description: "A tip that gets reviewed".into(),
+            website: None,
+        };
+        let tip_id = create_game_tip(&pool, &request, None).await.unwrap();
+
+        let new = get_game_tips_page(&pool, 0, Some(TipStatus::New))
+            .await
+            .unwrap();
+        assert!(new.items.iter().any(|tip| tip.id == tip_id));
+
+        assert!(
+            update_game_tip_status(&pool, tip_id, TipStatus::Reviewed)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !update_game_tip_status(&pool, Uuid::new_v4(), TipStatus::Reviewed)
+                .await
+                .unwrap()
+        );
+
+        let new = get_game_tips_page(&pool, 0, Some(TipStatus::New))
+            .await
+            .unwrap();
+        assert!(new.items.iter().all(|tip| tip.id != tip_id));
+
+        sqlx::query(r#"DELETE FROM "game_tip" WHERE id = $1"#)
+            .bind(tip_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn full_last_page_has_no_next() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let page_size = CONFIG.server.page_size as i64;
+        let request = CreateGameTipRequest {
+            header: "Paging test".into(),
+            mobile_phone: "12345678".into(),
+            description: "Pads the dismissed tips to a full page".into(),
+            website: None,
+        };
+
+        // Dismissed tips are only written here, pad them to a multiple of the page size
+        let dismissed: i64 =
+            sqlx::query_scalar(r#"SELECT COUNT(*) FROM "game_tip" WHERE status = $1"#)
+                .bind(TipStatus::Dismissed)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        let padding = page_size - dismissed % page_size;
+
+        let mut tip_ids = Vec::new();
+        for _ in 0..padding {
+            let tip_id = create_game_tip(&pool, &request, None).await.unwrap();
+            update_game_tip_status(&pool, tip_id, TipStatus::Dismissed)
+                .await
+                .unwrap();
+            tip_ids.push(tip_id);
+        }
+
+        let last_page = ((dismissed + padding) / page_size - 1) as u16;
+        let page = get_game_tips_page(&pool, last_page, Some(TipStatus::Dismissed)).await;
+
+        sqlx::query(r#"DELETE FROM "game_tip" WHERE id = ANY($1)"#)
+            .bind(&tip_ids)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let page = page.unwrap();
+        assert_eq!(page.items.len() as i64, page_size);
+        assert!(!page.has_next);
+    }
+
+    #[tokio::test]
+    async fn tips_are_listed_for_their_submitter() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let user_id = Uuid::new_v4();
+        sqlx::query(r#"INSERT INTO "base_user" (id, username) VALUES ($1, $2)"#)
+            .bind(user_id)
+            .bind(format!("tipper_{}", &user_id.simple().to_string()[..8]))
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let request = CreateGameTipRequest {
+            header: "Export test".into(),
+            mobile_phone: "12345678".into(),
+            description: "A tip from a signed in user".into(),
+            website: None,
+        };
+        let own_tip = create_game_tip(&pool, &request, Some(user_id))
+            .await
+            .unwrap();
+        let anonymous_tip = create_game_tip(&pool, &request, None).await.unwrap();
+
+        let tips = get_game_tips_by_user(&pool, user_id).await;
+
+        sqlx::query(r#"DELETE FROM "game_tip" WHERE id = ANY($1)"#)
+            .bind([own_tip, anonymous_tip])
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(r#"DELETE FROM "base_user" WHERE id = $1"#)
+            .bind(user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let tips = tips.unwrap();
+        assert_eq!(tips.len(), 1);
+        assert_eq!(tips[0].id, own_tip);
+    }
 }
@@ -1,29 +1,27 @@
 use sqlx::{Executor, Pool, Postgres};
-use tracing::warn;
 use uuid::Uuid;
 
 use crate::models::imposter_game::ImposterGame;
 
+/// Same upsert as quiz games, a repeated persist replaces the stored rounds
 pub async fn create_imposter_game(
     executor: impl Executor<'_, Database = Postgres>,
     game_id: Uuid,
     rounds: &Vec<String>,
 ) -> Result<(), sqlx::Error> {
-    let row = sqlx::query!(
+    sqlx::query(
         r#"
         INSERT INTO "imposter_game" ("id", "rounds")
-        VALUES ($1, $2); 
+        VALUES ($1, $2)
+        ON CONFLICT (id) DO UPDATE SET
+            rounds = EXCLUDED.rounds
         "#,
-        game_id,
-        rounds
     )
+    .bind(game_id)
+    .bind(rounds)
     .execute(executor)
     .await?;
 
-    if row.rows_affected() == 0 {
-        warn!("Skipping imposter game creation: id already exists");
-    }
-
     Ok(())
 }
 
@@ -43,3 +41,67 @@ pub async fn get_imposter_game_by_id(
     .fetch_one(pool)
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::{
+        db::game_base::create_game_base,
+        models::{
+            game_base::{GameBase, GameCategory, GameType, JsonConverter},
+            imposter_game::ImposterSession,
+        },
+        tests::db::dev_pool,
+    };
+
+    use super::{create_imposter_game, get_imposter_game_by_id};
+
+    #[tokio::test]
+    async fn created_session_persists_and_reloads() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let host_id = Uuid::new_v4();
+        let game_id = Uuid::new_v4();
+
+        // Created empty, handed back by the session service with rounds filled in
+        let created = ImposterSession::new(host_id, game_id).to_json().unwrap();
+        let mut session: ImposterSession = serde_json::from_value(created).unwrap();
+        session.rounds = vec!["Pizza".into(), "Beach".into()];
+
+        let game_base = GameBase::new(
+            session.game_id,
+            "imposter_round_trip".into(),
+            GameType::Imposter,
+            GameCategory::Mixed,
+            session.rounds.len() as i32,
+        );
+
+        let mut tx = pool.begin().await.unwrap();
+        create_game_base(tx.as_mut(), &game_base).await.unwrap();
+        create_imposter_game(tx.as_mut(), game_id, &session.rounds)
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+
+        // A retried persist must not fail on the existing row
+        let retried = vec!["Pizza".to_string(), "Beach".to_string(), "Zoo".into()];
+        create_imposter_game(&pool, game_id, &retried)
+            .await
+            .unwrap();
+
+        let game = get_imposter_game_by_id(&pool, game_id).await.unwrap();
+        let reloaded = ImposterSession::from_game(host_id, game);
+        assert_eq!(reloaded.game_id, game_id);
+        assert_eq!(reloaded.host_id, host_id);
+        assert_eq!(reloaded.rounds, retried);
+
+        sqlx::query(r#"DELETE FROM "game_base" WHERE id = $1"#)
+            .bind(game_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,83 @@
+use sqlx::{Pool, Postgres};
+
+use crate::models::integration::IntegrationConfig;
+
+pub async fn get_integrations(
+    pool: &Pool<Postgres>,
+) -> Result<Vec<IntegrationConfig>, sqlx::Error> {
+    sqlx::query_as::<_, IntegrationConfig>(
+        r#"
+        SELECT name, subject
+        FROM "integration"
+        ORDER BY subject
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Registering a known subject again moves it to the new integration
+pub async fn upsert_integration(
+    pool: &Pool<Postgres>,
+    integration: &IntegrationConfig,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO "integration" (subject, name)
+        VALUES ($1, $2)
+        ON CONFLICT (subject) DO UPDATE SET name = EXCLUDED.name
+        "#,
+    )
+    .bind(&integration.subject)
+    .bind(&integration.name)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::models::integration::{IntegrationConfig, IntegrationName};
+
+    use crate::tests::db::dev_pool;
+
+    use super::{get_integrations, upsert_integration};
+
+    #[tokio::test]
+    async fn reregistering_a_subject_replaces_its_integration() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let subject = format!("test_{}", uuid::Uuid::new_v4().simple());
+
+        for name in [IntegrationName::Session, IntegrationName::Platform] {
+            upsert_integration(
+                &pool,
+                &IntegrationConfig {
+                    name,
+                    subject: subject.clone(),
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        let stored: Vec<IntegrationConfig> = get_integrations(&pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|i| i.subject == subject)
+            .collect();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].name, IntegrationName::Platform);
+
+        sqlx::query(r#"DELETE FROM "integration" WHERE subject = $1"#)
+            .bind(&subject)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
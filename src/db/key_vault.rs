@@ -14,3 +14,11 @@ pub async fn get_word_sets(
 
     Ok((prefix_result?, suffix_result?))
 }
+
+pub async fn get_blocked_key_combos(
+    pool: &Pool<Postgres>,
+) -> Result<Vec<(String, String)>, sqlx::Error> {
+    sqlx::query_as("SELECT prefix, suffix FROM blocked_key_combo")
+        .fetch_all(pool)
+        .await
+}
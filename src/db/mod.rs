@@ -2,8 +2,63 @@ pub mod game_base;
 pub mod game_tip;
 pub mod health;
 pub mod imposter_game;
+pub mod integration;
 pub mod key_vault;
+pub mod popup;
 pub mod quiz_game;
 pub mod spin_game;
 pub mod system_log;
 pub mod user;
+
+/// Limit and offset for a page. The limit fetches one sentinel row past the
+/// page to detect `has_next`, and the math is done in `i64` so large page
+/// numbers can not overflow `u16`.
+pub fn page_window(page_num: u16) -> (i64, i64) {
+    page_window_with(page_num, crate::config::app_config::CONFIG.server.page_size)
+}
+
+/// Same as `page_window`, for pages whose size the client picked
+pub fn page_window_with(page_num: u16, page_size: u16) -> (i64, i64) {
+    let page_size = page_size as i64;
+    (page_size + 1, page_size * page_num as i64)
+}
+
+/// The client's `page_size` clamped to `server.max_page_size`, or the
+/// configured default when none was asked for
+pub fn effective_page_size(requested: Option<u16>) -> u16 {
+    let server = &crate::config::app_config::CONFIG.server;
+    effective_page_size_with(requested, server.page_size, server.max_page_size)
+}
+
+fn effective_page_size_with(requested: Option<u16>, default: u16, max: u16) -> u16 {
+    requested.unwrap_or(default).clamp(1, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{effective_page_size_with, page_window_with};
+
+    #[test]
+    fn omitted_page_size_uses_default() {
+        assert_eq!(effective_page_size_with(None, 20, 100), 20);
+    }
+
+    #[test]
+    fn requested_page_size_is_honored_up_to_the_cap() {
+        assert_eq!(effective_page_size_with(Some(50), 20, 100), 50);
+        assert_eq!(effective_page_size_with(Some(100), 20, 100), 100);
+        assert_eq!(effective_page_size_with(Some(5000), 20, 100), 100);
+        assert_eq!(effective_page_size_with(Some(0), 20, 100), 1);
+    }
+
+    #[test]
+    fn missing_page_num_starts_at_first_page() {
+        let page_num: Option<u16> = None;
+        assert_eq!(page_window_with(page_num.unwrap_or(0), 20), (21, 0));
+    }
+
+    #[test]
+    fn large_page_num_does_not_overflow() {
+        assert_eq!(page_window_with(u16::MAX, 20), (21, u16::MAX as i64 * 20));
+    }
+}
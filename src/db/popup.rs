@@ -0,0 +1,73 @@
+use sqlx::{Pool, Postgres};
+
+use crate::service::popup_manager::ClientPopup;
+
+pub async fn get_client_popup(pool: &Pool<Postgres>) -> Result<Option<ClientPopup>, sqlx::Error> {
+    sqlx::query_as::<_, ClientPopup>(
+        r#"
+        SELECT heading, paragraph, active, start_at, end_at
+        FROM "client_popup"
+        WHERE id = 1
+        "#,
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// The table holds a single row, so this always overwrites the current popup
+pub async fn upsert_client_popup(
+    pool: &Pool<Postgres>,
+    popup: &ClientPopup,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO "client_popup" (id, heading, paragraph, active, start_at, end_at, updated_at)
+        VALUES (1, $1, $2, $3, $4, $5, NOW())
+        ON CONFLICT (id) DO UPDATE SET
+            heading = EXCLUDED.heading,
+            paragraph = EXCLUDED.paragraph,
+            active = EXCLUDED.active,
+            start_at = EXCLUDED.start_at,
+            end_at = EXCLUDED.end_at,
+            updated_at = EXCLUDED.updated_at
+        "#,
+    )
+    .bind(&popup.heading)
+    .bind(&popup.paragraph)
+    .bind(popup.active)
+    .bind(popup.start_at)
+    .bind(popup.end_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::service::popup_manager::PopupManager;
+
+    use crate::tests::db::dev_pool;
+
+    use super::get_client_popup;
+
+    #[tokio::test]
+    async fn popup_update_survives_reload() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let manager = PopupManager::load(&pool).await;
+        let original = get_client_popup(&pool).await.unwrap().unwrap();
+
+        let mut popup = original.clone();
+        popup.heading = "Nedetid".into();
+        manager.update(popup).await.unwrap();
+
+        let reloaded = PopupManager::load(&pool).await;
+        assert_eq!(reloaded.read().await.heading, "Nedetid");
+
+        manager.update(original).await.unwrap();
+    }
+}
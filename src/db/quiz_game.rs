@@ -1,45 +1,154 @@
-use sqlx::{Executor, Pool, Postgres};
+use sqlx::{Executor, Pool, Postgres, types::Json};
 use uuid::Uuid;
 
-use crate::models::{error::ServerError, quiz_game::QuizGame};
+use crate::models::{
+    error::ServerError,
+    quiz_game::{QuizGame, QuizQuestion},
+};
 
 pub async fn get_quiz_game_by_id(
     pool: &Pool<Postgres>,
     game_id: Uuid,
 ) -> Result<QuizGame, sqlx::Error> {
-    sqlx::query_as!(
-        QuizGame,
+    sqlx::query_as::<_, QuizGame>(
         r#"
-        SELECT id, rounds 
-        FROM "quiz_game" 
-        WHERE id = $1
+        SELECT quiz.id, quiz.rounds, quiz.questions, base.description
+        FROM "quiz_game" quiz
+        JOIN "game_base" base ON base.id = quiz.id
+        WHERE quiz.id = $1
         "#,
-        game_id
     )
+    .bind(game_id)
     .fetch_one(pool)
     .await
 }
 
+/// Writes the prompts to `rounds` next to the full questions
 pub async fn create_quiz_game<'e, E>(
     executor: E,
     game_id: Uuid,
-    rounds: &Vec<String>,
+    questions: &[QuizQuestion],
 ) -> Result<(), ServerError>
 where
     E: Executor<'e, Database = Postgres>,
 {
-    sqlx::query!(
+    let prompts: Vec<&str> = questions.iter().map(|q| q.prompt.as_str()).collect();
+
+    sqlx::query(
         r#"
-        INSERT INTO "quiz_game" (id, rounds)
-        VALUES ($1, $2)
+        INSERT INTO "quiz_game" (id, rounds, questions)
+        VALUES ($1, $2, $3)
         ON CONFLICT (id) DO UPDATE SET
-            rounds = EXCLUDED.rounds
+            rounds = EXCLUDED.rounds,
+            questions = EXCLUDED.questions
         "#,
-        game_id,
-        rounds
     )
+    .bind(game_id)
+    .bind(prompts)
+    .bind(Json(questions))
     .execute(executor)
     .await?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::{
+        db::game_base::create_game_base,
+        models::{
+            game_base::{GameBase, GameCategory, GameType},
+            quiz_game::{QuizQuestion, QuizSession},
+        },
+        tests::db::dev_pool,
+    };
+
+    use super::{create_quiz_game, get_quiz_game_by_id};
+
+    #[tokio::test]
+    async fn description_survives_persist_and_reload() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let rounds = vec![
+            QuizQuestion::open("Who?".into()),
+            QuizQuestion::open("Why?".into()),
+        ];
+        let game_base = GameBase {
+            description: Some("Questions for the cabin trip".into()),
+            ..GameBase::new(
+                Uuid::new_v4(),
+                "quiz_description".into(),
+                GameType::Quiz,
+                GameCategory::Mixed,
+                rounds.len() as i32,
+            )
+        };
+
+        let mut tx = pool.begin().await.unwrap();
+        create_game_base(tx.as_mut(), &game_base).await.unwrap();
+        create_quiz_game(tx.as_mut(), game_base.id, &rounds)
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+
+        let game = get_quiz_game_by_id(&pool, game_base.id).await.unwrap();
+        let session = QuizSession::from_game(game);
+        assert_eq!(session.questions, rounds);
+        assert_eq!(session.description, game_base.description);
+
+        sqlx::query(r#"DELETE FROM "game_base" WHERE id = $1"#)
+            .bind(game_base.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn multiple_choice_questions_survive_persist_and_reload() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let questions = vec![
+            QuizQuestion {
+                prompt: "Capital of Norway?".into(),
+                options: vec!["Bergen".into(), "Oslo".into(), "Trondheim".into()],
+                correct_index: 1,
+            },
+            QuizQuestion::open("Name a fjord".into()),
+        ];
+        let game_base = GameBase::new(
+            Uuid::new_v4(),
+            "quiz_choices".into(),
+            GameType::Quiz,
+            GameCategory::Mixed,
+            questions.len() as i32,
+        );
+
+        let mut tx = pool.begin().await.unwrap();
+        create_game_base(tx.as_mut(), &game_base).await.unwrap();
+        create_quiz_game(tx.as_mut(), game_base.id, &questions)
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+
+        let game = get_quiz_game_by_id(&pool, game_base.id).await.unwrap();
+        assert_eq!(
+            game.rounds,
+            vec!["Capital of Norway?".to_string(), "Name a fjord".to_string()]
+        );
+
+        let session = QuizSession::from_game(game);
+        assert_eq!(session.questions, questions);
+
+        sqlx::query(r#"DELETE FROM "game_base" WHERE id = $1"#)
+            .bind(game_base.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
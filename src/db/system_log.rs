@@ -1,27 +1,61 @@
-use chrono::Utc;
-use sqlx::{Pool, Postgres};
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{Pool, Postgres, QueryBuilder};
 
 use crate::{
-    config::app_config::CONFIG,
+    config::app_config::LogRetentionConfig,
+    db::{effective_page_size, page_window_with},
     models::{
         error::ServerError,
         game_base::PagedResponse,
         system_log::{
-            LogAction, LogCategoryCount, LogCeverity, SubjectType, SyslogPageQuery, SystemLog,
+            LogAction, LogCategoryCount, LogCursor, NewSystemLog, SubjectType, SyslogPage,
+            SyslogPageQuery, SystemLog,
         },
     },
+    service::log_stream,
 };
 
+fn push_log_filters(query: &mut QueryBuilder<'_, Postgres>, request: &SyslogPageQuery) {
+    if let Some(subject_type) = &request.subject_type {
+        query
+            .push(" AND subject_type = ")
+            .push_bind(subject_type.clone());
+    }
+
+    if let Some(action) = &request.action {
+        query.push(" AND action = ").push_bind(action.clone());
+    }
+
+    if let Some(ceverity) = &request.ceverity {
+        query.push(" AND ceverity = ").push_bind(ceverity.clone());
+    }
+
+    if let Some(from) = request.from {
+        query.push(" AND created_at >= ").push_bind(from);
+    }
+
+    if let Some(to) = request.to {
+        query.push(" AND created_at <= ").push_bind(to);
+    }
+}
+
+/// Pages through logs newest first. A `before` cursor seeks past the given
+/// `(created_at, id)` so rows inserted meanwhile do not shift the page,
+/// otherwise falls back to `page_num` offsets. Time windows and cursors
+/// are served by `idx_system_log_created_at`. The total ignores the cursor.
 pub async fn get_system_log_page(
     pool: &Pool<Postgres>,
     request: SyslogPageQuery,
-) -> Result<PagedResponse<SystemLog>, sqlx::Error> {
+    before: Option<LogCursor>,
+) -> Result<SyslogPage, sqlx::Error> {
     let page_num = request.page_num.unwrap_or(0);
-    let page_size = CONFIG.server.page_size;
-    let offset = (page_size * page_num) as i64;
-    let limit = (page_size + 1) as i64;
+    let page_size = effective_page_size(request.page_size);
+    let (limit, offset) = page_window_with(page_num, page_size);
 
-    let mut query = r#"
+    let mut query = QueryBuilder::<Postgres>::new(
+        r#"
         SELECT 
             id,
             subject_id,
@@ -33,101 +67,217 @@ pub async fn get_system_log_page(
             metadata,
             created_at
         FROM system_log 
-    "#
-    .to_string();
+        WHERE TRUE
+        "#,
+    );
 
-    let mut where_clause = Vec::new();
+    push_log_filters(&mut query, &request);
 
-    if let Some(subject_type) = request.subject_type {
-        where_clause.push(format!(" subject_type = '{}'", subject_type));
-    }
+    let mut count_query =
+        QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM system_log WHERE TRUE");
+    push_log_filters(&mut count_query, &request);
 
-    if let Some(action) = request.action {
-        where_clause.push(format!(" action = '{}'", action));
+    if let Some(cursor) = before {
+        query
+            .push(" AND (created_at, id) < (")
+            .push_bind(cursor.created_at)
+            .push(", ")
+            .push_bind(cursor.id)
+            .push(")");
     }
 
-    if let Some(ceverity) = request.ceverity {
-        where_clause.push(format!(" ceverity = '{}'", ceverity));
-    }
+    query
+        .push(" ORDER BY created_at DESC, id DESC LIMIT ")
+        .push_bind(limit);
 
-    if !where_clause.is_empty() {
-        let conditions = where_clause.join(" AND ");
-        query.push_str(&format!("WHERE {}", conditions));
+    if before.is_none() {
+        query.push(" OFFSET ").push_bind(offset);
     }
 
-    query.push_str(&format!(
-        r#"
-        ORDER BY created_at DESC
-        LIMIT {} OFFSET {} 
-    "#,
-        limit, offset
-    ));
-
-    let mut logs = sqlx::query_as::<_, SystemLog>(&query)
-        .fetch_all(pool)
-        .await?;
+    let (logs, total) = tokio::join!(
+        query.build_query_as::<SystemLog>().fetch_all(pool),
+        count_query.build_query_scalar::<i64>().fetch_one(pool)
+    );
+    let mut logs = logs?;
 
-    let has_next = logs.len() >= page_size as usize;
+    let has_next = logs.len() > page_size as usize;
     if has_next {
         logs.pop();
     }
 
-    let page = PagedResponse {
-        has_prev: page_num > 0,
-        items: logs,
-        page_num,
-        has_next,
+    let next_cursor = match has_next {
+        true => logs.last().map(|log| LogCursor::from_log(log).to_string()),
+        false => None,
     };
 
-    Ok(page)
+    let mut page = PagedResponse::with_total(page_num, logs, has_next, total?);
+    page.has_prev |= before.is_some();
+
+    Ok(SyslogPage { page, next_cursor })
 }
 
-#[allow(clippy::too_many_arguments)] // TODO
 pub async fn create_system_log(
     pool: &Pool<Postgres>,
-    subject_id: &str,
-    subject_type: &SubjectType,
-    action: &LogAction,
-    ceverity: &LogCeverity,
-    function: &str,
-    description: &str,
-    metadata: &Option<serde_json::Value>,
+    log: &NewSystemLog,
 ) -> Result<(), ServerError> {
-    let created_at = Utc::now();
-    let row = sqlx::query!(
+    create_system_logs(pool, std::slice::from_ref(log)).await
+}
+
+/// Writes all logs in a single multi-row insert
+pub async fn create_system_logs(
+    pool: &Pool<Postgres>,
+    logs: &[NewSystemLog],
+) -> Result<(), ServerError> {
+    if logs.is_empty() {
+        return Ok(());
+    }
+
+    let mut query = QueryBuilder::<Postgres>::new(
+        r#"INSERT INTO "system_log" (subject_id, subject_type, action, ceverity, function, description, metadata, created_at) "#,
+    );
+
+    query.push_values(logs, |mut row, log| {
+        row.push_bind(&log.subject_id)
+            .push_bind(&log.subject_type)
+            .push_bind(&log.action)
+            .push_bind(&log.ceverity)
+            .push_bind(&log.function)
+            .push_bind(&log.description)
+            .push_bind(&log.metadata)
+            .push_bind(log.created_at);
+    });
+
+    query.push(
+        " RETURNING id, subject_id, subject_type, action, ceverity, function, description, metadata, created_at",
+    );
+
+    let created = query.build_query_as::<SystemLog>().fetch_all(pool).await?;
+    if created.len() != logs.len() {
+        return Err(ServerError::Internal("Failed to create system log".into()));
+    }
+
+    for log in created {
+        log_stream::publish(log);
+    }
+
+    Ok(())
+}
+
+#[derive(sqlx::FromRow)]
+struct CountRow {
+    info: i64,
+    warning: i64,
+    critical: i64,
+}
+
+impl From<CountRow> for LogCategoryCount {
+    fn from(row: CountRow) -> Self {
+        Self {
+            info: row.info,
+            warning: row.warning,
+            critical: row.critical,
+        }
+    }
+}
+
+/// Deletes logs older than their severity's retention window as of `now`,
+/// returning how many were purged per severity
+pub async fn purge_old_logs(
+    pool: &Pool<Postgres>,
+    retention: &LogRetentionConfig,
+    now: DateTime<Utc>,
+) -> Result<LogCategoryCount, sqlx::Error> {
+    let cutoff = |days: u16| now - Duration::days(days as i64);
+
+    let result = sqlx::query_as::<_, CountRow>(
         r#"
-        INSERT INTO "system_log" (subject_id, subject_type, action, ceverity, function, description, metadata, created_at)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        WITH purged AS (
+            DELETE FROM system_log
+            WHERE (ceverity = 'info' AND created_at < $1)
+                OR (ceverity = 'warning' AND created_at < $2)
+                OR (ceverity = 'critical' AND created_at < $3)
+            RETURNING ceverity
+        )
+        SELECT
+            COUNT(*) FILTER (WHERE ceverity = 'info') as info,
+            COUNT(*) FILTER (WHERE ceverity = 'warning') as warning,
+            COUNT(*) FILTER (WHERE ceverity = 'critical') as critical
+        FROM purged
         "#,
-        subject_id,
-        subject_type as _,
-        action as _,
-        ceverity as _,
-        function,
-        description,
-        metadata as _,
-        created_at
     )
-    .execute(pool)
+    .bind(cutoff(retention.info_days))
+    .bind(cutoff(retention.warning_days))
+    .bind(cutoff(retention.critical_days))
+    .fetch_one(pool)
     .await?;
 
-    if row.rows_affected() == 0 {
-        return Err(ServerError::Internal("Failed to create system log".into()));
-    }
+    Ok(result.into())
+}
 
-    Ok(())
+/// Log counts per action, every action is present even when zero
+pub async fn get_log_action_count(
+    pool: &Pool<Postgres>,
+) -> Result<HashMap<String, i64>, sqlx::Error> {
+    let (create, read, update, delete, sync, other) =
+        sqlx::query_as::<_, (i64, i64, i64, i64, i64, i64)>(
+            r#"
+            SELECT
+                COUNT(*) FILTER (WHERE action = 'create'),
+                COUNT(*) FILTER (WHERE action = 'read'),
+                COUNT(*) FILTER (WHERE action = 'update'),
+                COUNT(*) FILTER (WHERE action = 'delete'),
+                COUNT(*) FILTER (WHERE action = 'sync'),
+                COUNT(*) FILTER (WHERE action = 'other')
+            FROM system_log
+            "#,
+        )
+        .fetch_one(pool)
+        .await?;
+
+    Ok([
+        (LogAction::Create, create),
+        (LogAction::Read, read),
+        (LogAction::Update, update),
+        (LogAction::Delete, delete),
+        (LogAction::Sync, sync),
+        (LogAction::Other, other),
+    ]
+    .into_iter()
+    .map(|(action, count)| (action.to_string(), count))
+    .collect())
+}
+
+/// Log counts per subject type, every subject type is present even when zero
+pub async fn get_log_subject_count(
+    pool: &Pool<Postgres>,
+) -> Result<HashMap<String, i64>, sqlx::Error> {
+    let (registered, guest, integration, system) = sqlx::query_as::<_, (i64, i64, i64, i64)>(
+        r#"
+            SELECT
+                COUNT(*) FILTER (WHERE subject_type = 'registered_user'),
+                COUNT(*) FILTER (WHERE subject_type = 'guest_user'),
+                COUNT(*) FILTER (WHERE subject_type = 'integration'),
+                COUNT(*) FILTER (WHERE subject_type = 'system')
+            FROM system_log
+            "#,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok([
+        (SubjectType::RegisteredUser, registered),
+        (SubjectType::GuestUser, guest),
+        (SubjectType::Integration, integration),
+        (SubjectType::System, system),
+    ]
+    .into_iter()
+    .map(|(subject_type, count)| (subject_type.to_string(), count))
+    .collect())
 }
 
 pub async fn get_log_category_count(
     pool: &Pool<Postgres>,
 ) -> Result<LogCategoryCount, sqlx::Error> {
-    #[derive(sqlx::FromRow)]
-    struct CountRow {
-        info: i64,
-        warning: i64,
-        critical: i64,
-    }
-
     let result = sqlx::query_as::<_, CountRow>(
         r#"
         SELECT 
@@ -140,9 +290,177 @@ pub async fn get_log_category_count(
     .fetch_one(pool)
     .await?;
 
-    Ok(LogCategoryCount {
-        info: result.info,
-        warning: result.warning,
-        critical: result.critical,
-    })
+    Ok(result.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use uuid::Uuid;
+
+    use crate::models::system_log::{
+        LogAction, LogCeverity, SubjectType, SyslogPageQuery, SystemLog,
+    };
+
+    use crate::config::app_config::LogRetentionConfig;
+
+    use crate::tests::db::dev_pool;
+
+    use super::{get_log_action_count, get_log_subject_count, get_system_log_page, purge_old_logs};
+
+    #[tokio::test]
+    async fn grouped_counts_cover_every_variant() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let actions = get_log_action_count(&pool).await.unwrap();
+        assert_eq!(actions.len(), 6);
+        assert!(actions.contains_key("sync"));
+
+        let subjects = get_log_subject_count(&pool).await.unwrap();
+        assert_eq!(subjects.len(), 4);
+        assert!(subjects.contains_key("guest_user"));
+    }
+
+    fn day(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2001, 1, day, 12, 0, 0).unwrap()
+    }
+
+    fn range_query(from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> SyslogPageQuery {
+        SyslogPageQuery {
+            page_num: None,
+            page_size: None,
+            before: None,
+            from,
+            to,
+            subject_type: None,
+            action: None,
+            ceverity: None,
+        }
+    }
+
+    fn owned_days(logs: &[SystemLog], subject_id: &str) -> Vec<DateTime<Utc>> {
+        logs.iter()
+            .filter(|log| log.subject_id == subject_id)
+            .map(|log| log.created_at)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn logs_are_purged_per_severity_window() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let subject_id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+        let retention = LogRetentionConfig {
+            info_days: 14,
+            warning_days: 60,
+            critical_days: 180,
+        };
+
+        let windows = [
+            (LogCeverity::Info, 14),
+            (LogCeverity::Warning, 60),
+            (LogCeverity::Critical, 180),
+        ];
+        for (ceverity, days) in &windows {
+            for age in [days - 1, days + 1] {
+                sqlx::query(
+                    r#"
+                    INSERT INTO "system_log" (subject_id, subject_type, action, ceverity, function, description, created_at)
+                    VALUES ($1, $2, $3, $4, 'test', 'purge test', $5)
+                    "#,
+                )
+                .bind(&subject_id)
+                .bind(SubjectType::System)
+                .bind(LogAction::Other)
+                .bind(ceverity.clone())
+                .bind(now - Duration::days(age))
+                .execute(&pool)
+                .await
+                .unwrap();
+            }
+        }
+
+        let purged = purge_old_logs(&pool, &retention, now).await.unwrap();
+        assert!(purged.info >= 1 && purged.warning >= 1 && purged.critical >= 1);
+
+        let survivors: Vec<DateTime<Utc>> = sqlx::query_scalar(
+            r#"SELECT created_at FROM "system_log" WHERE subject_id = $1 ORDER BY created_at DESC"#,
+        )
+        .bind(&subject_id)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        let expected: Vec<DateTime<Utc>> = windows
+            .iter()
+            .map(|(_, days)| now - Duration::days(days - 1))
+            .collect();
+        assert_eq!(survivors.len(), expected.len());
+        for (survivor, expected) in survivors.iter().zip(&expected) {
+            assert!((*survivor - *expected).num_seconds().abs() < 1);
+        }
+
+        sqlx::query(r#"DELETE FROM "system_log" WHERE subject_id = $1"#)
+            .bind(&subject_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn page_is_scoped_to_created_at_range() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let subject_id = Uuid::new_v4().to_string();
+
+        for created_at in [day(1), day(2), day(3)] {
+            sqlx::query(
+                r#"
+                INSERT INTO "system_log" (subject_id, subject_type, action, ceverity, function, description, created_at)
+                VALUES ($1, $2, $3, $4, 'test', 'range test', $5)
+                "#,
+            )
+            .bind(&subject_id)
+            .bind(SubjectType::System)
+            .bind(LogAction::Other)
+            .bind(LogCeverity::Info)
+            .bind(created_at)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+
+        let both = get_system_log_page(&pool, range_query(Some(day(2)), Some(day(2))), None)
+            .await
+            .unwrap();
+        assert_eq!(owned_days(&both.page.items, &subject_id), vec![day(2)]);
+
+        let only_from = get_system_log_page(&pool, range_query(Some(day(2)), None), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            owned_days(&only_from.page.items, &subject_id),
+            vec![day(3), day(2)]
+        );
+
+        let only_to = get_system_log_page(&pool, range_query(None, Some(day(2))), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            owned_days(&only_to.page.items, &subject_id),
+            vec![day(2), day(1)]
+        );
+
+        sqlx::query(r#"DELETE FROM "system_log" WHERE subject_id = $1"#)
+            .bind(&subject_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
 }
@@ -1,15 +1,14 @@
-use chrono::Utc;
-use serde_json::json;
-use sqlx::{Pool, Postgres, QueryBuilder, Transaction};
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, Pool, Postgres, QueryBuilder, Transaction};
 use tracing::warn;
 use uuid::Uuid;
 
 use crate::{
-    config::app_config::CONFIG,
+    db::{effective_page_size, page_window_with},
     models::{
         error::ServerError,
         game_base::{Gender, PagedResponse},
-        system_log::{LogAction, LogCeverity},
+        system_log::{LogAction, LogCeverity, LogMetadata},
         user::{
             ActivityStats, Auth0User, AverageUserStats, BaseUser, ListUsersQuery, PatchUserRequest,
             RecentUserStats,
@@ -33,6 +32,8 @@ pub async fn delete_pseudo_user(pool: &Pool<Postgres>, id: Uuid) -> Result<bool,
     Ok(row.rows_affected() == 0)
 }
 
+/// Hard delete, only for erasure requests. Everything else should tombstone the
+/// user with `soft_delete_base_user` so references to the id stay intact
 pub async fn delete_base_user(pool: &Pool<Postgres>, id: Uuid) -> Result<bool, sqlx::Error> {
     let row = sqlx::query!(
         r#"
@@ -47,6 +48,30 @@ pub async fn delete_base_user(pool: &Pool<Postgres>, id: Uuid) -> Result<bool, s
     Ok(row.rows_affected() > 0)
 }
 
+/// Tombstones the user and scrubs personal data, the row and its id are kept
+pub async fn soft_delete_base_user(pool: &Pool<Postgres>, id: Uuid) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query(
+        r#"
+        UPDATE "base_user"
+        SET deleted_at = NOW(),
+            updated_at = NOW(),
+            username = 'deleted_user',
+            auth0_id = NULL,
+            email = NULL,
+            email_verified = NULL,
+            given_name = NULL,
+            family_name = NULL,
+            birth_date = NULL
+        WHERE id = $1 AND deleted_at IS NULL
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(row.rows_affected() > 0)
+}
+
 pub async fn create_pseudo_user(pool: &Pool<Postgres>) -> Result<Uuid, sqlx::Error> {
     let id = Uuid::new_v4();
     let last_active = Utc::now();
@@ -106,7 +131,7 @@ pub async fn ensure_pseudo_user(pool: &Pool<Postgres>, id: Uuid) {
                 .ceverity(LogCeverity::Critical)
                 .function("ensure_psuedo_user")
                 .description("Failed to do insert on pseudo user. Should not fail")
-                .metadata(json!({"error": e.to_string()}))
+                .metadata(LogMetadata::error(e))
                 .log_async();
             warn!("Failed to ensure pseudo user exists for id {}: {}", id, e);
         }
@@ -127,38 +152,59 @@ pub async fn ensure_pseudo_user(pool: &Pool<Postgres>, id: Uuid) {
     };
 }
 
-pub async fn get_base_user_by_auth0_id(
-    pool: &Pool<Postgres>,
+pub async fn get_base_user_by_auth0_id<'e, E>(
+    executor: E,
     auth0_id: &str,
-) -> Result<Option<BaseUser>, sqlx::Error> {
-    sqlx::query_as!(
-        BaseUser,
+) -> Result<Option<BaseUser>, sqlx::Error>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    sqlx::query_as::<_, BaseUser>(
         r#"
-        SELECT id, username, auth0_id, birth_date, gender as "gender: _", email,
+        SELECT id, username, auth0_id, birth_date, gender, email,
             email_verified, family_name, updated_at, given_name, created_at
         FROM "base_user"
-        WHERE auth0_id = $1
+        WHERE auth0_id = $1 AND deleted_at IS NULL
         "#,
-        auth0_id
     )
-    .fetch_optional(pool)
+    .bind(auth0_id)
+    .fetch_optional(executor)
     .await
 }
 
+/// Returns the user registered for `auth0_id` and whether it was created now.
+/// Retried registration webhooks are serialized on a transaction scoped
+/// advisory lock, so the lookup and insert can not race each other.
+pub async fn get_or_create_base_user(
+    tx: &mut Transaction<'_, Postgres>,
+    auth0_user: &Auth0User,
+) -> Result<(Uuid, bool), ServerError> {
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+        .bind(&auth0_user.auth0_id)
+        .execute(tx.as_mut())
+        .await?;
+
+    if let Some(existing) = get_base_user_by_auth0_id(tx.as_mut(), &auth0_user.auth0_id).await? {
+        return Ok((existing.id, false));
+    }
+
+    let id = create_base_user(tx, auth0_user).await?;
+    Ok((id, true))
+}
+
 pub async fn get_base_user_by_id(
     pool: &Pool<Postgres>,
     user_id: Uuid,
 ) -> Result<Option<BaseUser>, sqlx::Error> {
-    sqlx::query_as!(
-        BaseUser,
+    sqlx::query_as::<_, BaseUser>(
         r#"
-        SELECT id, username, auth0_id, birth_date, gender as "gender: _", email,
+        SELECT id, username, auth0_id, birth_date, gender, email,
             email_verified, family_name, updated_at, given_name, created_at
         FROM "base_user"
-        WHERE id = $1
+        WHERE id = $1 AND deleted_at IS NULL
         "#,
-        user_id
     )
+    .bind(user_id)
     .fetch_optional(pool)
     .await
 }
@@ -175,80 +221,55 @@ pub async fn create_base_user(
     tx: &mut Transaction<'_, Postgres>,
     auth0_user: &Auth0User,
 ) -> Result<Uuid, ServerError> {
-    let email = auth0_user.email.clone().unwrap_or("Kenneth".to_string());
-    let split = email.split('@').next().unwrap_or("Kenneth").to_string();
-
-    let username = match &auth0_user.username {
-        Some(username) => username.to_string(),
-        None => split,
-    };
-
-    // Extract names safely, with fallbacks to username split
-    let given_name: &str = auth0_user
-        .given_name
-        .as_deref()
-        .unwrap_or_else(|| username.split('.').next().unwrap_or("John"));
-
-    let family_name: &str = auth0_user
-        .family_name
-        .as_deref()
-        .unwrap_or_else(|| username.split('.').nth(1).unwrap_or("Doe"));
-
     let id = Uuid::new_v4();
-    let gender = Gender::Unknown;
-    let email_value = auth0_user
-        .email
-        .clone()
-        .unwrap_or(format!("{}@mail.com", Uuid::new_v4()));
+    let username = auth0_user.derive_username(id);
 
-    let id = sqlx::query_scalar!(
+    // Missing Auth0 fields are stored as NULL, see `Auth0User::missing_fields`
+    let id = sqlx::query_scalar::<_, Uuid>(
         r#"
         INSERT INTO "base_user" (id, username, auth0_id, gender, email, email_verified, updated_at, family_name, given_name, created_at)
         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
         RETURNING id
         "#,
-        id,
-        username,
-        auth0_user.auth0_id,
-        gender as _,
-        email_value,
-        auth0_user.email_verified,
-        auth0_user.updated_at,
-        family_name,
-        given_name,
-        auth0_user.created_at
     )
+    .bind(id)
+    .bind(username)
+    .bind(&auth0_user.auth0_id)
+    .bind(Gender::Unknown)
+    .bind(&auth0_user.email)
+    .bind(auth0_user.email_verified)
+    .bind(auth0_user.updated_at)
+    .bind(&auth0_user.family_name)
+    .bind(&auth0_user.given_name)
+    .bind(auth0_user.created_at)
     .fetch_one(&mut **tx)
     .await?;
 
     Ok(id)
 }
 
-pub async fn update_pseudo_user_activity(
+/// Applies a batch of `(pseudo_id, last_active)` pairs in a single update,
+/// an older timestamp never overwrites a newer one
+pub async fn update_pseudo_users_activity(
     pool: &Pool<Postgres>,
-    id: Uuid,
-) -> Result<(), ServerError> {
-    let last_active = Utc::now();
-    let row = sqlx::query!(
+    activity: &[(Uuid, DateTime<Utc>)],
+) -> Result<u64, sqlx::Error> {
+    let (ids, last_active): (Vec<Uuid>, Vec<DateTime<Utc>>) = activity.iter().copied().unzip();
+
+    let row = sqlx::query(
         r#"
-        UPDATE "pseudo_user"
-        SET last_active = $1
-        WHERE id = $2
+        UPDATE "pseudo_user" AS p
+        SET last_active = GREATEST(p.last_active, batch.last_active)
+        FROM UNNEST($1::uuid[], $2::timestamptz[]) AS batch(id, last_active)
+        WHERE p.id = batch.id
         "#,
-        last_active,
-        id
     )
+    .bind(ids)
+    .bind(last_active)
     .execute(pool)
     .await?;
 
-    if row.rows_affected() == 0 {
-        return Err(ServerError::NotFound(format!(
-            "User with id {} does not exist",
-            id
-        )));
-    }
-
-    Ok(())
+    Ok(row.rows_affected())
 }
 
 pub async fn patch_base_user_by_id(
@@ -295,37 +316,45 @@ pub async fn patch_base_user_by_id(
 pub async fn list_base_users(
     pool: &Pool<Postgres>,
     request: ListUsersQuery,
+    include_deleted: bool,
 ) -> Result<PagedResponse<BaseUser>, sqlx::Error> {
-    let offset = CONFIG.server.page_size * request.page_num;
-    let limit = CONFIG.server.page_size + 1;
+    let page_size = effective_page_size(request.page_size);
+    let (limit, offset) = page_window_with(request.page_num, page_size);
 
-    let mut users = sqlx::query_as!(
-        BaseUser,
+    let users_fut = sqlx::query_as::<_, BaseUser>(
         r#"
-        SELECT id, username, auth0_id, birth_date, gender as "gender: _", email, email_verified, updated_at, family_name, given_name, created_at
+        SELECT id, username, auth0_id, birth_date, gender, email, email_verified, updated_at, family_name, given_name, created_at
         FROM "base_user"
+        WHERE $1 OR deleted_at IS NULL
         ORDER BY created_at DESC
-        LIMIT $1 OFFSET $2
+        LIMIT $2 OFFSET $3
         "#,
-        limit as i64,
-        offset as i64
     )
-    .fetch_all(pool)
-    .await?;
+    .bind(include_deleted)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool);
 
-    let has_next = users.len() > CONFIG.server.page_size as usize;
+    let total_fut = sqlx::query_scalar::<_, i64>(
+        r#"SELECT COUNT(*) FROM "base_user" WHERE $1 OR deleted_at IS NULL"#,
+    )
+    .bind(include_deleted)
+    .fetch_one(pool);
+
+    let (users, total) = tokio::join!(users_fut, total_fut);
+    let mut users = users?;
+
+    let has_next = users.len() > page_size as usize;
     if has_next {
         users.pop();
     }
 
-    let response = PagedResponse {
-        page_num: request.page_num,
-        items: users,
-        has_prev: request.page_num > 0,
+    Ok(PagedResponse::with_total(
+        request.page_num,
+        users,
         has_next,
-    };
-
-    Ok(response)
+        total?,
+    ))
 }
 
 pub async fn get_user_activity_stats(pool: &Pool<Postgres>) -> Result<ActivityStats, sqlx::Error> {
@@ -405,3 +434,312 @@ pub async fn get_user_activity_stats(pool: &Pool<Postgres>) -> Result<ActivitySt
         average: average?,
     })
 }
+
+/// Same shape as `get_user_activity_stats`, with the recent windows ending at
+/// `to` and the averages bucketed over `[from, to)` instead of fixed trailing windows
+pub async fn get_user_activity_stats_between(
+    pool: &Pool<Postgres>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<ActivityStats, sqlx::Error> {
+    let recent_fut = sqlx::query_as::<_, RecentUserStats>(
+        r#"
+        SELECT
+            COUNT(*) FILTER (WHERE last_active >= date_trunc('month', $1::timestamptz)) AS this_month_users,
+            COUNT(*) FILTER (WHERE last_active >= date_trunc('week', $1::timestamptz)) AS this_week_users,
+            COUNT(*) FILTER (WHERE last_active >= date_trunc('day', $1::timestamptz)) AS todays_users
+        FROM pseudo_user
+        WHERE last_active < $1
+        "#,
+    )
+    .bind(to)
+    .fetch_one(pool);
+
+    let average_fut = sqlx::query_as::<_, AverageUserStats>(
+        r#"
+        SELECT
+            COALESCE((
+                SELECT FLOOR(AVG(cnt))::float8
+                FROM (
+                    SELECT COUNT(*) AS cnt
+                    FROM pseudo_user
+                    WHERE last_active >= $1 AND last_active < $2
+                    GROUP BY date_trunc('month', last_active)
+                ) t
+            ), 0) AS avg_month_users,
+            COALESCE((
+                SELECT FLOOR(AVG(cnt))::float8
+                FROM (
+                    SELECT COUNT(*) AS cnt
+                    FROM pseudo_user
+                    WHERE last_active >= $1 AND last_active < $2
+                    GROUP BY date_trunc('week', last_active)
+                ) t
+            ), 0) AS avg_week_users,
+            COALESCE((
+                SELECT FLOOR(AVG(cnt))::float8
+                FROM (
+                    SELECT COUNT(*) AS cnt
+                    FROM pseudo_user
+                    WHERE last_active >= $1 AND last_active < $2
+                    GROUP BY last_active::date
+                ) t
+            ), 0) AS avg_daily_users
+        "#,
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_one(pool);
+
+    let total_game_count_fut =
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*)::bigint FROM game_base").fetch_one(pool);
+
+    let total_user_count_fut =
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*)::bigint FROM pseudo_user").fetch_one(pool);
+
+    let (recent, average, total_game_count, total_user_count) = tokio::join!(
+        recent_fut,
+        average_fut,
+        total_game_count_fut,
+        total_user_count_fut
+    );
+
+    Ok(ActivityStats {
+        total_game_count: total_game_count?,
+        total_user_count: total_user_count?,
+        recent: recent?,
+        average: average?,
+    })
+}
+
+/// Case-insensitive, `exclude` lets a user keep their own name
+pub async fn username_taken(
+    pool: &Pool<Postgres>,
+    username: &str,
+    exclude: Option<Uuid>,
+) -> Result<bool, sqlx::Error> {
+    let taken = sqlx::query_scalar::<_, i32>(
+        r#"
+        SELECT 1 FROM "base_user"
+        WHERE LOWER(username) = LOWER($1)
+        AND ($2::uuid IS NULL OR id <> $2)
+        LIMIT 1
+        "#,
+    )
+    .bind(username)
+    .bind(exclude)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(taken.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    use crate::{
+        models::user::{Auth0User, ListUsersQuery},
+        tests::db::dev_pool,
+    };
+
+    use super::{
+        get_base_user_by_id, get_or_create_base_user, list_base_users, soft_delete_base_user,
+        username_taken,
+    };
+
+    #[tokio::test]
+    async fn repeated_registration_creates_a_single_user() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let auth0_id = format!("auth0|{}", Uuid::new_v4());
+        let auth0_user = Auth0User {
+            auth0_id: auth0_id.clone(),
+            email: None,
+            email_verified: Some(false),
+            username: Some("retried.trigger".into()),
+            phone_number: None,
+            phone_verified: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            name: None,
+            nickname: None,
+            given_name: None,
+            family_name: None,
+        };
+
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            let mut tx = pool.begin().await.unwrap();
+            ids.push(get_or_create_base_user(&mut tx, &auth0_user).await.unwrap());
+            tx.commit().await.unwrap();
+        }
+
+        assert!(ids[0].1);
+        assert!(!ids[1].1);
+        assert_eq!(ids[0].0, ids[1].0);
+
+        let count: i64 =
+            sqlx::query_scalar(r#"SELECT COUNT(*) FROM "base_user" WHERE auth0_id = $1"#)
+                .bind(&auth0_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(count, 1);
+
+        sqlx::query(r#"DELETE FROM "base_user" WHERE auth0_id = $1"#)
+            .bind(&auth0_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn user_with_only_auth0_id_is_stored_without_made_up_fields() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let auth0_id = format!("auth0|{}", Uuid::new_v4());
+        let auth0_user = Auth0User {
+            auth0_id: auth0_id.clone(),
+            email: None,
+            email_verified: None,
+            username: None,
+            phone_number: None,
+            phone_verified: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            name: None,
+            nickname: None,
+            given_name: None,
+            family_name: None,
+        };
+
+        let mut tx = pool.begin().await.unwrap();
+        let (user_id, _) = get_or_create_base_user(&mut tx, &auth0_user).await.unwrap();
+        tx.commit().await.unwrap();
+
+        let user = get_base_user_by_id(&pool, user_id).await.unwrap().unwrap();
+        assert!(user.username.starts_with("user_"));
+        assert_eq!(user.email, None);
+        assert_eq!(user.given_name, None);
+        assert_eq!(user.family_name, None);
+
+        sqlx::query(r#"DELETE FROM "base_user" WHERE id = $1"#)
+            .bind(user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn deleted_users_are_listed_only_when_included() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let deleted_id = Uuid::new_v4();
+
+        sqlx::query(
+            r#"INSERT INTO "base_user" (id, username, deleted_at) VALUES ($1, 'tombstoned', NOW())"#,
+        )
+        .bind(deleted_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let query = || ListUsersQuery {
+            page_num: 0,
+            page_size: None,
+            include_deleted: None,
+        };
+
+        let excluded = list_base_users(&pool, query(), false).await.unwrap();
+        assert!(excluded.items.iter().all(|user| user.id != deleted_id));
+
+        let included = list_base_users(&pool, query(), true).await.unwrap();
+        assert!(included.items.iter().any(|user| user.id == deleted_id));
+
+        sqlx::query(r#"DELETE FROM "base_user" WHERE id = $1"#)
+            .bind(deleted_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn soft_delete_scrubs_personal_data_and_hides_user() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let user_id = Uuid::new_v4();
+
+        sqlx::query(
+            r#"INSERT INTO "base_user" (id, username, email, given_name) VALUES ($1, 'soon_gone', 'gone@tero.test', 'Gone')"#,
+        )
+        .bind(user_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert!(soft_delete_base_user(&pool, user_id).await.unwrap());
+        assert!(!soft_delete_base_user(&pool, user_id).await.unwrap());
+        assert!(get_base_user_by_id(&pool, user_id).await.unwrap().is_none());
+
+        let (email, given_name): (Option<String>, Option<String>) =
+            sqlx::query_as(r#"SELECT email, given_name FROM "base_user" WHERE id = $1"#)
+                .bind(user_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert!(email.is_none() && given_name.is_none());
+
+        sqlx::query(r#"DELETE FROM "base_user" WHERE id = $1"#)
+            .bind(user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn username_taken_is_case_insensitive() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let user_id = Uuid::new_v4();
+        let username = format!("Taken_{}", &user_id.simple().to_string()[..8]);
+
+        sqlx::query(r#"INSERT INTO "base_user" (id, username) VALUES ($1, $2)"#)
+            .bind(user_id)
+            .bind(&username)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let free = format!("free_{}", &Uuid::new_v4().simple().to_string()[..8]);
+
+        assert!(
+            username_taken(&pool, &username.to_lowercase(), None)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !username_taken(&pool, &username, Some(user_id))
+                .await
+                .unwrap()
+        );
+        assert!(!username_taken(&pool, &free, None).await.unwrap());
+
+        sqlx::query(r#"DELETE FROM "base_user" WHERE id = $1"#)
+            .bind(user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
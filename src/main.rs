@@ -1,6 +1,7 @@
+use std::net::SocketAddr;
+
 use axum::{Router, middleware::from_fn_with_state, routing::post};
 use dotenvy::dotenv;
-use sqlx::Pool;
 use tracing::{error, info};
 use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -10,13 +11,13 @@ use crate::{
         game::game_routes,
         game_tip::{protected_game_tip_routes, public_game_tip_routes},
         health::health_routes,
+        integration::integration_routes,
         system_log::log_routes,
         user::{auth0_trigger_endpoint, protected_auth_routes, public_auth_routes},
         webhook_mw::webhook_mw,
     },
     app_state::AppState,
     config::app_config::CONFIG,
-    models::integration::INTEGRATION_NAMES,
 };
 
 mod api;
@@ -40,7 +41,17 @@ async fn main() {
 
     // Connect once, run migrations, then hand the pool to AppState.
     // KeyVault queries the DB on init so migrations must run first.
-    let pool = Pool::<sqlx::Postgres>::connect(&CONFIG.database_url)
+    let db_config = &CONFIG.database;
+    info!(
+        "Database pool: max {} / min {} connections, acquire timeout {}s, idle timeout {}s",
+        db_config.max_connections,
+        db_config.min_connections,
+        db_config.acquire_timeout_secs,
+        db_config.idle_timeout_secs
+    );
+    let pool = db_config
+        .pool_options()
+        .connect(&CONFIG.database_url)
         .await
         .unwrap_or_else(|e| panic!("Failed to connect to database: {}", e));
     if let Err(e) = sqlx::migrate!().run(&pool).await {
@@ -54,10 +65,10 @@ async fn main() {
 
     // Spawn cron jobs
     state.spawn_game_cleanup();
+    state.spawn_log_cleanup();
+    state.spawn_jwks_refresh();
     state.spawn_round_pool_job();
-
-    // Force static initialization of INTEGRATION_NAMES from config
-    let _ = &*INTEGRATION_NAMES;
+    state.spawn_activity_flush();
 
     let event_routes = Router::new()
         .route("/{pseudo_id}", post(auth0_trigger_endpoint))
@@ -74,6 +85,7 @@ async fn main() {
         .nest("/users", protected_auth_routes(state.clone()))
         .nest("/logs", log_routes(state.clone()))
         .nest("/tips", protected_game_tip_routes(state.clone()))
+        .nest("/integrations", integration_routes(state.clone()))
         .layer(from_fn_with_state(state.clone(), auth_mw));
 
     let app = Router::new()
@@ -91,5 +103,50 @@ async fn main() {
         "Server listening on address: {}",
         listener.local_addr().unwrap()
     );
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .unwrap();
+
+    info!("Shutting down, draining background work");
+    state.shutdown();
+    state.drain_tasks().await;
+    state.flush_activity().await;
+    state.flush_logs().await;
+    state.get_pool().close().await;
+}
+
+/// Resolves on SIGINT or SIGTERM, after which axum stops accepting connections
+/// and waits for in-flight requests
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for ctrl-c: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
 }
@@ -2,20 +2,49 @@ use std::collections::HashSet;
 
 use serde::{Deserialize, Serialize};
 
-use crate::models::user::Permission;
+use crate::models::{error::ServerError, user::Permission};
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Jwks {
-    pub keys: [Jwk; 2],
+    pub keys: Vec<Jwk>,
+}
+
+impl Jwks {
+    pub fn has_kid(&self, kid: &str) -> bool {
+        self.keys.iter().any(|jwk| jwk.kid == kid)
+    }
+
+    /// The number of keys varies across tenants and rotations, but an empty set
+    /// would reject every token
+    pub fn ensure_not_empty(&self) -> Result<(), ServerError> {
+        match self.keys.is_empty() {
+            true => Err(ServerError::JwtVerification(
+                "JWKS does not contain any keys".into(),
+            )),
+            false => Ok(()),
+        }
+    }
 }
 
 #[allow(dead_code)]
 #[derive(Debug, Deserialize, Clone)]
 pub struct Jwk {
     pub kid: String,
+    /// RSA modulus and exponent, empty for EC keys
+    #[serde(default)]
     pub n: String,
+    #[serde(default)]
     pub e: String,
+    /// EC curve and point, empty for RSA keys
+    #[serde(default)]
+    pub crv: String,
+    #[serde(default)]
+    pub x: String,
+    #[serde(default)]
+    pub y: String,
     pub kty: String,
+    /// Optional per RFC 7517, see `jwk_algorithm` for the fallback
+    #[serde(default)]
     pub alg: String,
     #[serde(rename(deserialize = "use"))]
     pub use_: String,
@@ -51,6 +80,20 @@ pub struct Claims {
     pub permissions: Option<HashSet<Permission>>,
 }
 
+/// Outcome of checking claims against a set of required permissions.
+/// `granted` holds every permission the subject has, for audit purposes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PermissionCheck {
+    pub granted: HashSet<Permission>,
+    pub missing: HashSet<Permission>,
+}
+
+impl PermissionCheck {
+    pub fn is_allowed(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
 fn deserialize_aud<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -81,20 +124,56 @@ impl Claims {
         &self.sub
     }
 
+    pub fn permission_check<I>(&self, required: I) -> PermissionCheck
+    where
+        I: IntoIterator<Item = Permission>,
+    {
+        let granted = self.permissions.clone().unwrap_or_default();
+        let missing: HashSet<Permission> = required
+            .into_iter()
+            .filter(|p: &Permission| !granted.contains(p))
+            .collect();
+
+        PermissionCheck { granted, missing }
+    }
+
     pub fn missing_permission<I>(&self, required: I) -> Option<HashSet<Permission>>
     where
         I: IntoIterator<Item = Permission>,
     {
-        let required_iter = required.into_iter();
-        let permissions = match &self.permissions {
-            None => return Some(required_iter.collect()),
-            Some(perm) => perm,
-        };
-
-        let missing: HashSet<Permission> = required_iter
-            .filter(|p: &Permission| !permissions.contains(p))
+        let check = self.permission_check(required);
+        (!check.is_allowed()).then_some(check.missing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::Jwks;
+
+    fn key_set(kids: &[&str]) -> Jwks {
+        let keys: Vec<_> = kids
+            .iter()
+            .map(|kid| {
+                json!({
+                    "kid": kid,
+                    "n": "n",
+                    "e": "AQAB",
+                    "kty": "RSA",
+                    "alg": "RS256",
+                    "use": "sig",
+                })
+            })
             .collect();
 
-        (!missing.is_empty()).then_some(missing)
+        serde_json::from_value(json!({ "keys": keys })).unwrap()
+    }
+
+    #[test]
+    fn key_sets_of_any_size_are_accepted() {
+        assert!(key_set(&["a"]).ensure_not_empty().is_ok());
+        assert!(key_set(&["a", "b", "c"]).has_kid("c"));
+        assert!(key_set(&[]).ensure_not_empty().is_err());
     }
 }
@@ -1,17 +1,28 @@
-use std::{collections::HashSet, time::SystemTimeError};
+use std::time::SystemTimeError;
 
-use axum::{http::StatusCode, response::IntoResponse};
+use axum::{
+    Json,
+    http::{HeaderValue, StatusCode, header::RETRY_AFTER},
+    response::IntoResponse,
+};
+use chrono::Utc;
+use serde_json::json;
 use thiserror::Error;
 use tracing::{error, warn};
 
 use crate::{
-    api::gs_client::GSClientError, models::user::Permission, service::key_vault::KeyVaultError,
+    api::gs_client::GSClientError,
+    models::auth::PermissionCheck,
+    service::{key_vault::KeyVaultError, rate_limiter::RateLimited},
 };
 
 #[derive(Debug, Error)]
 pub enum ServerError {
     #[error("Sqlx failed: {0}")]
-    Sqlx(#[from] sqlx::Error),
+    Sqlx(#[source] sqlx::Error),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
 
     #[error("Internal server error: {0}")]
     Internal(String),
@@ -20,11 +31,14 @@ pub enum ServerError {
     Api(StatusCode, String),
 
     #[error("Permission error")]
-    Permission(HashSet<Permission>),
+    Permission(PermissionCheck),
 
     #[error("Access denied error")]
     AccessDenied,
 
+    #[error("Rate limited, retry after {0:?}")]
+    RateLimited(RateLimited),
+
     #[error("Not found: {0}")]
     NotFound(String),
 
@@ -47,6 +61,24 @@ pub enum ServerError {
     TimeCreation(#[from] SystemTimeError),
 }
 
+/// Unique violations become `Conflict`, any other database error stays a 500
+impl From<sqlx::Error> for ServerError {
+    fn from(e: sqlx::Error) -> Self {
+        let constraint = e
+            .as_database_error()
+            .filter(|db| db.is_unique_violation())
+            .map(|db| db.constraint().unwrap_or("unknown").to_string());
+
+        match constraint {
+            Some(constraint) => {
+                warn!("Unique constraint {} violated", constraint);
+                ServerError::Conflict(String::from("Resource already exists"))
+            }
+            None => ServerError::Sqlx(e),
+        }
+    }
+}
+
 impl IntoResponse for ServerError {
     fn into_response(self) -> axum::response::Response {
         match self {
@@ -62,17 +94,25 @@ impl IntoResponse for ServerError {
                 error!("Api error: {} - {}", sc, msg);
                 (sc, msg)
             }
-            ServerError::Permission(missing) => {
-                warn!("Missing permission: {:?}", missing);
+            ServerError::Permission(check) => {
+                warn!(
+                    "Missing permission: {:?}, granted: {:?}",
+                    check.missing, check.granted
+                );
                 (
                     StatusCode::FORBIDDEN,
-                    format!("Missing permission: {:?}", missing),
+                    format!("Missing permission: {:?}", check.missing),
                 )
             }
             ServerError::NotFound(e) => {
                 warn!("Entity not found: {}", e);
                 (StatusCode::NOT_FOUND, e)
             }
+            ServerError::Conflict(e) => {
+                warn!("Conflict: {}", e);
+                (StatusCode::CONFLICT, e)
+            }
+            ServerError::RateLimited(limited) => return rate_limited_response(limited),
             ServerError::AccessDenied => {
                 warn!("Access denied for requesting entity");
                 (StatusCode::FORBIDDEN, String::from("Access denied"))
@@ -111,3 +151,141 @@ impl IntoResponse for ServerError {
         .into_response()
     }
 }
+
+/// Turns a missing row into a 404, a bare `RowNotFound` would otherwise become an opaque 500
+pub trait SqlxResultExt<T> {
+    fn not_found(self, msg: impl Into<String>) -> Result<T, ServerError>;
+}
+
+impl<T> SqlxResultExt<T> for Result<T, sqlx::Error> {
+    fn not_found(self, msg: impl Into<String>) -> Result<T, ServerError> {
+        self.map_err(|e| match e {
+            sqlx::Error::RowNotFound => ServerError::NotFound(msg.into()),
+            e => ServerError::from(e),
+        })
+    }
+}
+
+/// Same as `SqlxResultExt` for lookups made with `fetch_optional`
+pub trait OptionalExt<T> {
+    fn not_found(self, msg: impl Into<String>) -> Result<T, ServerError>;
+}
+
+impl<T> OptionalExt<T> for Option<T> {
+    fn not_found(self, msg: impl Into<String>) -> Result<T, ServerError> {
+        self.ok_or_else(|| ServerError::NotFound(msg.into()))
+    }
+}
+
+/// 429 with a `Retry-After` header and a body describing the exhausted limit
+fn rate_limited_response(limited: RateLimited) -> axum::response::Response {
+    let retry_after_secs = limited.retry_after_secs();
+    warn!("Rate limited, retry after {}s", retry_after_secs);
+
+    let reset_at =
+        Utc::now() + chrono::Duration::milliseconds(limited.retry_after.as_millis() as i64);
+    let body = json!({
+        "error": "Too many requests",
+        "limit": limited.limit,
+        "retry_after_secs": retry_after_secs,
+        "reset_at": reset_at,
+    });
+
+    let mut response = (StatusCode::TOO_MANY_REQUESTS, Json(body)).into_response();
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(retry_after_secs));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::{
+        body::to_bytes,
+        http::{StatusCode, header::RETRY_AFTER},
+        response::IntoResponse,
+    };
+
+    use crate::service::rate_limiter::RateLimited;
+
+    use crate::tests::db::dev_pool;
+
+    use super::{OptionalExt, ServerError, SqlxResultExt};
+
+    #[tokio::test]
+    async fn rate_limited_response_has_retry_after_and_body() {
+        let response = ServerError::RateLimited(RateLimited {
+            limit: 20,
+            retry_after: Duration::from_millis(2500),
+        })
+        .into_response();
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "3");
+
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["limit"], 20);
+        assert_eq!(body["retry_after_secs"], 3);
+        assert!(body["reset_at"].is_string());
+    }
+
+    #[test]
+    fn missing_row_becomes_not_found() {
+        let missing: Result<u8, sqlx::Error> = Err(sqlx::Error::RowNotFound);
+        assert!(matches!(
+            missing.not_found("Game not found"),
+            Err(ServerError::NotFound(msg)) if msg == "Game not found"
+        ));
+
+        let broken: Result<u8, sqlx::Error> = Err(sqlx::Error::PoolTimedOut);
+        assert!(matches!(
+            broken.not_found("Game not found"),
+            Err(ServerError::Sqlx(sqlx::Error::PoolTimedOut))
+        ));
+
+        let found: Result<u8, sqlx::Error> = Ok(1);
+        assert_eq!(found.not_found("Game not found").unwrap(), 1);
+    }
+
+    #[test]
+    fn missing_option_becomes_not_found() {
+        assert!(matches!(
+            None::<u8>.not_found("User not found"),
+            Err(ServerError::NotFound(_))
+        ));
+        assert_eq!(Some(1).not_found("User not found").unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn unique_violation_is_a_conflict() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let subject = format!("test_{}", uuid::Uuid::new_v4().simple());
+
+        let mut results = Vec::new();
+        for _ in 0..2 {
+            results.push(
+                sqlx::query(r#"INSERT INTO "integration" (subject, name) VALUES ($1, 'platform')"#)
+                    .bind(&subject)
+                    .execute(&pool)
+                    .await,
+            );
+        }
+
+        sqlx::query(r#"DELETE FROM "integration" WHERE subject = $1"#)
+            .bind(&subject)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(results[0].is_ok());
+        let error = ServerError::from(results.pop().unwrap().unwrap_err());
+        assert!(matches!(error, ServerError::Conflict(_)));
+        assert_eq!(error.into_response().status(), StatusCode::CONFLICT);
+    }
+}
@@ -2,19 +2,99 @@ use core::fmt;
 use std::hash::Hash;
 
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, de::IntoDeserializer};
+use sqlx::{
+    Postgres,
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgHasArrayType, PgTypeInfo, PgValueRef},
+};
+use tracing::warn;
 use uuid::Uuid;
 use validator::Validate;
 
 use crate::models::{
-    imposter_game::ImposterSession, quiz_game::QuizSession, spin_game::SpinSession,
+    imposter_game::ImposterSession,
+    quiz_game::{QuizQuestion, QuizSession},
+    spin_game::SpinSession,
 };
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GamePagedRequest {
     pub page_num: Option<u16>,
+    /// Clamped to `server.max_page_size`, defaults to `server.page_size`
+    pub page_size: Option<u16>,
     pub game_type: Option<GameType>,
     pub category: Option<GameCategory>,
+    /// Comma separated, e.g. `categories=Girls,Boys`, combined with `category`
+    #[serde(default, deserialize_with = "deserialize_categories")]
+    pub categories: Option<Vec<GameCategory>>,
+    pub sort: Option<GameSort>,
+}
+
+/// Orderings offered when browsing games, each maps to a fixed `ORDER BY`
+#[derive(Debug, Serialize, Deserialize, Hash, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GameSort {
+    #[default]
+    MostPlayed,
+    /// By `last_played`, games have no creation timestamp
+    Newest,
+    Alphabetical,
+}
+
+impl GameSort {
+    /// Ends on `id` so rows with equal sort values page deterministically
+    pub fn order_by(&self) -> &'static str {
+        match self {
+            GameSort::MostPlayed => " ORDER BY times_played DESC, id",
+            GameSort::Newest => " ORDER BY last_played DESC, id",
+            GameSort::Alphabetical => " ORDER BY LOWER(name), id",
+        }
+    }
+}
+
+impl GamePagedRequest {
+    /// Every requested category, sorted and deduplicated. Empty means no filter.
+    pub fn category_filter(&self) -> Vec<GameCategory> {
+        let mut categories: Vec<GameCategory> = self
+            .category
+            .iter()
+            .chain(self.categories.iter().flatten())
+            .cloned()
+            .collect();
+
+        categories.sort();
+        categories.dedup();
+        categories
+    }
+}
+
+fn deserialize_categories<'de, D>(deserializer: D) -> Result<Option<Vec<GameCategory>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(raw) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+
+    raw.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| GameCategory::deserialize(item.into_deserializer()))
+        .collect::<Result<Vec<_>, D::Error>>()
+        .map(Some)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PlayerCountRequest {
+    pub count: u16,
+}
+
+/// Persist endpoints answer with a bare status unless `return_game` is set
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PersistGameQuery {
+    #[serde(default)]
+    pub return_game: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -23,6 +103,28 @@ pub struct PagedResponse<T> {
     pub items: Vec<T>,
     pub has_next: bool,
     pub has_prev: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<i64>,
+}
+
+impl<T> PagedResponse<T> {
+    pub fn new(page_num: u16, items: Vec<T>, has_next: bool) -> Self {
+        Self {
+            page_num,
+            items,
+            has_next,
+            has_prev: page_num > 0,
+            total: None,
+        }
+    }
+
+    /// Same as `new`, for pages that also counted every row matching their filters
+    pub fn with_total(page_num: u16, items: Vec<T>, has_next: bool, total: i64) -> Self {
+        Self {
+            total: Some(total),
+            ..Self::new(page_num, items, has_next)
+        }
+    }
 }
 
 pub trait JsonConverter {
@@ -47,6 +149,8 @@ pub struct GameBase {
     pub iterations: i32,
     pub times_played: i32,
     pub last_played: DateTime<Utc>,
+    #[sqlx(default)]
+    pub description: Option<String>,
 }
 
 impl GameBase {
@@ -65,27 +169,94 @@ impl GameBase {
             iterations,
             times_played: 1, // If the user manages to get to the create screen the game has been played 1 time.
             last_played: Utc::now(),
+            description: None,
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Hash, Clone, PartialEq, Eq, sqlx::Type)]
-#[sqlx(type_name = "game_category", rename_all = "lowercase")]
+/// Rounds of a saved game, returned as is so the client can re-host it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedGameContent {
+    pub game: GameBase,
+    pub rounds: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Hash, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum GameCategory {
     Girls,
     Boys,
     Mixed,
     InnerCircle,
+    /// Fallback for database values this build does not know about.
+    /// Never accepted from clients and never written back to the database.
+    #[serde(skip_deserializing)]
+    Unknown,
+}
+
+impl GameCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameCategory::Girls => "girls",
+            GameCategory::Boys => "boys",
+            GameCategory::Mixed => "mixed",
+            GameCategory::InnerCircle => "innercircle",
+            GameCategory::Unknown => "unknown",
+        }
+    }
+
+    fn from_db(value: &str) -> Self {
+        match value {
+            "girls" => GameCategory::Girls,
+            "boys" => GameCategory::Boys,
+            "mixed" => GameCategory::Mixed,
+            "innercircle" => GameCategory::InnerCircle,
+            other => {
+                warn!(
+                    "Unknown game category '{}' in database, falling back to Unknown",
+                    other
+                );
+                GameCategory::Unknown
+            }
+        }
+    }
 }
 
 impl fmt::Display for GameCategory {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            GameCategory::Girls => write!(f, "girls"),
-            GameCategory::Boys => write!(f, "boys"),
-            GameCategory::Mixed => write!(f, "mixed"),
-            GameCategory::InnerCircle => write!(f, "innercircle"),
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl sqlx::Type<Postgres> for GameCategory {
+    fn type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("game_category")
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        *ty == Self::type_info() || <&str as sqlx::Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl PgHasArrayType for GameCategory {
+    fn array_type_info() -> PgTypeInfo {
+        PgTypeInfo::array_of("game_category")
+    }
+}
+
+impl<'r> sqlx::Decode<'r, Postgres> for GameCategory {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        let value = <&str as sqlx::Decode<Postgres>>::decode(value)?;
+        Ok(Self::from_db(value))
+    }
+}
+
+impl sqlx::Encode<'_, Postgres> for GameCategory {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        if *self == GameCategory::Unknown {
+            return Err("Unknown game category cannot be written to the database".into());
         }
+
+        <&str as sqlx::Encode<Postgres>>::encode_by_ref(&self.as_str(), buf)
     }
 }
 
@@ -100,6 +271,9 @@ pub enum Gender {
     Unknown,
 }
 
+/// Every type can be initiated statically on a single device. Roulette and
+/// Duel can also be hosted as interactive sessions, while Quiz and Imposter
+/// only use the session service while their rounds are being created.
 #[derive(Debug, Serialize, Deserialize, Hash, Clone, PartialEq, Eq, sqlx::Type, Copy)]
 #[sqlx(type_name = "game_type", rename_all = "lowercase")]
 pub enum GameType {
@@ -110,6 +284,13 @@ pub enum GameType {
 }
 
 impl GameType {
+    pub const ALL: [GameType; 4] = [
+        GameType::Roulette,
+        GameType::Duel,
+        GameType::Quiz,
+        GameType::Imposter,
+    ];
+
     pub fn as_str(&self) -> &'static str {
         match self {
             GameType::Quiz => "quiz",
@@ -119,6 +300,20 @@ impl GameType {
         }
     }
 
+    /// Can be created and played on a single device without `tero.session`.
+    /// Spin games qualify since the requesting user hosts them locally.
+    pub fn supports_standalone(&self) -> bool {
+        matches!(
+            self,
+            GameType::Quiz | GameType::Imposter | GameType::Roulette | GameType::Duel
+        )
+    }
+
+    /// Can be hosted as an interactive session on `tero.session`
+    pub fn supports_session(&self) -> bool {
+        matches!(self, GameType::Roulette | GameType::Duel)
+    }
+
     pub fn hub_name(&self) -> &'static str {
         match self {
             GameType::Quiz => "quiz",
@@ -126,21 +321,68 @@ impl GameType {
             GameType::Imposter => "imposter",
         }
     }
+
+    /// Table holding the rounds of persisted games of this type
+    pub fn table_name(&self) -> &'static str {
+        match self {
+            GameType::Quiz => "quiz_game",
+            GameType::Duel | GameType::Roulette => "spin_game",
+            GameType::Imposter => "imposter_game",
+        }
+    }
+
+    /// Path on `tero.session` used to initiate a session of this type
+    pub fn session_path(&self) -> String {
+        format!("session/initiate/{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, Default, PartialEq)]
+pub struct UpdateGameRequest {
+    #[validate(custom(function = "crate::api::validation::validate_game_name"))]
+    pub name: Option<String>,
+    pub category: Option<GameCategory>,
+    #[validate(length(max = 500, message = "Description must be at most 500 characters"))]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct SearchGamesRequest {
+    #[validate(length(
+        min = 2,
+        max = 100,
+        message = "Search must be between 2 and 100 characters"
+    ))]
+    pub query: String,
+    pub game_type: Option<GameType>,
+    #[serde(default)]
+    pub page_num: u16,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RandomGameQuery {
+    pub category: Option<GameCategory>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Hash, Eq, PartialEq, Clone)]
 pub struct GameCacheKey {
     pub page_num: u16,
+    /// The effective size, so clients asking for different sizes never share an entry
+    pub page_size: u16,
     pub game_type: Option<GameType>,
-    pub category: Option<GameCategory>,
+    /// Normalized by `category_filter`, so the same set always maps to one entry
+    pub categories: Vec<GameCategory>,
+    pub sort: GameSort,
 }
 
 impl GameCacheKey {
     pub fn from_request(query: &GamePagedRequest) -> Self {
         Self {
             page_num: query.page_num.unwrap_or(0),
+            page_size: crate::db::effective_page_size(query.page_size),
             game_type: query.game_type,
-            category: query.category.clone(),
+            categories: query.category_filter(),
+            sort: query.sort.unwrap_or_default(),
         }
     }
 }
@@ -150,7 +392,14 @@ pub struct CreateStaticGameRequest {
     #[validate(custom(function = "crate::api::validation::validate_game_name"))]
     pub name: String,
     pub category: GameCategory,
+    #[serde(default)]
     pub rounds: Vec<String>,
+    /// Multiple choice quiz questions, plain `rounds` are stored as open questions
+    #[serde(default)]
+    pub questions: Vec<QuizQuestion>,
+    #[serde(default)]
+    #[validate(length(max = 500, message = "Description must be at most 500 characters"))]
+    pub description: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Validate)]
@@ -159,6 +408,87 @@ pub struct InteractiveGameEnvelope {
     pub name: String,
     pub category: GameCategory,
     pub payload: serde_json::Value,
+    /// Set by the session service so a retried persist is only counted once
+    pub play_token: Option<Uuid>,
+    /// Key the session was hosted under, must still be held for the same game type.
+    /// Optional so a missing key gets the same 409 as a stale one instead of a parse error.
+    #[serde(default)]
+    pub game_key: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct GameImport {
+    #[validate(custom(function = "crate::api::validation::validate_game_name"))]
+    pub name: String,
+    pub game_type: GameType,
+    pub category: GameCategory,
+    #[validate(length(min = 1, message = "A game needs at least one round"))]
+    pub rounds: Vec<String>,
+}
+
+/// Syncs a client side favorites list in one request
+#[derive(Debug, Serialize, Deserialize, Validate, Default)]
+pub struct BulkSaveRequest {
+    #[serde(default)]
+    #[validate(length(max = 200, message = "At most 200 games can be saved at once"))]
+    pub add: Vec<Uuid>,
+    #[serde(default)]
+    #[validate(length(max = 200, message = "At most 200 games can be unsaved at once"))]
+    pub remove: Vec<Uuid>,
+}
+
+/// Inline feedback for a typed-in key, invalid keys are not an error
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct GameKeyValidation {
+    pub valid: bool,
+    pub game_type: Option<GameType>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct SavedStatusRequest {
+    #[validate(length(max = 100, message = "At most 100 games can be checked at once"))]
+    pub game_ids: Vec<Uuid>,
+}
+
+/// Saves that actually changed, ids already in the wanted state are not counted
+#[derive(Debug, Serialize, Deserialize, PartialEq, Default)]
+pub struct BulkSaveResult {
+    pub added: u64,
+    pub removed: u64,
+}
+
+/// Outcome of a single entry in an import batch, `index` points into the request
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ImportResult {
+    pub index: usize,
+    pub game_id: Option<Uuid>,
+    pub error: Option<String>,
+}
+
+impl ImportResult {
+    pub fn imported(index: usize, game_id: Uuid) -> Self {
+        Self {
+            index,
+            game_id: Some(game_id),
+            error: None,
+        }
+    }
+
+    pub fn failed(index: usize, error: String) -> Self {
+        Self {
+            index,
+            game_id: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// What a game delete removed, all zero when the game did not exist
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct DeleteGameResult {
+    pub game_deleted: bool,
+    pub rounds_deleted: u64,
+    pub saves_removed: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -166,3 +496,139 @@ pub struct InitiateGameRequest {
     pub key: String,
     pub value: serde_json::Value,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::config::app_config::CONFIG;
+
+    use super::{GameCacheKey, GameCategory, GamePagedRequest, GameSort, GameType, PagedResponse};
+
+    fn paged_request(query: serde_json::Value) -> GamePagedRequest {
+        serde_json::from_value(query).unwrap()
+    }
+
+    #[test]
+    fn categories_are_merged_and_normalized() {
+        let request = paged_request(serde_json::json!({
+            "category": "Boys",
+            "categories": "Girls, Boys",
+        }));
+        assert_eq!(
+            request.category_filter(),
+            vec![GameCategory::Girls, GameCategory::Boys]
+        );
+
+        let single = paged_request(serde_json::json!({ "category": "Boys" }));
+        assert_eq!(single.category_filter(), vec![GameCategory::Boys]);
+
+        assert!(
+            serde_json::from_value::<GamePagedRequest>(serde_json::json!({
+                "categories": "Girls,Vors",
+            }))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn sorted_pages_get_distinct_cache_keys() {
+        let default = paged_request(serde_json::json!({}));
+        let most_played = paged_request(serde_json::json!({ "sort": "MostPlayed" }));
+        let newest = paged_request(serde_json::json!({ "sort": "Newest" }));
+
+        assert_eq!(
+            GameCacheKey::from_request(&default),
+            GameCacheKey::from_request(&most_played)
+        );
+        assert_ne!(
+            GameCacheKey::from_request(&default),
+            GameCacheKey::from_request(&newest)
+        );
+        assert_eq!(GameCacheKey::from_request(&newest).sort, GameSort::Newest);
+    }
+
+    #[test]
+    fn category_sets_get_distinct_cache_keys() {
+        let both = paged_request(serde_json::json!({ "categories": "Girls,Boys" }));
+        let reversed = paged_request(serde_json::json!({ "categories": "Boys,Girls" }));
+        let girls = paged_request(serde_json::json!({ "categories": "Girls" }));
+
+        assert_eq!(
+            GameCacheKey::from_request(&both),
+            GameCacheKey::from_request(&reversed)
+        );
+        assert_ne!(
+            GameCacheKey::from_request(&both),
+            GameCacheKey::from_request(&girls)
+        );
+    }
+
+    #[test]
+    fn page_sizes_get_distinct_cache_keys() {
+        let small = paged_request(serde_json::json!({ "page_size": 5 }));
+        let large = paged_request(serde_json::json!({ "page_size": 50 }));
+        let over_cap = paged_request(serde_json::json!({ "page_size": 5000 }));
+        let capped = paged_request(serde_json::json!({ "page_size": CONFIG.server.max_page_size }));
+
+        assert_ne!(
+            GameCacheKey::from_request(&small),
+            GameCacheKey::from_request(&large)
+        );
+        assert_eq!(
+            GameCacheKey::from_request(&over_cap),
+            GameCacheKey::from_request(&capped)
+        );
+    }
+
+    #[test]
+    fn unknown_db_category_falls_back() {
+        assert_eq!(GameCategory::from_db("vors"), GameCategory::Unknown);
+        assert_eq!(GameCategory::from_db("boys"), GameCategory::Boys);
+    }
+
+    #[test]
+    fn every_game_type_is_handled_by_helpers() {
+        for game_type in GameType::ALL {
+            let (hub, table, session) = match game_type {
+                GameType::Roulette | GameType::Duel => ("spin", "spin_game", true),
+                GameType::Quiz => ("quiz", "quiz_game", false),
+                GameType::Imposter => ("imposter", "imposter_game", false),
+            };
+
+            assert_eq!(
+                game_type.as_str(),
+                format!("{:?}", game_type).to_lowercase()
+            );
+            assert_eq!(game_type.hub_name(), hub);
+            assert_eq!(game_type.table_name(), table);
+            assert!(game_type.supports_standalone());
+            assert_eq!(game_type.supports_session(), session);
+            assert!(game_type.session_path().ends_with(game_type.as_str()));
+        }
+    }
+
+    #[test]
+    fn every_game_type_round_trips_through_serde() {
+        for game_type in GameType::ALL {
+            let json = serde_json::to_value(game_type).unwrap();
+            assert_eq!(json, format!("{:?}", game_type));
+            assert_eq!(serde_json::from_value::<GameType>(json).unwrap(), game_type);
+        }
+    }
+
+    #[test]
+    fn total_is_only_serialized_when_counted() {
+        let plain = serde_json::to_value(PagedResponse::new(0, vec![1], false)).unwrap();
+        assert!(plain.get("total").is_none());
+
+        let counted =
+            serde_json::to_value(PagedResponse::with_total(2, vec![1], true, 11)).unwrap();
+        assert_eq!(counted["total"], 11);
+        assert_eq!(counted["has_prev"], true);
+    }
+
+    #[test]
+    fn unknown_category_is_not_accepted_from_clients() {
+        let result = serde_json::from_str::<GameCategory>("\"Unknown\"");
+        assert!(result.is_err());
+    }
+}
@@ -3,12 +3,21 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use validator::Validate;
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "tip_status", rename_all = "lowercase")]
+pub enum TipStatus {
+    New,
+    Reviewed,
+    Dismissed,
+}
+
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
 pub struct GameTip {
     pub id: Uuid,
     pub header: String,
     pub mobile_phone: String,
     pub description: String,
+    pub status: TipStatus,
     pub created_at: DateTime<Utc>,
 }
 
@@ -20,9 +29,19 @@ pub struct CreateGameTipRequest {
     pub mobile_phone: String,
     #[validate(length(min = 8, max = 300))]
     pub description: String,
+    /// Honeypot, hidden in the client so only bots fill it in
+    #[serde(default, skip_serializing)]
+    #[validate(length(max = 0))]
+    pub website: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GameTipPageQuery {
     pub page_num: u16,
+    pub status: Option<TipStatus>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PatchGameTipRequest {
+    pub status: TipStatus,
 }
@@ -66,3 +66,31 @@ impl ImposterSession {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::models::game_base::JsonConverter;
+
+    use super::{ImposterGame, ImposterSession};
+
+    #[test]
+    fn created_session_round_trips_into_persisted_game() {
+        let host_id = Uuid::new_v4();
+        let game_id = Uuid::new_v4();
+
+        let mut session = ImposterSession::new(host_id, game_id);
+        let created = session.to_json().unwrap();
+        assert_eq!(created["game_id"], game_id.to_string());
+
+        // The session service hands the payload back with rounds filled in
+        session.rounds = vec!["Pizza".into(), "Beach".into()];
+        let persisted: ImposterSession =
+            serde_json::from_value(session.to_json().unwrap()).unwrap();
+        let game = ImposterGame::from(persisted);
+
+        assert_eq!(game.id, game_id);
+        assert_eq!(game.rounds, vec!["Pizza".to_string(), "Beach".to_string()]);
+    }
+}
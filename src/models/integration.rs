@@ -1,25 +1,36 @@
 use core::fmt;
 use std::collections::HashMap;
 
-use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use validator::Validate;
 
-use crate::config::app_config::CONFIG;
-
-pub static INTEGRATION_NAMES: Lazy<HashMap<String, IntegrationName>> = Lazy::new(|| {
-    CONFIG
-        .integrations
-        .iter()
-        .map(|i| (i.subject.clone(), i.name.clone()))
-        .collect()
-});
-
-#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, sqlx::FromRow)]
 pub struct IntegrationConfig {
     pub name: IntegrationName,
     pub subject: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct RegisterIntegrationRequest {
+    pub name: IntegrationName,
+    /// Client id of the machine client, with or without the `@clients` suffix
+    #[validate(length(min = 1, max = 100))]
+    pub subject: String,
+}
+
+impl RegisterIntegrationRequest {
+    pub fn into_config(self) -> IntegrationConfig {
+        let subject = self.subject.trim();
+        IntegrationConfig {
+            name: self.name,
+            subject: subject
+                .strip_suffix("@clients")
+                .unwrap_or(subject)
+                .to_string(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Hash, PartialEq, Eq, sqlx::Type)]
 #[sqlx(type_name = "integration_name", rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
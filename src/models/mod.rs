@@ -4,6 +4,7 @@ pub mod game_base;
 pub mod game_tip;
 pub mod imposter_game;
 pub mod integration;
+pub mod permission;
 pub mod quiz_game;
 pub mod spin_game;
 pub mod system_log;
@@ -0,0 +1,192 @@
+use std::collections::{HashMap, HashSet};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::app_config::CONFIG,
+    models::{auth::Claims, error::ServerError, user::Permission},
+};
+
+pub static PERMISSION_POLICY: Lazy<PermissionPolicy> =
+    Lazy::new(|| PermissionPolicy::new(&CONFIG.permissions));
+
+/// Permission gated routes, used as keys in the `[permissions]` config table
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyRoute {
+    PersistInteractiveGame,
+    FreeGameKey,
+    ReportPlayerCount,
+    PatchGame,
+    DeleteGame,
+    ImportGames,
+    /// Not a route of its own, gates hard deletes on user deletion
+    EraseUser,
+    /// Not a route of its own, gates `include_deleted` on admin listings
+    IncludeDeleted,
+    ListAllUsers,
+    /// Exporting another user's data, exporting your own needs no permission
+    ExportUserData,
+    GetUserActivityStats,
+    UpdateClientPopup,
+    GetSystemLogPage,
+    StreamSystemLogs,
+    GetLogCategoryCount,
+    CreateSystemLog,
+    GetGameTipsAdmin,
+    PatchGameTip,
+    ListIntegrations,
+    RegisterIntegration,
+}
+
+#[derive(Debug)]
+pub struct PermissionPolicy {
+    routes: HashMap<PolicyRoute, HashSet<Permission>>,
+}
+
+fn default_routes() -> HashMap<PolicyRoute, HashSet<Permission>> {
+    [
+        (PolicyRoute::PersistInteractiveGame, Permission::WriteGame),
+        (PolicyRoute::FreeGameKey, Permission::WriteGame),
+        (PolicyRoute::ReportPlayerCount, Permission::WriteGame),
+        (PolicyRoute::PatchGame, Permission::WriteAdmin),
+        (PolicyRoute::DeleteGame, Permission::WriteAdmin),
+        (PolicyRoute::ImportGames, Permission::WriteAdmin),
+        (PolicyRoute::EraseUser, Permission::WriteAdmin),
+        (PolicyRoute::IncludeDeleted, Permission::WriteAdmin),
+        (PolicyRoute::ListAllUsers, Permission::ReadAdmin),
+        (PolicyRoute::ExportUserData, Permission::ReadAdmin),
+        (PolicyRoute::GetUserActivityStats, Permission::ReadAdmin),
+        (PolicyRoute::UpdateClientPopup, Permission::WriteAdmin),
+        (PolicyRoute::GetSystemLogPage, Permission::ReadAdmin),
+        (PolicyRoute::StreamSystemLogs, Permission::ReadAdmin),
+        (PolicyRoute::GetLogCategoryCount, Permission::ReadAdmin),
+        (PolicyRoute::CreateSystemLog, Permission::WriteSystemLog),
+        (PolicyRoute::GetGameTipsAdmin, Permission::ReadAdmin),
+        (PolicyRoute::PatchGameTip, Permission::WriteAdmin),
+        (PolicyRoute::ListIntegrations, Permission::ReadAdmin),
+        (PolicyRoute::RegisterIntegration, Permission::WriteAdmin),
+    ]
+    .into_iter()
+    .map(|(route, permission)| (route, HashSet::from([permission])))
+    .collect()
+}
+
+impl PermissionPolicy {
+    /// Starts from the built-in defaults and replaces the requirements of every overridden route
+    pub fn new(overrides: &HashMap<PolicyRoute, HashSet<Permission>>) -> Self {
+        let mut routes = default_routes();
+        routes.extend(
+            overrides
+                .iter()
+                .map(|(route, permissions)| (*route, permissions.clone())),
+        );
+
+        Self { routes }
+    }
+
+    pub fn required(&self, route: PolicyRoute) -> HashSet<Permission> {
+        self.routes.get(&route).cloned().unwrap_or_default()
+    }
+
+    pub fn allows(&self, route: PolicyRoute, claims: &Claims) -> bool {
+        claims.missing_permission(self.required(route)).is_none()
+    }
+
+    pub fn check(&self, route: PolicyRoute, claims: &Claims) -> Result<(), ServerError> {
+        let check = claims.permission_check(self.required(route));
+        match check.is_allowed() {
+            true => Ok(()),
+            false => Err(ServerError::Permission(check)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use serde_json::json;
+
+    use crate::models::{auth::Claims, error::ServerError, user::Permission};
+
+    use super::{PermissionPolicy, PolicyRoute};
+
+    fn claims_with(permissions: &[&str]) -> Claims {
+        serde_json::from_value(json!({
+            "aud": "https://api.tero.com",
+            "azp": "client",
+            "exp": 0,
+            "iat": 0,
+            "iss": "issuer",
+            "scope": "",
+            "sub": "auth0|user",
+            "permissions": permissions,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn defaults_match_previous_requirements() {
+        let policy = PermissionPolicy::new(&HashMap::new());
+        let reader = claims_with(&["read:admin"]);
+
+        assert!(policy.check(PolicyRoute::GetGameTipsAdmin, &reader).is_ok());
+        assert!(
+            policy
+                .check(PolicyRoute::UpdateClientPopup, &reader)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn denied_check_reports_granted_permissions() {
+        let policy = PermissionPolicy::new(&HashMap::new());
+        let claims = claims_with(&["read:admin", "write:game"]);
+
+        let Err(ServerError::Permission(check)) =
+            policy.check(PolicyRoute::UpdateClientPopup, &claims)
+        else {
+            panic!("Expected a permission error");
+        };
+
+        assert_eq!(check.missing, HashSet::from([Permission::WriteAdmin]));
+        assert_eq!(
+            check.granted,
+            HashSet::from([Permission::ReadAdmin, Permission::WriteGame])
+        );
+        assert_eq!(
+            claims.missing_permission([Permission::WriteAdmin]),
+            Some(check.missing)
+        );
+    }
+
+    #[test]
+    fn include_deleted_requires_write_admin() {
+        let policy = PermissionPolicy::new(&HashMap::new());
+
+        assert!(!policy.allows(PolicyRoute::IncludeDeleted, &claims_with(&["read:admin"])));
+        assert!(policy.allows(PolicyRoute::IncludeDeleted, &claims_with(&["write:admin"])));
+    }
+
+    #[test]
+    fn overridden_route_is_honored() {
+        let overrides = HashMap::from([(
+            PolicyRoute::GetGameTipsAdmin,
+            HashSet::from([Permission::WriteAdmin]),
+        )]);
+        let policy = PermissionPolicy::new(&overrides);
+
+        let reader = claims_with(&["read:admin"]);
+        let writer = claims_with(&["write:admin"]);
+
+        assert!(
+            policy
+                .check(PolicyRoute::GetGameTipsAdmin, &reader)
+                .is_err()
+        );
+        assert!(policy.check(PolicyRoute::GetGameTipsAdmin, &writer).is_ok());
+        assert!(policy.check(PolicyRoute::ListAllUsers, &reader).is_ok());
+    }
+}
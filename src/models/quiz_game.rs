@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
 use uuid::Uuid;
 
 use crate::models::game_base::JsonConverter;
@@ -9,10 +10,47 @@ impl JsonConverter for QuizSession {
     }
 }
 
+/// A quiz round. Questions stored before multiple choice existed are open
+/// questions, with no options and `correct_index` 0.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct QuizQuestion {
+    pub prompt: String,
+    #[serde(default)]
+    pub options: Vec<String>,
+    #[serde(default)]
+    pub correct_index: usize,
+}
+
+impl QuizQuestion {
+    pub fn open(prompt: String) -> Self {
+        Self {
+            prompt,
+            options: vec![],
+            correct_index: 0,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        match self.options.is_empty() {
+            true => self.correct_index == 0,
+            false => self.correct_index < self.options.len(),
+        }
+    }
+}
+
+pub fn open_questions(rounds: &[String]) -> Vec<QuizQuestion> {
+    rounds.iter().cloned().map(QuizQuestion::open).collect()
+}
+
+/// `rounds` holds the prompts only, it is what the session service and the
+/// round pool still work with
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
 pub struct QuizGame {
     pub id: Uuid,
     pub rounds: Vec<String>,
+    pub questions: Json<Vec<QuizQuestion>>,
+    #[sqlx(default)]
+    pub description: Option<String>,
 }
 
 impl From<QuizSession> for QuizGame {
@@ -20,6 +58,8 @@ impl From<QuizSession> for QuizGame {
         Self {
             id: value.game_id,
             rounds: value.rounds,
+            questions: Json(value.questions),
+            description: value.description,
         }
     }
 }
@@ -29,6 +69,10 @@ pub struct QuizSession {
     pub game_id: Uuid,
     pub current_iteration: i32,
     pub rounds: Vec<String>,
+    #[serde(default)]
+    pub questions: Vec<QuizQuestion>,
+    #[serde(default)]
+    pub description: Option<String>,
 }
 
 impl QuizSession {
@@ -37,6 +81,8 @@ impl QuizSession {
             game_id,
             current_iteration: 0,
             rounds: vec![],
+            questions: vec![],
+            description: None,
         }
     }
 
@@ -45,6 +91,8 @@ impl QuizSession {
             game_id: game.id,
             current_iteration: 0,
             rounds: game.rounds,
+            questions: game.questions.0,
+            description: game.description,
         }
     }
 
@@ -52,7 +100,9 @@ impl QuizSession {
         Self {
             game_id,
             current_iteration: 0,
+            questions: open_questions(&rounds),
             rounds,
+            description: None,
         }
     }
 }
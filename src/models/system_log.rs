@@ -1,9 +1,14 @@
 use core::fmt;
+use std::str::FromStr;
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+use axum::http::StatusCode;
+
+use crate::models::{error::ServerError, game_base::PagedResponse};
+
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
 pub struct SystemLog {
     pub id: i64,
     pub subject_id: String,
@@ -16,7 +21,7 @@ pub struct SystemLog {
     pub created_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize, Deserialize, sqlx::Type)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, sqlx::Type)]
 #[sqlx(type_name = "log_ceverity", rename_all = "lowercase")]
 pub enum LogCeverity {
     Critical,
@@ -34,7 +39,7 @@ impl fmt::Display for LogCeverity {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, sqlx::Type)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, sqlx::Type)]
 #[sqlx(type_name = "log_action", rename_all = "lowercase")]
 pub enum LogAction {
     Create,
@@ -58,7 +63,7 @@ impl fmt::Display for LogAction {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, sqlx::Type)]
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::Type)]
 #[sqlx(type_name = "subject_type", rename_all = "lowercase")]
 pub enum SubjectType {
     #[sqlx(rename = "registered_user")]
@@ -83,11 +88,101 @@ impl fmt::Display for SubjectType {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SyslogPageQuery {
     pub page_num: Option<u16>,
+    /// Clamped to `server.max_page_size`, defaults to `server.page_size`
+    pub page_size: Option<u16>,
+    /// Cursor from a previous page, takes precedence over `page_num` when set
+    pub before: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
     pub subject_type: Option<SubjectType>,
     pub action: Option<LogAction>,
     pub ceverity: Option<LogCeverity>,
 }
 
+impl SyslogPageQuery {
+    pub fn validate_range(&self) -> Result<(), ServerError> {
+        match (self.from, self.to) {
+            (Some(from), Some(to)) if from > to => Err(ServerError::Api(
+                StatusCode::BAD_REQUEST,
+                "`from` must not be after `to`".into(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Position in the log ordered by `(created_at, id)` descending,
+/// encoded as `{created_at micros}:{id}`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: i64,
+}
+
+impl LogCursor {
+    pub fn from_log(log: &SystemLog) -> Self {
+        Self {
+            created_at: log.created_at,
+            id: log.id,
+        }
+    }
+}
+
+impl fmt::Display for LogCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.created_at.timestamp_micros(), self.id)
+    }
+}
+
+impl FromStr for LogCursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (micros, id) = s.split_once(':').ok_or("Cursor is missing separator")?;
+        let micros = micros
+            .parse::<i64>()
+            .map_err(|_| "Cursor timestamp is not a number")?;
+        let id = id.parse::<i64>().map_err(|_| "Cursor id is not a number")?;
+        let created_at =
+            DateTime::from_timestamp_micros(micros).ok_or("Cursor timestamp is out of range")?;
+
+        Ok(Self { created_at, id })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyslogPage {
+    #[serde(flatten)]
+    pub page: PagedResponse<SystemLog>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogStreamQuery {
+    pub ceverity: Option<LogCeverity>,
+    pub action: Option<LogAction>,
+}
+
+impl LogStreamQuery {
+    pub fn matches(&self, log: &SystemLog) -> bool {
+        self.ceverity.as_ref().is_none_or(|c| *c == log.ceverity)
+            && self.action.as_ref().is_none_or(|a| *a == log.action)
+    }
+}
+
+/// A log entry that has not been written yet
+#[derive(Debug, Clone)]
+pub struct NewSystemLog {
+    pub subject_id: String,
+    pub subject_type: SubjectType,
+    pub action: LogAction,
+    pub ceverity: LogCeverity,
+    pub function: String,
+    pub description: String,
+    pub metadata: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateSyslogRequest {
     pub action: Option<LogAction>,
@@ -105,9 +200,145 @@ pub struct CreateClientLogRequest {
     pub metadata: Option<serde_json::Value>,
 }
 
+/// Metadata shapes shared across call sites, so logs can be aggregated on
+/// keys like `metadata->>'error'`. `Raw` is the escape hatch for anything else.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogMetadata {
+    Error(String),
+    /// A spawned task that failed, `panic` tells a panic from a cancellation
+    TaskFailed {
+        error: String,
+        panic: bool,
+    },
+    Entity(String),
+    /// Rows a job touched, `detail` holds what produced the count, e.g. a retention
+    Count {
+        count: i64,
+        detail: Option<serde_json::Value>,
+    },
+    Raw(serde_json::Value),
+}
+
+impl LogMetadata {
+    pub fn error(error: impl fmt::Display) -> Self {
+        LogMetadata::Error(error.to_string())
+    }
+
+    pub fn task_failed(error: &tokio::task::JoinError) -> Self {
+        LogMetadata::TaskFailed {
+            error: error.to_string(),
+            panic: error.is_panic(),
+        }
+    }
+
+    pub fn count_with(count: impl Into<i64>, detail: serde_json::Value) -> Self {
+        LogMetadata::Count {
+            count: count.into(),
+            detail: Some(detail),
+        }
+    }
+
+    pub fn entity(entity_id: impl fmt::Display) -> Self {
+        LogMetadata::Entity(entity_id.to_string())
+    }
+}
+
+impl From<serde_json::Value> for LogMetadata {
+    fn from(value: serde_json::Value) -> Self {
+        LogMetadata::Raw(value)
+    }
+}
+
+impl From<LogMetadata> for serde_json::Value {
+    fn from(metadata: LogMetadata) -> Self {
+        match metadata {
+            LogMetadata::Error(error) => serde_json::json!({ "error": error }),
+            LogMetadata::TaskFailed { error, panic } => {
+                serde_json::json!({ "error": error, "panic": panic })
+            }
+            LogMetadata::Entity(entity_id) => serde_json::json!({ "entity_id": entity_id }),
+            LogMetadata::Count { count, detail } => match detail {
+                Some(detail) => serde_json::json!({ "count": count, "detail": detail }),
+                None => serde_json::json!({ "count": count }),
+            },
+            LogMetadata::Raw(value) => value,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogGroupBy {
+    #[default]
+    Severity,
+    Action,
+    Subject,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct LogCountQuery {
+    #[serde(default)]
+    pub group_by: LogGroupBy,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LogCategoryCount {
     pub info: i64,
     pub warning: i64,
     pub critical: i64,
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use serde_json::json;
+
+    use super::{LogCursor, LogMetadata};
+
+    #[test]
+    fn typed_metadata_has_stable_keys() {
+        assert_eq!(
+            serde_json::Value::from(LogMetadata::error("boom")),
+            json!({"error": "boom"})
+        );
+        assert_eq!(
+            serde_json::Value::from(LogMetadata::entity(42)),
+            json!({"entity_id": "42"})
+        );
+        assert_eq!(
+            serde_json::Value::from(LogMetadata::Count {
+                count: 3,
+                detail: None
+            }),
+            json!({"count": 3})
+        );
+        assert_eq!(
+            serde_json::Value::from(LogMetadata::count_with(3, json!({"retention_days": 30}))),
+            json!({"count": 3, "detail": {"retention_days": 30}})
+        );
+        assert_eq!(
+            serde_json::Value::from(LogMetadata::from(json!({"custom": true}))),
+            json!({"custom": true})
+        );
+    }
+
+    #[test]
+    fn cursor_round_trips_through_string() {
+        let cursor = LogCursor {
+            created_at: Utc.timestamp_micros(1_700_000_000_123_456).unwrap(),
+            id: 42,
+        };
+
+        let encoded = cursor.to_string();
+        assert_eq!(encoded, "1700000000123456:42");
+        assert_eq!(encoded.parse::<LogCursor>().unwrap(), cursor);
+    }
+
+    #[test]
+    fn malformed_cursor_is_rejected() {
+        assert!("1700000000123456".parse::<LogCursor>().is_err());
+        assert!("abc:42".parse::<LogCursor>().is_err());
+        assert!("1700000000123456:abc".parse::<LogCursor>().is_err());
+    }
+}
@@ -1,9 +1,18 @@
-use chrono::{DateTime, NaiveDate, Utc};
+use axum::http::StatusCode;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use validator::Validate;
 
-use crate::models::{game_base::Gender, integration::IntegrationName};
+use crate::models::{
+    error::ServerError,
+    game_base::{GameBase, Gender},
+    game_tip::GameTip,
+    integration::IntegrationName,
+};
+
+/// Longest range activity stats can be computed over
+const MAX_STATS_RANGE_DAYS: i64 = 365;
 
 #[derive(Deserialize, Debug, Validate)]
 pub struct ResetPasswordRequest {
@@ -14,11 +23,46 @@ pub struct ResetPasswordRequest {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ListUsersQuery {
     pub page_num: u16,
+    /// Clamped to `server.max_page_size`, defaults to `server.page_size`
+    pub page_size: Option<u16>,
+    /// Only honored for admins with write access
+    pub include_deleted: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct DeleteUserQuery {
     pub user_id: Uuid,
+    /// Removes the row instead of tombstoning it, only for erasure requests
+    pub erase: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportUserQuery {
+    /// Only honored for admins with read access
+    pub user_id: Option<Uuid>,
+}
+
+/// Everything held about a base user
+#[derive(Debug, Serialize)]
+pub struct UserDataExport {
+    pub exported_at: DateTime<Utc>,
+    pub user: BaseUser,
+    pub saved_game_ids: Vec<Uuid>,
+    pub created_games: Vec<GameBase>,
+    /// Tips sent in while signed in, anonymous tips can not be traced to a user
+    pub game_tips: Vec<GameTip>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UsernameQuery {
+    pub username: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UsernameAvailability {
+    pub available: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -62,6 +106,40 @@ pub struct Auth0User {
     pub family_name: Option<String>,
 }
 
+impl Auth0User {
+    /// Profile fields we expect from Auth0 that were not sent
+    pub fn missing_fields(&self) -> Vec<&'static str> {
+        [
+            ("email", self.email.is_none()),
+            ("given_name", self.given_name.is_none()),
+            ("family_name", self.family_name.is_none()),
+        ]
+        .into_iter()
+        .filter_map(|(field, missing)| missing.then_some(field))
+        .collect()
+    }
+
+    /// Username from the Auth0 profile, or a neutral handle from the user id
+    pub fn derive_username(&self, user_id: Uuid) -> String {
+        let from_email = self
+            .email
+            .as_deref()
+            .and_then(|email| email.split('@').next());
+
+        [
+            self.username.as_deref(),
+            self.nickname.as_deref(),
+            from_email,
+        ]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .find(|name| !name.is_empty())
+        .map(String::from)
+        .unwrap_or_else(|| format!("user_{}", &user_id.simple().to_string()[..8]))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
 pub struct BaseUser {
     pub id: Uuid,
@@ -93,10 +171,41 @@ pub struct PatchUserRequest {
     pub family_name: Option<String>,
     #[validate(custom(function = "crate::api::validation::validate_person_name"))]
     pub given_name: Option<String>,
+    #[validate(custom(function = "crate::api::validation::validate_birth_date"))]
     pub birth_date: Option<NaiveDate>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Deserialize, Default)]
+pub struct ActivityStatsQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl ActivityStatsQuery {
+    /// `None` means the default trailing windows, a range needs both bounds
+    pub fn range(&self) -> Result<Option<(DateTime<Utc>, DateTime<Utc>)>, ServerError> {
+        match (self.from, self.to) {
+            (None, None) => Ok(None),
+            (Some(from), Some(to)) if from >= to => Err(ServerError::Api(
+                StatusCode::BAD_REQUEST,
+                "`from` must be before `to`".into(),
+            )),
+            (Some(from), Some(to)) if to - from > Duration::days(MAX_STATS_RANGE_DAYS) => {
+                Err(ServerError::Api(
+                    StatusCode::BAD_REQUEST,
+                    format!("Range can span at most {} days", MAX_STATS_RANGE_DAYS),
+                ))
+            }
+            (Some(from), Some(to)) => Ok(Some((from, to))),
+            _ => Err(ServerError::Api(
+                StatusCode::BAD_REQUEST,
+                "Both `from` and `to` are required for a range".into(),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ActivityStats {
     pub total_game_count: i64,
     pub total_user_count: i64,
@@ -104,16 +213,89 @@ pub struct ActivityStats {
     pub average: AverageUserStats,
 }
 
-#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
 pub struct RecentUserStats {
     pub this_month_users: i64,
     pub this_week_users: i64,
     pub todays_users: i64,
 }
 
-#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
 pub struct AverageUserStats {
     pub avg_month_users: f64,
     pub avg_week_users: f64,
     pub avg_daily_users: f64,
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    use super::{ActivityStatsQuery, Auth0User};
+
+    fn auth0_user(email: Option<&str>) -> Auth0User {
+        Auth0User {
+            auth0_id: "auth0|only-id".into(),
+            email: email.map(String::from),
+            email_verified: None,
+            username: None,
+            phone_number: None,
+            phone_verified: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            name: None,
+            nickname: None,
+            given_name: None,
+            family_name: None,
+        }
+    }
+
+    #[test]
+    fn user_with_only_auth0_id_gets_neutral_handle() {
+        let user = auth0_user(None);
+        let id = Uuid::new_v4();
+
+        assert_eq!(
+            user.derive_username(id),
+            format!("user_{}", &id.simple().to_string()[..8])
+        );
+        assert_eq!(
+            user.missing_fields(),
+            vec!["email", "given_name", "family_name"]
+        );
+    }
+
+    #[test]
+    fn username_is_derived_from_real_data() {
+        let user = auth0_user(Some("ola.nordmann@example.com"));
+        assert_eq!(user.derive_username(Uuid::new_v4()), "ola.nordmann");
+        assert_eq!(user.missing_fields(), vec!["given_name", "family_name"]);
+    }
+
+    #[test]
+    fn activity_range_is_validated() {
+        let now = Utc::now();
+        let query = |from, to| ActivityStatsQuery { from, to };
+
+        assert!(query(None, None).range().unwrap().is_none());
+        assert!(
+            query(Some(now - Duration::days(30)), Some(now))
+                .range()
+                .unwrap()
+                .is_some()
+        );
+
+        assert!(query(Some(now), None).range().is_err());
+        assert!(
+            query(Some(now), Some(now - Duration::days(1)))
+                .range()
+                .is_err()
+        );
+        assert!(
+            query(Some(now - Duration::days(400)), Some(now))
+                .range()
+                .is_err()
+        );
+    }
+}
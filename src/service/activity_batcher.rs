@@ -0,0 +1,131 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use sqlx::{Pool, Postgres};
+use tokio_util::sync::CancellationToken;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::db::user::update_pseudo_users_activity;
+
+/// Collects pseudo user activity and writes it with one statement per flush.
+/// Activity stats tolerate `last_active` lagging a few seconds behind.
+#[derive(Clone, Default)]
+pub struct ActivityBatcher {
+    pending: Arc<DashMap<Uuid, DateTime<Utc>>>,
+}
+
+impl ActivityBatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks the guest as active now, repeated calls before a flush collapse into one row
+    pub fn record(&self, pseudo_id: Uuid) {
+        self.pending.insert(pseudo_id, Utc::now());
+    }
+
+    /// Removes and returns everything recorded since the last flush
+    fn take(&self) -> Vec<(Uuid, DateTime<Utc>)> {
+        let ids: Vec<Uuid> = self.pending.iter().map(|entry| *entry.key()).collect();
+        ids.into_iter()
+            .filter_map(|id| self.pending.remove(&id))
+            .collect()
+    }
+
+    /// Writes pending activity, returns the number of pseudo users updated
+    pub async fn flush(&self, pool: &Pool<Postgres>) -> Result<u64, sqlx::Error> {
+        let batch = self.take();
+        if batch.is_empty() {
+            return Ok(0);
+        }
+
+        update_pseudo_users_activity(pool, &batch).await
+    }
+
+    /// Flushes every `interval` until `shutdown` is cancelled
+    pub fn spawn(&self, pool: Pool<Postgres>, interval: Duration, shutdown: CancellationToken) {
+        let batcher = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(interval) => {}
+                }
+
+                if let Err(e) = batcher.flush(&pool).await {
+                    error!("Failed to write pseudo user activity: {}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::tests::db::dev_pool;
+
+    use super::ActivityBatcher;
+
+    #[test]
+    fn repeated_activity_collapses_into_one_row() {
+        let batcher = ActivityBatcher::new();
+        let pseudo_id = Uuid::new_v4();
+
+        for _ in 0..50 {
+            batcher.record(pseudo_id);
+        }
+
+        let batch = batcher.take();
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch[0].0, pseudo_id);
+        assert!(batcher.take().is_empty());
+    }
+
+    #[tokio::test]
+    async fn flush_updates_every_pending_user_at_once() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let ids = [Uuid::new_v4(), Uuid::new_v4()];
+        for id in ids {
+            sqlx::query(
+                r#"INSERT INTO "pseudo_user" (id, last_active) VALUES ($1, NOW() - INTERVAL '1 day')"#,
+            )
+            .bind(id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+
+        let batcher = ActivityBatcher::new();
+        for _ in 0..10 {
+            for id in ids {
+                batcher.record(id);
+            }
+        }
+
+        assert_eq!(batcher.flush(&pool).await.unwrap(), 2);
+        assert_eq!(batcher.flush(&pool).await.unwrap(), 0);
+
+        let stale: i64 = sqlx::query_scalar(
+            r#"SELECT COUNT(*) FROM "pseudo_user" WHERE id = ANY($1) AND last_active < NOW() - INTERVAL '1 hour'"#,
+        )
+        .bind(&ids[..])
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(stale, 0);
+
+        sqlx::query(r#"DELETE FROM "pseudo_user" WHERE id = ANY($1)"#)
+            .bind(&ids[..])
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
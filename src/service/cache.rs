@@ -1,47 +1,130 @@
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 
 use moka::future::Cache;
+use serde::Serialize;
 use tracing::{debug, warn};
 
 use crate::models::{
     error::ServerError,
-    game_base::{GameCacheKey, GameCategory, GameType},
+    game_base::{GameBase, GameCacheKey, GameCategory, GameType, PagedResponse},
 };
 
 /// INFO:
 ///     I accepted that the eviction on max cache size will make the cache inconsistent.
 ///     A game might not appear or appear twice, but will be conistent after the ttl.
-///      Approximate 20MB with ~10k entries
-pub static MAX_CACHE_ENTRIES: u64 = 10_000;
+///     Entries are weighed by their heap footprint, so this is an actual byte budget.
+pub static MAX_BYTE_SIZE: u64 = 20 * 1024 * 1024;
+
+/// Approximate number of heap bytes owned by a value, used to weigh cache entries.
+pub trait HeapSize {
+    fn heap_size(&self) -> usize;
+}
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl HeapSize for GameBase {
+    fn heap_size(&self) -> usize {
+        self.name.capacity() + self.description.as_ref().map_or(0, String::capacity)
+    }
+}
+
+impl<T: HeapSize> HeapSize for PagedResponse<T> {
+    fn heap_size(&self) -> usize {
+        self.items.capacity() * std::mem::size_of::<T>()
+            + self.items.iter().map(HeapSize::heap_size).sum::<usize>()
+    }
+}
+
+fn entry_weight<T: HeapSize>(value: &T) -> u32 {
+    let bytes = std::mem::size_of::<T>() + value.heap_size();
+    u32::try_from(bytes).unwrap_or(u32::MAX)
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheMetrics {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: u64,
+    pub max_bytes: usize,
+}
 
 #[derive(Debug, Clone)]
-pub struct GustCache<T: Clone + Send + Sync + 'static> {
+pub struct GustCache<T: Clone + Send + Sync + HeapSize + 'static> {
     cache: Arc<Cache<GameCacheKey, T>>,
+    max_bytes: usize,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
 }
 
-impl<T: Clone + Send + Sync + 'static> GustCache<T> {
+impl<T: Clone + Send + Sync + HeapSize + 'static> GustCache<T> {
+    /// Shim for caches that are fine with the default `MAX_BYTE_SIZE` budget.
     pub fn from_ttl(ttl_secs: u64) -> Self {
+        Self::with_limits(ttl_secs, MAX_BYTE_SIZE as usize)
+    }
+
+    /// TTL and size eviction run independently: an entry is dropped once it has
+    /// been idle for `ttl_secs`, or earlier if the cache's total weight goes over
+    /// `max_bytes`. A small `max_bytes` can therefore evict entries well before
+    /// their TTL, while a large one leaves expiry entirely to the TTL.
+    pub fn with_limits(ttl_secs: u64, max_bytes: usize) -> Self {
         let cache = Cache::builder()
-            .max_capacity(MAX_CACHE_ENTRIES)
+            .max_capacity(max_bytes as u64)
+            .weigher(|_key, value: &T| entry_weight(value))
             .time_to_idle(Duration::from_secs(ttl_secs))
             .support_invalidation_closures()
             .build();
 
         Self {
             cache: Arc::new(cache),
+            max_bytes,
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Hit/miss counts since startup. A caller that waited on another caller's
+    /// load counts as a hit, since only the loading caller reached the database.
+    pub fn metrics(&self) -> CacheMetrics {
+        CacheMetrics {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.cache.entry_count(),
+            max_bytes: self.max_bytes,
         }
     }
 
+    /// Returns the cached entry or runs `on_failure` to load it.
+    ///
+    /// Concurrent misses on the same key are coalesced: the first caller runs
+    /// `on_failure` while the rest await its result, so an expired popular page
+    /// only hits the database once.
     pub async fn get_or<F>(&self, key: GameCacheKey, on_failure: F) -> Result<T, ServerError>
     where
         F: Future<Output = Result<T, sqlx::Error>>,
     {
-        match self
+        // Expired entries are not returned by moka, so they also land on the loader
+        let mut loaded = false;
+        let result = self
             .cache
-            .try_get_with(key, async { on_failure.await.map_err(ServerError::from) })
-            .await
-        {
+            .try_get_with(key, async {
+                loaded = true;
+                on_failure.await.map_err(ServerError::from)
+            })
+            .await;
+
+        if loaded {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+
+        match result {
             Ok(entry) => Ok(entry),
             Err(e) => {
                 warn!("Cache failed to get entry: {}", e);
@@ -50,6 +133,19 @@ impl<T: Clone + Send + Sync + 'static> GustCache<T> {
         }
     }
 
+    /// Drops a single entry so the next `get_or` on the key runs its loader again.
+    #[cfg(test)]
+    pub async fn invalidate_key(&self, key: &GameCacheKey) {
+        debug!("Invalidating cache entry {:?}", key);
+        self.cache.invalidate(key).await;
+    }
+
+    /// Drops every entry, used when a mutation may touch any page.
+    pub fn clear(&self) {
+        debug!("Clearing all cache entries");
+        self.cache.invalidate_all();
+    }
+
     pub async fn invalidate(
         &self,
         game_type: GameType,
@@ -64,7 +160,7 @@ impl<T: Clone + Send + Sync + 'static> GustCache<T> {
         let category = category.clone();
         match self.cache.invalidate_entries_if(move |key, _| {
             key.game_type == Some(game_type)
-                && (key.category == Some(category.clone()) || key.category.is_none())
+                && (key.categories.is_empty() || key.categories.contains(&category))
         }) {
             Ok(_) => Ok(()),
             Err(e) => {
@@ -80,14 +176,17 @@ impl<T: Clone + Send + Sync + 'static> GustCache<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::models::game_base::GameSort;
+    use std::sync::atomic::AtomicUsize;
     use tokio::time::sleep;
 
     fn make_key(game_type: GameType, category: Option<GameCategory>, page: u16) -> GameCacheKey {
         GameCacheKey {
             game_type: Some(game_type),
-            category,
+            categories: category.into_iter().collect(),
+            sort: GameSort::default(),
             page_num: page,
+            page_size: 20,
         }
     }
 
@@ -125,6 +224,65 @@ mod tests {
         assert_eq!(call_count.load(Ordering::SeqCst), 1); // Fallback NOT called again
     }
 
+    #[tokio::test]
+    async fn test_get_or_concurrent_miss_runs_loader_once() {
+        let cache: GustCache<String> = GustCache::from_ttl(60);
+        let key = make_key(GameType::Quiz, Some(GameCategory::Mixed), 0);
+        let call_count = Arc::new(AtomicUsize::new(0));
+
+        let mut handles = Vec::new();
+        for _ in 0..50 {
+            let cache = cache.clone();
+            let key = key.clone();
+            let call_count = call_count.clone();
+
+            handles.push(tokio::spawn(async move {
+                cache
+                    .get_or(key, async move {
+                        call_count.fetch_add(1, Ordering::SeqCst);
+                        sleep(Duration::from_millis(50)).await;
+                        Ok::<_, sqlx::Error>("loaded".to_string())
+                    })
+                    .await
+                    .unwrap()
+            }));
+        }
+
+        let results = futures::future::join_all(handles).await;
+
+        for result in results {
+            assert_eq!(result.unwrap(), "loaded");
+        }
+        assert_eq!(call_count.load(Ordering::SeqCst), 1); // Only the first miss hit the loader
+    }
+
+    #[tokio::test]
+    async fn test_metrics_count_hits_and_misses() {
+        let cache: GustCache<String> = GustCache::from_ttl(1);
+        let key = make_key(GameType::Quiz, Some(GameCategory::Mixed), 0);
+
+        let _ = cache
+            .get_or(key.clone(), async { Ok("first".to_string()) })
+            .await;
+        let _ = cache
+            .get_or(key.clone(), async { Ok("second".to_string()) })
+            .await;
+
+        let metrics = cache.metrics();
+        assert_eq!(metrics.misses, 1);
+        assert_eq!(metrics.hits, 1);
+
+        // The key still exists after expiry, but serving it is a miss
+        sleep(Duration::from_secs(2)).await;
+        let _ = cache
+            .get_or(key, async { Ok("after_ttl".to_string()) })
+            .await;
+
+        let metrics = cache.metrics();
+        assert_eq!(metrics.misses, 2);
+        assert_eq!(metrics.hits, 1);
+    }
+
     #[tokio::test]
     async fn test_get_or_different_keys_are_separate() {
         let cache: GustCache<String> = GustCache::from_ttl(60);
@@ -157,6 +315,62 @@ mod tests {
         assert_eq!(duel_result, "duel_value");
     }
 
+    #[tokio::test]
+    async fn test_invalidate_key_reruns_loader() {
+        let cache: GustCache<String> = GustCache::from_ttl(60);
+        let key = make_key(GameType::Quiz, Some(GameCategory::Mixed), 0);
+        let other_key = make_key(GameType::Quiz, Some(GameCategory::Mixed), 1);
+
+        let _ = cache
+            .get_or(key.clone(), async { Ok("initial".to_string()) })
+            .await;
+        let _ = cache
+            .get_or(other_key.clone(), async { Ok("other".to_string()) })
+            .await;
+
+        cache.invalidate_key(&key).await;
+
+        let result = cache
+            .get_or(key, async { Ok("reloaded".to_string()) })
+            .await
+            .unwrap();
+        let other_result = cache
+            .get_or(other_key, async { Ok("new_other".to_string()) })
+            .await
+            .unwrap();
+
+        assert_eq!(result, "reloaded"); // Loader ran again
+        assert_eq!(other_result, "other"); // Untouched key still cached
+    }
+
+    #[tokio::test]
+    async fn test_clear_removes_all_entries() {
+        let cache: GustCache<String> = GustCache::from_ttl(60);
+        let key_quiz = make_key(GameType::Quiz, Some(GameCategory::Mixed), 0);
+        let key_duel = make_key(GameType::Duel, None, 0);
+
+        let _ = cache
+            .get_or(key_quiz.clone(), async { Ok("quiz".to_string()) })
+            .await;
+        let _ = cache
+            .get_or(key_duel.clone(), async { Ok("duel".to_string()) })
+            .await;
+
+        cache.clear();
+
+        let quiz_result = cache
+            .get_or(key_quiz, async { Ok("new_quiz".to_string()) })
+            .await
+            .unwrap();
+        let duel_result = cache
+            .get_or(key_duel, async { Ok("new_duel".to_string()) })
+            .await
+            .unwrap();
+
+        assert_eq!(quiz_result, "new_quiz");
+        assert_eq!(duel_result, "new_duel");
+    }
+
     #[tokio::test]
     async fn test_invalidate_removes_matching_category() {
         let cache: GustCache<String> = GustCache::from_ttl(60);
@@ -318,6 +532,65 @@ mod tests {
         assert_eq!(result, "after_ttl");
     }
 
+    #[tokio::test]
+    async fn test_eviction_keeps_weighted_size_near_limit() {
+        let cache: GustCache<String> = GustCache::from_ttl(60);
+        let entry_size = 1024 * 1024;
+
+        // Twice the byte budget worth of 1MB entries
+        for page in 0..40 {
+            let key = make_key(GameType::Quiz, None, page);
+            let _ = cache
+                .get_or(key, async move { Ok("x".repeat(entry_size)) })
+                .await;
+        }
+
+        cache.cache.run_pending_tasks().await;
+
+        let weighted_size = cache.cache.weighted_size();
+        assert!(weighted_size <= MAX_BYTE_SIZE);
+        assert!(weighted_size >= MAX_BYTE_SIZE / 2);
+        assert!(cache.cache.entry_count() < 40);
+    }
+
+    #[tokio::test]
+    async fn test_with_limits_uses_instance_byte_budget() {
+        let max_bytes = 4 * 1024 * 1024;
+        let small: GustCache<String> = GustCache::with_limits(60, max_bytes);
+        let large: GustCache<String> = GustCache::from_ttl(60);
+        let entry_size = 1024 * 1024;
+
+        for page in 0..10 {
+            let key = make_key(GameType::Quiz, None, page);
+            let _ = small
+                .get_or(key.clone(), async move { Ok("x".repeat(entry_size)) })
+                .await;
+            let _ = large
+                .get_or(key, async move { Ok("x".repeat(entry_size)) })
+                .await;
+        }
+
+        small.cache.run_pending_tasks().await;
+        large.cache.run_pending_tasks().await;
+
+        assert!(small.cache.weighted_size() <= max_bytes as u64);
+        assert_eq!(large.cache.entry_count(), 10); // Well under the default budget
+    }
+
+    #[test]
+    fn test_heap_size_counts_paged_items() {
+        let game = GameBase::new(
+            uuid::Uuid::new_v4(),
+            "x".repeat(100),
+            GameType::Quiz,
+            GameCategory::Mixed,
+            0,
+        );
+        let page = PagedResponse::new(0, vec![game], false);
+
+        assert!(page.heap_size() >= 100 + std::mem::size_of::<GameBase>());
+    }
+
     #[tokio::test]
     async fn test_different_pages_are_separate_entries() {
         let cache: GustCache<String> = GustCache::from_ttl(60);
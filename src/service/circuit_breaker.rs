@@ -0,0 +1,165 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct Breaker {
+    state: BreakerState,
+    failures: u32,
+    opened_at: Instant,
+}
+
+/// Opens after `failure_threshold` consecutive failures and rejects calls until
+/// `cooldown` has passed, then lets a single probe through to decide whether to
+/// close again.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    inner: Arc<Mutex<Breaker>>,
+    failure_threshold: u32,
+    cooldown: Duration,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(5, Duration::from_secs(30))
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Breaker {
+                state: BreakerState::Closed,
+                failures: 0,
+                opened_at: Instant::now(),
+            })),
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Breaker> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn state(&self) -> BreakerState {
+        self.lock().state
+    }
+
+    /// True if a call may go through, moving an expired open breaker to half open
+    pub fn try_acquire(&self) -> bool {
+        self.try_acquire_at(Instant::now())
+    }
+
+    fn try_acquire_at(&self, now: Instant) -> bool {
+        let mut breaker = self.lock();
+        match breaker.state {
+            BreakerState::Closed => true,
+            BreakerState::Open
+                if now.saturating_duration_since(breaker.opened_at) >= self.cooldown =>
+            {
+                breaker.state = BreakerState::HalfOpen;
+                true
+            }
+            // Either still cooling down or a probe is already in flight
+            BreakerState::Open | BreakerState::HalfOpen => false,
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut breaker = self.lock();
+        breaker.state = BreakerState::Closed;
+        breaker.failures = 0;
+    }
+
+    pub fn record_failure(&self) {
+        self.record_failure_at(Instant::now());
+    }
+
+    fn record_failure_at(&self, now: Instant) {
+        let mut breaker = self.lock();
+        breaker.failures = breaker.failures.saturating_add(1);
+
+        let trips = match breaker.state {
+            BreakerState::Closed => breaker.failures >= self.failure_threshold,
+            BreakerState::HalfOpen | BreakerState::Open => true,
+        };
+
+        if trips {
+            if breaker.state != BreakerState::Open {
+                warn!(
+                    "Circuit breaker opened after {} failure(s)",
+                    breaker.failures
+                );
+            }
+            breaker.state = BreakerState::Open;
+            breaker.opened_at = now;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{BreakerState, CircuitBreaker};
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(10));
+        let start = Instant::now();
+
+        breaker.record_failure_at(start);
+        breaker.record_success();
+        breaker.record_failure_at(start);
+        assert_eq!(breaker.state(), BreakerState::Closed);
+
+        breaker.record_failure_at(start);
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(!breaker.try_acquire_at(start + Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn half_open_allows_a_single_probe() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(10));
+        let start = Instant::now();
+        breaker.record_failure_at(start);
+
+        let later = start + Duration::from_secs(10);
+        assert!(breaker.try_acquire_at(later));
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        assert!(!breaker.try_acquire_at(later));
+
+        breaker.record_success();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert!(breaker.try_acquire_at(later));
+    }
+
+    #[test]
+    fn failed_probe_reopens() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(10));
+        let start = Instant::now();
+        breaker.record_failure_at(start);
+
+        let later = start + Duration::from_secs(10);
+        assert!(breaker.try_acquire_at(later));
+        breaker.record_failure_at(later);
+
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(!breaker.try_acquire_at(later + Duration::from_secs(5)));
+    }
+}
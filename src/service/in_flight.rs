@@ -0,0 +1,110 @@
+use std::{hash::Hash, sync::Arc};
+
+use dashmap::DashSet;
+
+/// Tracks keys with work in progress so concurrent callers for the same key
+/// can skip instead of repeating it.
+#[derive(Debug, Clone)]
+pub struct InFlightSet<K: Hash + Eq + Clone> {
+    keys: Arc<DashSet<K>>,
+}
+
+impl<K: Hash + Eq + Clone> InFlightSet<K> {
+    pub fn new() -> Self {
+        Self {
+            keys: Arc::new(DashSet::new()),
+        }
+    }
+
+    /// Runs `task` only if no other task for `key` is in flight.
+    /// Returns `None` when the call was coalesced into a running one.
+    pub async fn run_exclusive<F: Future>(&self, key: K, task: F) -> Option<F::Output> {
+        let _guard = self.try_claim(key)?;
+        Some(task.await)
+    }
+
+    fn try_claim(&self, key: K) -> Option<InFlightGuard<K>> {
+        if !self.keys.insert(key.clone()) {
+            return None;
+        }
+
+        Some(InFlightGuard {
+            keys: self.keys.clone(),
+            key,
+        })
+    }
+}
+
+impl<K: Hash + Eq + Clone> Default for InFlightSet<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Releases the key when the task finishes or is cancelled
+struct InFlightGuard<K: Hash + Eq + Clone> {
+    keys: Arc<DashSet<K>>,
+    key: K,
+}
+
+impl<K: Hash + Eq + Clone> Drop for InFlightGuard<K> {
+    fn drop(&mut self) {
+        self.keys.remove(&self.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+
+    use tokio::sync::Barrier;
+    use uuid::Uuid;
+
+    use super::InFlightSet;
+
+    #[tokio::test]
+    async fn concurrent_calls_for_same_key_run_once() {
+        let in_flight = InFlightSet::new();
+        let upserts = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(Barrier::new(50));
+        let pseudo_id = Uuid::new_v4();
+
+        let handles: Vec<_> = (0..50)
+            .map(|_| {
+                let in_flight = in_flight.clone();
+                let upserts = upserts.clone();
+                let barrier = barrier.clone();
+
+                tokio::spawn(async move {
+                    barrier.wait().await;
+                    in_flight
+                        .run_exclusive(pseudo_id, async {
+                            upserts.fetch_add(1, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(100)).await;
+                        })
+                        .await
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        assert_eq!(upserts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn key_is_released_after_completion() {
+        let in_flight = InFlightSet::new();
+
+        assert_eq!(in_flight.run_exclusive(1, async { 1 }).await, Some(1));
+        assert_eq!(in_flight.run_exclusive(1, async { 2 }).await, Some(2));
+    }
+}
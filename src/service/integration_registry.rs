@@ -0,0 +1,169 @@
+use std::{collections::HashMap, sync::Arc};
+
+use reqwest::StatusCode;
+use sqlx::{Pool, Postgres};
+use tokio::sync::RwLock;
+use tracing::warn;
+
+use crate::{
+    config::app_config::CONFIG,
+    db::integration::{get_integrations, upsert_integration},
+    models::{
+        error::ServerError,
+        integration::{IntegrationConfig, IntegrationName},
+    },
+};
+
+/// Machine client subjects mapped to the integration they authenticate as.
+/// Seeded from config, then the `integration` table, so registrations made at
+/// runtime survive restarts and win over config for the same subject.
+#[derive(Debug, Clone)]
+pub struct IntegrationRegistry {
+    names: Arc<RwLock<HashMap<String, IntegrationName>>>,
+    pool: Option<Pool<Postgres>>,
+}
+
+impl IntegrationRegistry {
+    /// Unbacked registry holding only the given integrations, registrations are lost on restart
+    #[cfg(test)]
+    pub fn new(integrations: &[IntegrationConfig]) -> Self {
+        Self {
+            names: Arc::new(RwLock::new(merge(integrations, Vec::new()))),
+            pool: None,
+        }
+    }
+
+    /// Falls back to the configured integrations if the table can not be read,
+    /// so the stored ones never block startup
+    pub async fn load(pool: &Pool<Postgres>) -> Self {
+        let stored = match get_integrations(pool).await {
+            Ok(stored) => stored,
+            Err(e) => {
+                warn!(
+                    "Failed to load stored integrations, using config only: {}",
+                    e
+                );
+                Vec::new()
+            }
+        };
+
+        Self {
+            names: Arc::new(RwLock::new(merge(&CONFIG.integrations, stored))),
+            pool: Some(pool.clone()),
+        }
+    }
+
+    /// Resolves a token `sub` such as `{client_id}@clients`
+    pub async fn resolve(&self, subject: &str) -> Option<IntegrationName> {
+        IntegrationName::from_subject(subject, &*self.names.read().await)
+    }
+
+    pub async fn register(&self, integration: IntegrationConfig) -> Result<(), ServerError> {
+        if integration.subject.is_empty() {
+            return Err(ServerError::Api(
+                StatusCode::BAD_REQUEST,
+                "Integration subject must not be empty".into(),
+            ));
+        }
+
+        let mut lock = self.names.write().await;
+        if let Some(pool) = &self.pool {
+            upsert_integration(pool, &integration).await?;
+        }
+
+        lock.insert(integration.subject, integration.name);
+        Ok(())
+    }
+
+    pub async fn list(&self) -> Vec<IntegrationConfig> {
+        let mut integrations: Vec<IntegrationConfig> = self
+            .names
+            .read()
+            .await
+            .iter()
+            .map(|(subject, name)| IntegrationConfig {
+                name: name.clone(),
+                subject: subject.clone(),
+            })
+            .collect();
+        integrations.sort_by(|a, b| a.subject.cmp(&b.subject));
+        integrations
+    }
+}
+
+fn merge(
+    configured: &[IntegrationConfig],
+    stored: Vec<IntegrationConfig>,
+) -> HashMap<String, IntegrationName> {
+    configured
+        .iter()
+        .cloned()
+        .chain(stored)
+        .map(|i| (i.subject, i.name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::integration::{IntegrationConfig, IntegrationName};
+
+    use super::{IntegrationRegistry, merge};
+
+    fn integration(name: IntegrationName, subject: &str) -> IntegrationConfig {
+        IntegrationConfig {
+            name,
+            subject: subject.into(),
+        }
+    }
+
+    #[test]
+    fn stored_integrations_override_config() {
+        let merged = merge(
+            &[
+                integration(IntegrationName::Session, "abc"),
+                integration(IntegrationName::Auth0, "def"),
+            ],
+            vec![
+                integration(IntegrationName::Platform, "abc"),
+                integration(IntegrationName::Session, "ghi"),
+            ],
+        );
+
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged["abc"], IntegrationName::Platform);
+        assert_eq!(merged["def"], IntegrationName::Auth0);
+        assert_eq!(merged["ghi"], IntegrationName::Session);
+    }
+
+    #[tokio::test]
+    async fn registered_subject_resolves_immediately() {
+        let registry = IntegrationRegistry::new(&[integration(IntegrationName::Session, "abc")]);
+        assert_eq!(registry.resolve("new@clients").await, None);
+
+        registry
+            .register(integration(IntegrationName::Platform, "new"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            registry.resolve("new@clients").await,
+            Some(IntegrationName::Platform)
+        );
+        assert_eq!(
+            registry.resolve("abc@clients").await,
+            Some(IntegrationName::Session)
+        );
+        assert_eq!(registry.list().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn empty_subject_is_rejected() {
+        let registry = IntegrationRegistry::new(&[]);
+        assert!(
+            registry
+                .register(integration(IntegrationName::Platform, ""))
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use reqwest::Client;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::models::{auth::Jwks, error::ServerError};
+
+/// Minimum time between two fetches, so tokens with bogus kids can not make us hammer Auth0
+static REFRESH_DEBOUNCE: Duration = Duration::from_secs(60);
+
+/// Auth0 signing keys, re-fetched when a token carries a kid we have not seen
+#[derive(Debug, Clone)]
+pub struct JwksStore {
+    jwks: Arc<RwLock<Jwks>>,
+    last_fetch: Arc<Mutex<Option<Instant>>>,
+    client: Client,
+    url: String,
+}
+
+impl JwksStore {
+    pub fn new(jwks: Jwks, client: Client, url: String) -> Self {
+        Self {
+            jwks: Arc::new(RwLock::new(jwks)),
+            last_fetch: Arc::new(Mutex::new(Some(Instant::now()))),
+            client,
+            url,
+        }
+    }
+
+    pub async fn fetch(client: &Client, url: &str) -> Result<Jwks, ServerError> {
+        let response = client.get(url).send().await?.error_for_status()?;
+        let jwks = response.json::<Jwks>().await?;
+        jwks.ensure_not_empty()?;
+        Ok(jwks)
+    }
+
+    pub fn snapshot(&self) -> Jwks {
+        match self.jwks.read() {
+            Ok(jwks) => jwks.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Re-fetches the key set unless it was fetched within the debounce window.
+    /// Returns true if new keys were stored.
+    pub async fn refresh(&self) -> bool {
+        let mut last_fetch = self.last_fetch.lock().await;
+        if last_fetch.is_some_and(|at| at.elapsed() < REFRESH_DEBOUNCE) {
+            return false;
+        }
+
+        // Failed attempts count too, otherwise an Auth0 outage is hit on every request
+        *last_fetch = Some(Instant::now());
+
+        match Self::fetch(&self.client, &self.url).await {
+            Ok(jwks) => {
+                info!("Refreshed JWKS with {} key(s)", jwks.keys.len());
+                match self.jwks.write() {
+                    Ok(mut current) => *current = jwks,
+                    Err(poisoned) => *poisoned.into_inner() = jwks,
+                }
+                true
+            }
+            Err(e) => {
+                warn!("Failed to refresh JWKS: {}", e);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Client;
+
+    use crate::models::auth::{Jwk, Jwks};
+
+    use super::JwksStore;
+
+    fn jwk(kid: &str) -> Jwk {
+        Jwk {
+            kid: kid.into(),
+            n: String::new(),
+            e: "AQAB".into(),
+            crv: String::new(),
+            x: String::new(),
+            y: String::new(),
+            kty: "RSA".into(),
+            alg: "RS256".into(),
+            use_: "sig".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn refresh_is_debounced_after_initial_fetch() {
+        let jwks = Jwks {
+            keys: vec![jwk("current")],
+        };
+        let store = JwksStore::new(jwks, Client::new(), "http://127.0.0.1:9/jwks".into());
+
+        assert!(!store.refresh().await);
+        assert!(store.snapshot().has_kid("current"));
+        assert!(!store.snapshot().has_kid("rotated"));
+    }
+}
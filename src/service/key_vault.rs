@@ -1,16 +1,22 @@
 use std::{
+    collections::HashSet,
     sync::Arc,
     time::{Duration, SystemTime, SystemTimeError, UNIX_EPOCH},
 };
 
-use dashmap::DashMap;
+use dashmap::{DashMap, mapref::entry::Entry};
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
 use sqlx::{Pool, Postgres};
-use tracing::{debug, warn};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 
-use crate::{db::key_vault::get_word_sets, models::game_base::GameType};
+use crate::{
+    db::key_vault::{get_blocked_key_combos, get_word_sets},
+    models::game_base::GameType,
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum KeyVaultError {
@@ -40,25 +46,76 @@ pub struct KeyVault {
     active_keys: Arc<DashMap<(String, String), VaultValue>>,
     prefix_words: Arc<Vec<String>>,
     suffix_words: Arc<Vec<String>>,
+    /// Prefix and suffix pairs that read as something offensive, never handed out
+    blocked: HashSet<(String, String)>,
+    /// Stops the cleanup task on shutdown or when the vault is dropped
+    cancel: CancellationToken,
 }
 
 impl KeyVault {
     pub async fn load_words(pool: &Pool<Postgres>) -> Result<Self, KeyVaultError> {
         let (db_prefix, db_suffix) = get_word_sets(pool).await?;
+        let blocked = get_blocked_key_combos(pool).await?;
+
+        let vault = Self::from_words(db_prefix, db_suffix)?.with_blocked(blocked);
+        info!(
+            "Key vault loaded {} words with {} blocked combinations",
+            vault.word_count,
+            vault.blocked.len()
+        );
+
+        Ok(vault)
+    }
+
+    pub fn from_words(prefix: Vec<String>, suffix: Vec<String>) -> Result<Self, KeyVaultError> {
+        let (vault, _cleanup) = Self::from_words_with_cleanup(prefix, suffix)?;
+        Ok(vault)
+    }
 
-        if db_prefix.len() != db_suffix.len() {
+    /// Also hands back the cleanup task, so tests can watch it stop
+    pub(crate) fn from_words_with_cleanup(
+        prefix: Vec<String>,
+        suffix: Vec<String>,
+    ) -> Result<(Self, JoinHandle<()>), KeyVaultError> {
+        if prefix.len() != suffix.len() {
             return Err(KeyVaultError::IncompatibleLength);
         }
 
         let vault = Self {
-            word_count: db_prefix.len() as u8,
+            word_count: prefix.len() as u8,
             active_keys: Arc::new(DashMap::new()),
-            prefix_words: Arc::new(db_prefix),
-            suffix_words: Arc::new(db_suffix),
+            prefix_words: Arc::new(prefix),
+            suffix_words: Arc::new(suffix),
+            blocked: HashSet::new(),
+            cancel: CancellationToken::new(),
         };
 
-        vault.spawn_vault_cleanup(pool);
-        Ok(vault)
+        let cleanup = vault.spawn_vault_cleanup();
+        Ok((vault, cleanup))
+    }
+
+    pub fn with_blocked(mut self, blocked: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.blocked = blocked
+            .into_iter()
+            .map(|(prefix, suffix)| (prefix.to_lowercase(), suffix.to_lowercase()))
+            .collect();
+        self
+    }
+
+    pub fn is_blocked(&self, pair: &(String, String)) -> bool {
+        !self.blocked.is_empty()
+            && self
+                .blocked
+                .contains(&(pair.0.to_lowercase(), pair.1.to_lowercase()))
+    }
+
+    pub fn shutdown(&self) {
+        self.cancel.cancel();
+    }
+
+    #[cfg(test)]
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel.clone()
     }
 
     pub fn key_active(&self, key: &(String, String)) -> Option<(GameType, bool, Uuid)> {
@@ -68,6 +125,16 @@ impl KeyVault {
         }
     }
 
+    #[cfg(test)]
+    pub fn active_key_count(&self) -> usize {
+        self.active_keys.len()
+    }
+
+    /// True if the key is active and was handed out for `game_type`
+    pub fn holds_key(&self, key: &(String, String), game_type: GameType) -> bool {
+        matches!(self.key_active(key), Some((held_type, _, _)) if held_type == game_type)
+    }
+
     pub fn remove_key(&self, key: (String, String)) {
         self.active_keys.remove(&key);
     }
@@ -80,6 +147,8 @@ impl KeyVault {
         Ok((prefix_idx, suffix_idx))
     }
 
+    /// Reserves a join key for an interactive session. Standalone games are
+    /// played on a single device and never call this, so they cost no capacity.
     pub fn create_key(
         &self,
         _pool: &Pool<Postgres>,
@@ -97,54 +166,86 @@ impl KeyVault {
                 self.suffix_words[idx2].clone(),
             );
 
-            if self.active_keys.contains_key(&key) {
-                continue;
+            if let Some(key) = self.try_claim(key, game_type, is_draft, game_id)? {
+                return Ok(key);
             }
-
-            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
-            let value = VaultValue {
-                timestamp,
-                game_type,
-                is_draft,
-                game_id,
-            };
-
-            self.active_keys.insert(key.clone(), value);
-            return Ok(format!("{} {}", key.0, key.1));
         }
 
         for i in 0..self.prefix_words.len() {
             for j in 0..self.suffix_words.len() {
                 let key = (self.prefix_words[i].clone(), self.suffix_words[j].clone());
 
-                if self.active_keys.contains_key(&key) {
-                    continue;
+                if let Some(key) = self.try_claim(key, game_type, is_draft, game_id)? {
+                    return Ok(key);
                 }
+            }
+        }
+
+        // Failed to find available key after exhaustive search
+        Err(KeyVaultError::FullCapasity)
+    }
 
-                let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
-                let value = VaultValue {
+    /// Checks and inserts under the same shard lock, so two concurrent callers
+    /// can never both be handed the same key
+    fn try_claim(
+        &self,
+        key: (String, String),
+        game_type: GameType,
+        is_draft: bool,
+        game_id: Uuid,
+    ) -> Result<Option<String>, KeyVaultError> {
+        if self.is_blocked(&key) {
+            return Ok(None);
+        }
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        match self.active_keys.entry(key) {
+            Entry::Occupied(_) => Ok(None),
+            Entry::Vacant(entry) => {
+                let key = format!("{} {}", entry.key().0, entry.key().1);
+                entry.insert(VaultValue {
                     timestamp,
                     game_type,
                     is_draft,
                     game_id,
-                };
-
-                self.active_keys.insert(key.clone(), value);
-                return Ok(format!("{} {}", key.0, key.1));
+                });
+                Ok(Some(key))
             }
         }
+    }
 
-        // Failed to find available key after exhaustive search
-        Err(KeyVaultError::FullCapasity)
+    /// Number of distinct keys the vault can hand out at once
+    #[cfg(test)]
+    pub fn capacity(&self) -> usize {
+        let pairs = self.prefix_words.len() * self.suffix_words.len();
+        let blocked = self
+            .blocked
+            .iter()
+            .filter(|(prefix, suffix)| {
+                self.prefix_words
+                    .iter()
+                    .any(|w| w.to_lowercase() == *prefix)
+                    && self
+                        .suffix_words
+                        .iter()
+                        .any(|w| w.to_lowercase() == *suffix)
+            })
+            .count();
+
+        pairs - blocked
     }
 
-    fn spawn_vault_cleanup(&self, _pool: &Pool<Postgres>) {
+    fn spawn_vault_cleanup(&self) -> JoinHandle<()> {
         let mut interval = tokio::time::interval(Duration::from_secs(3600));
         let active_keys = self.active_keys.clone();
+        let cancel = self.cancel.clone();
 
         tokio::spawn(async move {
             loop {
-                interval.tick().await;
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = interval.tick() => {}
+                }
                 debug!("KeyVault is cleaning up its keys");
 
                 let Ok(time) = SystemTime::now().duration_since(UNIX_EPOCH) else {
@@ -170,3 +271,9 @@ impl KeyVault {
         });
     }
 }
+
+impl Drop for KeyVault {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use sqlx::{Pool, Postgres};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{error, warn};
+
+use crate::{db::system_log::create_system_logs, models::system_log::NewSystemLog};
+
+const CHANNEL_CAPACITY: usize = 1024;
+const BATCH_SIZE: usize = 50;
+const FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+
+enum LogCommand {
+    Write(NewSystemLog),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Collects system logs on a channel and writes them in batches,
+/// flushing when `BATCH_SIZE` is reached or every `FLUSH_INTERVAL`.
+#[derive(Clone)]
+pub struct LogBuffer {
+    sender: mpsc::Sender<LogCommand>,
+}
+
+impl LogBuffer {
+    pub fn spawn(pool: Pool<Postgres>) -> Self {
+        let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        tokio::spawn(run_writer(pool, receiver));
+
+        Self { sender }
+    }
+
+    /// Queues a log without waiting. Hands the log back if the buffer
+    /// is full or closed so the caller can write it directly.
+    pub fn enqueue(&self, log: NewSystemLog) -> Result<(), NewSystemLog> {
+        self.sender
+            .try_send(LogCommand::Write(log))
+            .map_err(|e| match e.into_inner() {
+                LogCommand::Write(log) => log,
+                LogCommand::Flush(_) => unreachable!("only writes are sent with try_send"),
+            })
+    }
+
+    /// Writes everything queued before this call
+    pub async fn flush(&self) {
+        let (done, flushed) = oneshot::channel();
+        if self.sender.send(LogCommand::Flush(done)).await.is_err() {
+            warn!("Log buffer writer has stopped, nothing to flush");
+            return;
+        }
+
+        let _ = flushed.await;
+    }
+}
+
+async fn run_writer(pool: Pool<Postgres>, mut receiver: mpsc::Receiver<LogCommand>) {
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+
+    loop {
+        tokio::select! {
+            command = receiver.recv() => match command {
+                Some(LogCommand::Write(log)) => {
+                    batch.push(log);
+                    if batch.len() >= BATCH_SIZE {
+                        write_batch(&pool, &mut batch).await;
+                    }
+                }
+                Some(LogCommand::Flush(done)) => {
+                    write_batch(&pool, &mut batch).await;
+                    let _ = done.send(());
+                }
+                None => {
+                    write_batch(&pool, &mut batch).await;
+                    return;
+                }
+            },
+            _ = interval.tick() => write_batch(&pool, &mut batch).await,
+        }
+    }
+}
+
+async fn write_batch(pool: &Pool<Postgres>, batch: &mut Vec<NewSystemLog>) {
+    if batch.is_empty() {
+        return;
+    }
+
+    if let Err(e) = create_system_logs(pool, batch).await {
+        error!(
+            "Failed to write {} buffered system log(s): {}",
+            batch.len(),
+            e
+        );
+    }
+
+    batch.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    use crate::models::system_log::{LogAction, LogCeverity, NewSystemLog, SubjectType};
+
+    use crate::tests::db::dev_pool;
+
+    use super::LogBuffer;
+
+    #[tokio::test]
+    async fn flush_writes_queued_logs() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let buffer = LogBuffer::spawn(pool.clone());
+        let subject_id = Uuid::new_v4().to_string();
+
+        for i in 0..3 {
+            let log = NewSystemLog {
+                subject_id: subject_id.clone(),
+                subject_type: SubjectType::System,
+                action: LogAction::Other,
+                ceverity: LogCeverity::Info,
+                function: "flush_writes_queued_logs".into(),
+                description: format!("buffered {}", i),
+                metadata: None,
+                created_at: Utc::now(),
+            };
+            buffer.enqueue(log).unwrap();
+        }
+
+        buffer.flush().await;
+
+        let count: i64 =
+            sqlx::query_scalar(r#"SELECT COUNT(*) FROM "system_log" WHERE subject_id = $1"#)
+                .bind(&subject_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(count, 3);
+
+        sqlx::query(r#"DELETE FROM "system_log" WHERE subject_id = $1"#)
+            .bind(&subject_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,19 @@
+use once_cell::sync::Lazy;
+use tokio::sync::broadcast;
+
+use crate::models::system_log::SystemLog;
+
+/// Entries buffered per subscriber before a slow client starts skipping logs
+static LOG_STREAM_CAPACITY: usize = 256;
+
+static LOG_STREAM: Lazy<broadcast::Sender<SystemLog>> =
+    Lazy::new(|| broadcast::channel(LOG_STREAM_CAPACITY).0);
+
+pub fn publish(log: SystemLog) {
+    // Sending only fails when nobody is subscribed
+    let _ = LOG_STREAM.send(log);
+}
+
+pub fn subscribe() -> broadcast::Receiver<SystemLog> {
+    LOG_STREAM.subscribe()
+}
@@ -1,5 +1,17 @@
+pub mod activity_batcher;
 pub mod cache;
+pub mod circuit_breaker;
+pub mod in_flight;
+pub mod integration_registry;
+pub mod jwks_store;
 pub mod key_vault;
+pub mod log_buffer;
+pub mod log_stream;
+pub mod player_count;
 pub mod popup_manager;
+pub mod rate_limiter;
+pub mod recently_seen;
+pub mod stats_cache;
 pub mod system_log_builder;
+pub mod task_tracker;
 pub mod util;
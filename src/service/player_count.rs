@@ -0,0 +1,139 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::http::StatusCode;
+use dashmap::DashMap;
+use uuid::Uuid;
+
+use crate::models::{error::ServerError, game_base::GameType};
+
+/// Same lifetime as a vault key, once the key has expired nobody can join anyway
+const PLAYER_COUNT_TTL: Duration = Duration::from_secs(3600);
+
+/// Player counts per interactive session, as reported by `tero.session`.
+/// Player lists only live in the session service, so this keeps the last
+/// reported count. Entries of sessions that are never freed expire after
+/// `PLAYER_COUNT_TTL` and are dropped by `purge_stale`.
+#[derive(Debug, Clone)]
+pub struct PlayerCounts {
+    counts: Arc<DashMap<Uuid, (u16, Instant)>>,
+    max_players: u16,
+    ttl: Duration,
+}
+
+impl PlayerCounts {
+    pub fn new(max_players: u16) -> Self {
+        Self::with_ttl(max_players, PLAYER_COUNT_TTL)
+    }
+
+    fn with_ttl(max_players: u16, ttl: Duration) -> Self {
+        Self {
+            counts: Arc::new(DashMap::new()),
+            max_players,
+            ttl,
+        }
+    }
+
+    pub fn set(&self, game_id: Uuid, count: u16) {
+        self.counts.insert(game_id, (count, Instant::now()));
+    }
+
+    pub fn remove(&self, game_id: Uuid) {
+        self.counts.remove(&game_id);
+    }
+
+    /// Sessions without a fresh reported count are treated as having room
+    pub fn is_full(&self, game_id: Uuid) -> bool {
+        self.counts.get(&game_id).is_some_and(|entry| {
+            let (count, reported_at) = *entry;
+            reported_at.elapsed() < self.ttl && count >= self.max_players
+        })
+    }
+
+    /// Drops counts of sessions that stopped reporting, returns how many were removed
+    pub fn purge_stale(&self) -> usize {
+        let before = self.counts.len();
+        self.counts
+            .retain(|_, (_, reported_at)| reported_at.elapsed() < self.ttl);
+        before - self.counts.len()
+    }
+}
+
+/// Rejects joins for disabled game types and sessions at capacity
+pub fn ensure_joinable(
+    enabled_types: &[GameType],
+    counts: &PlayerCounts,
+    game_type: GameType,
+    game_id: Uuid,
+) -> Result<(), ServerError> {
+    if !enabled_types.contains(&game_type) {
+        return Err(ServerError::Api(
+            StatusCode::FORBIDDEN,
+            format!("Game type {} is not enabled", game_type.as_str()),
+        ));
+    }
+
+    if counts.is_full(game_id) {
+        return Err(ServerError::Api(
+            StatusCode::CONFLICT,
+            "Game session is full".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::http::StatusCode;
+    use uuid::Uuid;
+
+    use crate::models::{error::ServerError, game_base::GameType};
+
+    use super::{PlayerCounts, ensure_joinable};
+
+    #[test]
+    fn disabled_game_type_is_forbidden() {
+        let counts = PlayerCounts::new(10);
+        let result = ensure_joinable(&[GameType::Quiz], &counts, GameType::Duel, Uuid::new_v4());
+
+        assert!(matches!(
+            result,
+            Err(ServerError::Api(StatusCode::FORBIDDEN, _))
+        ));
+    }
+
+    #[test]
+    fn full_session_is_a_conflict() {
+        let counts = PlayerCounts::new(2);
+        let game_id = Uuid::new_v4();
+        let enabled = [GameType::Quiz];
+
+        counts.set(game_id, 1);
+        assert!(ensure_joinable(&enabled, &counts, GameType::Quiz, game_id).is_ok());
+
+        counts.set(game_id, 2);
+        assert!(matches!(
+            ensure_joinable(&enabled, &counts, GameType::Quiz, game_id),
+            Err(ServerError::Api(StatusCode::CONFLICT, _))
+        ));
+
+        counts.remove(game_id);
+        assert!(ensure_joinable(&enabled, &counts, GameType::Quiz, game_id).is_ok());
+    }
+
+    #[test]
+    fn stale_counts_expire_and_are_purged() {
+        let counts = PlayerCounts::with_ttl(2, Duration::ZERO);
+        let game_id = Uuid::new_v4();
+
+        counts.set(game_id, 2);
+        assert!(!counts.is_full(game_id));
+        assert_eq!(counts.purge_stale(), 1);
+        assert_eq!(counts.purge_stale(), 0);
+    }
+}
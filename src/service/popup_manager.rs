@@ -1,39 +1,147 @@
 use std::sync::Arc;
 
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
 use tokio::sync::RwLock;
+use tracing::warn;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::{
+    db::popup::{get_client_popup, upsert_client_popup},
+    models::error::ServerError,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
 pub struct ClientPopup {
     pub heading: String,
     pub paragraph: String,
     pub active: bool,
+    /// Optional window the popup is shown in, open ended on a missing side
+    #[serde(default)]
+    pub start_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub end_at: Option<DateTime<Utc>>,
+}
+
+impl ClientPopup {
+    fn in_window(&self, now: DateTime<Utc>) -> bool {
+        self.start_at.is_none_or(|start| start <= now) && self.end_at.is_none_or(|end| now < end)
+    }
+}
+
+impl Default for ClientPopup {
+    fn default() -> Self {
+        Self {
+            heading: "Velkommen".to_string(),
+            paragraph: "Takk for at du har lastet ned appen vår!".to_string(),
+            active: false,
+            start_at: None,
+            end_at: None,
+        }
+    }
 }
 
+/// In-memory copy of the `client_popup` row, updates are written through to
+/// the database when the manager is backed by a pool
 #[derive(Debug, Clone)]
 pub struct PopupManager {
     popup: Arc<RwLock<ClientPopup>>,
+    pool: Option<Pool<Postgres>>,
 }
 
 impl PopupManager {
+    /// Unbacked manager holding the default popup, updates are lost on restart
+    #[cfg(test)]
     pub fn new() -> Self {
         Self {
-            popup: Arc::new(RwLock::new(ClientPopup {
-                heading: "Velkommen".to_string(),
-                paragraph: "Takk for at du har lastet ned appen vår!".to_string(),
-                active: false,
-            })),
+            popup: Arc::new(RwLock::new(ClientPopup::default())),
+            pool: None,
+        }
+    }
+
+    /// Falls back to the default popup if the stored one can not be read, so
+    /// a missing row never blocks startup
+    pub async fn load(pool: &Pool<Postgres>) -> Self {
+        let popup = match get_client_popup(pool).await {
+            Ok(Some(popup)) => popup,
+            Ok(None) => ClientPopup::default(),
+            Err(e) => {
+                warn!("Failed to load client popup, using default: {}", e);
+                ClientPopup::default()
+            }
+        };
+
+        Self {
+            popup: Arc::new(RwLock::new(popup)),
+            pool: Some(pool.clone()),
         }
     }
 
-    pub async fn update(&self, update: ClientPopup) -> ClientPopup {
+    pub async fn update(&self, update: ClientPopup) -> Result<ClientPopup, ServerError> {
+        let inverted = matches!(
+            (update.start_at, update.end_at),
+            (Some(start), Some(end)) if start > end
+        );
+        if inverted {
+            return Err(ServerError::Api(
+                StatusCode::BAD_REQUEST,
+                "Popup start_at must be before end_at".into(),
+            ));
+        }
+
         let mut lock = self.popup.write().await;
+        if let Some(pool) = &self.pool {
+            upsert_client_popup(pool, &update).await?;
+        }
+
         *lock = update.clone();
-        update
+        Ok(update)
     }
 
+    /// A scheduled popup reads as inactive outside of its window
     pub async fn read(&self) -> ClientPopup {
-        let lock = self.popup.read().await;
-        lock.clone()
+        self.read_at(Utc::now()).await
+    }
+
+    async fn read_at(&self, now: DateTime<Utc>) -> ClientPopup {
+        let mut popup = self.popup.read().await.clone();
+        popup.active = popup.active && popup.in_window(now);
+        popup
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::{ClientPopup, PopupManager};
+
+    fn scheduled(offset_start: i64, offset_end: i64) -> ClientPopup {
+        let now = Utc::now();
+        ClientPopup {
+            heading: "Vedlikehold".into(),
+            paragraph: "Appen er nede i kveld".into(),
+            active: true,
+            start_at: Some(now + Duration::hours(offset_start)),
+            end_at: Some(now + Duration::hours(offset_end)),
+        }
+    }
+
+    #[tokio::test]
+    async fn popup_is_only_active_inside_its_window() {
+        let manager = PopupManager::new();
+        manager.update(scheduled(1, 2)).await.unwrap();
+
+        let now = Utc::now();
+        assert!(!manager.read_at(now).await.active);
+        assert!(manager.read_at(now + Duration::minutes(90)).await.active);
+        assert!(!manager.read_at(now + Duration::hours(3)).await.active);
+    }
+
+    #[tokio::test]
+    async fn window_ending_before_it_starts_is_rejected() {
+        let manager = PopupManager::new();
+        assert!(manager.update(scheduled(2, 1)).await.is_err());
     }
 }
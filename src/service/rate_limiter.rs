@@ -0,0 +1,120 @@
+use std::{
+    hash::Hash,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Rejection from a limiter, carries enough bucket state for clients to back off
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimited {
+    pub limit: u32,
+    pub retry_after: Duration,
+}
+
+impl RateLimited {
+    /// Whole seconds for the `Retry-After` header, never zero
+    pub fn retry_after_secs(&self) -> u64 {
+        self.retry_after.as_secs_f64().ceil().max(1.0) as u64
+    }
+}
+
+/// Token bucket per key. Each key may burst up to `capacity` requests and
+/// regains one token every `refill_every`.
+#[derive(Debug, Clone)]
+pub struct RateLimiter<K: Hash + Eq> {
+    buckets: Arc<DashMap<K, Bucket>>,
+    capacity: f64,
+    refill_every: Duration,
+}
+
+impl<K: Hash + Eq> RateLimiter<K> {
+    pub fn new(capacity: u32, refill_every: Duration) -> Self {
+        Self {
+            buckets: Arc::new(DashMap::new()),
+            capacity: capacity as f64,
+            refill_every,
+        }
+    }
+
+    /// Takes a token for `key`, or reports how long until the bucket holds one again
+    pub fn try_acquire(&self, key: K) -> Result<(), RateLimited> {
+        self.try_acquire_at(key, Instant::now())
+    }
+
+    fn try_acquire_at(&self, key: K, now: Instant) -> Result<(), RateLimited> {
+        let mut bucket = self.buckets.entry(key).or_insert(Bucket {
+            tokens: self.capacity,
+            refilled_at: now,
+        });
+
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+        let refill = elapsed.as_secs_f64() / self.refill_every.as_secs_f64();
+        bucket.tokens = (bucket.tokens + refill).min(self.capacity);
+        bucket.refilled_at = now;
+
+        if bucket.tokens < 1.0 {
+            return Err(RateLimited {
+                limit: self.capacity as u32,
+                retry_after: self.refill_every.mul_f64(1.0 - bucket.tokens),
+            });
+        }
+
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{RateLimited, RateLimiter};
+
+    #[test]
+    fn bucket_empties_and_refills() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(1));
+        let start = Instant::now();
+
+        assert!(limiter.try_acquire_at("a", start).is_ok());
+        assert!(limiter.try_acquire_at("a", start).is_ok());
+        assert!(limiter.try_acquire_at("a", start).is_err());
+        assert!(limiter.try_acquire_at("b", start).is_ok());
+
+        assert!(
+            limiter
+                .try_acquire_at("a", start + Duration::from_secs(1))
+                .is_ok()
+        );
+        assert!(
+            limiter
+                .try_acquire_at("a", start + Duration::from_secs(1))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn rejection_reports_time_until_next_token() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(4));
+        let start = Instant::now();
+
+        assert!(limiter.try_acquire_at("a", start).is_ok());
+        let rejected = limiter.try_acquire_at("a", start + Duration::from_secs(1));
+
+        assert_eq!(
+            rejected,
+            Err(RateLimited {
+                limit: 1,
+                retry_after: Duration::from_secs(3),
+            })
+        );
+        assert_eq!(rejected.unwrap_err().retry_after_secs(), 3);
+    }
+}
@@ -0,0 +1,53 @@
+use std::{hash::Hash, time::Duration};
+
+use moka::future::Cache;
+
+/// Remembers keys for a short time so repeated work for the same key can be skipped
+#[derive(Clone)]
+pub struct RecentlySeen<K: Hash + Eq + Clone + Send + Sync + 'static> {
+    cache: Cache<K, ()>,
+}
+
+impl<K: Hash + Eq + Clone + Send + Sync + 'static> RecentlySeen<K> {
+    pub fn new(ttl: Duration, max_entries: u64) -> Self {
+        Self {
+            cache: Cache::builder()
+                .time_to_live(ttl)
+                .max_capacity(max_entries)
+                .build(),
+        }
+    }
+
+    /// True only for the first call per key within the TTL
+    pub async fn first_sighting(&self, key: K) -> bool {
+        self.cache.entry(key).or_insert(()).await.is_fresh()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use uuid::Uuid;
+
+    use super::RecentlySeen;
+
+    #[tokio::test]
+    async fn recently_seen_key_is_not_fresh() {
+        let seen = RecentlySeen::new(Duration::from_secs(60), 100);
+        let pseudo_id = Uuid::new_v4();
+
+        assert!(seen.first_sighting(pseudo_id).await);
+        assert!(!seen.first_sighting(pseudo_id).await);
+        assert!(seen.first_sighting(Uuid::new_v4()).await);
+    }
+
+    #[tokio::test]
+    async fn key_is_fresh_again_after_ttl() {
+        let seen = RecentlySeen::new(Duration::from_millis(50), 100);
+
+        assert!(seen.first_sighting(1).await);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(seen.first_sighting(1).await);
+    }
+}
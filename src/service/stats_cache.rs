@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use moka::future::Cache;
+use tracing::warn;
+
+use crate::models::error::ServerError;
+
+/// Holds a single computed value for a fixed time, unlike `GustCache` entries
+/// do not live on while they keep being read
+#[derive(Clone)]
+pub struct StatsCache<T: Clone + Send + Sync + 'static> {
+    cache: Cache<(), T>,
+}
+
+impl<T: Clone + Send + Sync + 'static> StatsCache<T> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            cache: Cache::builder().time_to_live(ttl).max_capacity(1).build(),
+        }
+    }
+
+    /// Returns the cached value or runs `on_failure` to load it, concurrent
+    /// misses share a single load
+    pub async fn get_or<F>(&self, on_failure: F) -> Result<T, ServerError>
+    where
+        F: Future<Output = Result<T, sqlx::Error>>,
+    {
+        self.cache
+            .try_get_with((), async { on_failure.await.map_err(ServerError::from) })
+            .await
+            .map_err(|e| {
+                warn!("Stats cache failed to get entry: {}", e);
+                ServerError::Internal(e.to_string())
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::sleep;
+
+    use super::StatsCache;
+
+    #[tokio::test]
+    async fn entry_expires_after_configured_ttl() {
+        let cache: StatsCache<u32> = StatsCache::new(Duration::from_secs(1));
+
+        assert_eq!(cache.get_or(async { Ok(1) }).await.unwrap(), 1);
+        assert_eq!(cache.get_or(async { Ok(2) }).await.unwrap(), 1);
+
+        sleep(Duration::from_millis(1500)).await;
+        assert_eq!(cache.get_or(async { Ok(3) }).await.unwrap(), 3);
+    }
+}
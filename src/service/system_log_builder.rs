@@ -1,3 +1,4 @@
+use chrono::Utc;
 use sqlx::{Pool, Postgres};
 
 use tracing::warn;
@@ -6,9 +7,10 @@ use crate::{
     db::system_log::create_system_log,
     models::{
         error::ServerError,
-        system_log::{LogAction, LogCeverity, SubjectType},
+        system_log::{LogAction, LogCeverity, LogMetadata, NewSystemLog, SubjectType},
         user::SubjectId,
     },
+    service::log_buffer::LogBuffer,
 };
 
 pub struct SystemLogBuilder {
@@ -20,6 +22,7 @@ pub struct SystemLogBuilder {
     pub function: Option<String>,
     pub description: Option<String>,
     pub metadata: Option<serde_json::Value>,
+    pub buffer: Option<LogBuffer>,
 }
 
 impl SystemLogBuilder {
@@ -33,9 +36,16 @@ impl SystemLogBuilder {
             function: None,
             description: None,
             metadata: None,
+            buffer: None,
         }
     }
 
+    /// Makes `log_async` queue on the buffer instead of inserting right away
+    pub fn buffered(mut self, buffer: &LogBuffer) -> Self {
+        self.buffer = Some(buffer.clone());
+        self
+    }
+
     pub fn subject(mut self, subject: SubjectId) -> Self {
         let (id, _type) = match subject {
             SubjectId::PseudoUser(id) => (id.to_string(), SubjectType::GuestUser),
@@ -67,12 +77,13 @@ impl SystemLogBuilder {
         self
     }
 
-    pub fn metadata(mut self, metadata: serde_json::Value) -> Self {
-        self.metadata = Some(metadata);
+    /// Accepts a typed `LogMetadata` or raw json
+    pub fn metadata(mut self, metadata: impl Into<LogMetadata>) -> Self {
+        self.metadata = Some(metadata.into().into());
         self
     }
 
-    pub async fn log(self) -> Result<(), ServerError> {
+    fn into_new_log(self) -> (Pool<Postgres>, Option<LogBuffer>, NewSystemLog) {
         let (subject_id, subject_type) = match (self.subject_id, self.subject_type) {
             (Some(id), Some(_type)) => (id, _type),
             _ => ("[SYSTEM]".to_string(), SubjectType::System),
@@ -87,29 +98,82 @@ impl SystemLogBuilder {
             description = format!("{}...", &description[..509]);
         }
 
-        let action = self.action.unwrap_or(LogAction::Other);
-        let ceverity = self.ceverity.unwrap_or(LogCeverity::Info);
-        let function = self.function.unwrap_or("Not specified".into());
-
-        create_system_log(
-            &self.pool,
-            &subject_id,
-            &subject_type,
-            &action,
-            &ceverity,
-            &function,
-            &description,
-            &self.metadata,
-        )
-        .await?;
-        Ok(())
+        let log = NewSystemLog {
+            subject_id,
+            subject_type,
+            action: self.action.unwrap_or(LogAction::Other),
+            ceverity: self.ceverity.unwrap_or(LogCeverity::Info),
+            function: self.function.unwrap_or("Not specified".into()),
+            description,
+            metadata: self.metadata,
+            created_at: Utc::now(),
+        };
+
+        (self.pool, self.buffer, log)
+    }
+
+    pub async fn log(self) -> Result<(), ServerError> {
+        let (pool, _, log) = self.into_new_log();
+        create_system_log(&pool, &log).await
     }
 
     pub fn log_async(self) {
+        let (pool, buffer, log) = self.into_new_log();
+
+        // Falls back to a direct insert when unbuffered or the buffer is full
+        let log = match buffer {
+            Some(buffer) => match buffer.enqueue(log) {
+                Ok(()) => return,
+                Err(log) => log,
+            },
+            None => log,
+        };
+
         tokio::spawn(async move {
-            if let Err(e) = self.log().await {
+            if let Err(e) = create_system_log(&pool, &log).await {
                 warn!("Failed to create system log entry: {}", e);
             }
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::models::user::SubjectId;
+
+    use crate::tests::db::dev_pool;
+
+    use super::SystemLogBuilder;
+
+    #[tokio::test]
+    async fn function_is_stored_in_function_column() {
+        let Some(pool) = dev_pool().await else {
+            return;
+        };
+
+        let user_id = Uuid::new_v4();
+
+        SystemLogBuilder::new(&pool)
+            .subject(SubjectId::BaseUser(user_id))
+            .function("function_is_stored")
+            .log()
+            .await
+            .unwrap();
+
+        let function: String =
+            sqlx::query_scalar(r#"SELECT function FROM "system_log" WHERE subject_id = $1"#)
+                .bind(user_id.to_string())
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(function, "function_is_stored");
+
+        sqlx::query(r#"DELETE FROM "system_log" WHERE subject_id = $1"#)
+            .bind(user_id.to_string())
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,185 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use serde::Serialize;
+use tokio::{sync::Notify, task::JoinHandle};
+
+#[derive(Debug, Serialize)]
+pub struct TaskMetrics {
+    pub running: usize,
+    pub rejected: u64,
+    pub limit: usize,
+}
+
+/// Spawns background work that must finish before the process exits,
+/// like persists and log writes. Periodic cron jobs do not belong here.
+#[derive(Debug, Clone)]
+pub struct TaskTracker {
+    inner: Arc<TrackerInner>,
+    limit: usize,
+}
+
+#[derive(Debug, Default)]
+struct TrackerInner {
+    running: AtomicUsize,
+    rejected: AtomicU64,
+    idle: Notify,
+}
+
+impl TaskTracker {
+    #[cfg(test)]
+    pub fn new() -> Self {
+        Self::with_limit(usize::MAX)
+    }
+
+    /// `try_spawn` turns work away once `limit` tasks are running
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            inner: Arc::new(TrackerInner::default()),
+            limit,
+        }
+    }
+
+    /// Spawns unless the tracker is at its limit, in which case the task is
+    /// dropped and counted as rejected. Use for work that is safe to skip.
+    pub fn try_spawn<F>(&self, task: F) -> Option<JoinHandle<F::Output>>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let claimed =
+            self.inner
+                .running
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |running| {
+                    (running < self.limit).then_some(running + 1)
+                });
+
+        if claimed.is_err() {
+            self.inner.rejected.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+
+        Some(self.spawn_claimed(task))
+    }
+
+    pub fn metrics(&self) -> TaskMetrics {
+        TaskMetrics {
+            running: self.running(),
+            rejected: self.inner.rejected.load(Ordering::Relaxed),
+            limit: self.limit,
+        }
+    }
+
+    pub fn spawn<F>(&self, task: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.inner.running.fetch_add(1, Ordering::SeqCst);
+        self.spawn_claimed(task)
+    }
+
+    fn spawn_claimed<F>(&self, task: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let guard = TaskGuard(self.inner.clone());
+
+        tokio::spawn(async move {
+            let _guard = guard;
+            task.await
+        })
+    }
+
+    pub fn running(&self) -> usize {
+        self.inner.running.load(Ordering::SeqCst)
+    }
+
+    /// Waits for every tracked task to finish, giving up after `timeout`.
+    /// Returns whether all tasks completed in time.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        let wait_idle = async {
+            loop {
+                // Register before checking so a task finishing in between is not missed
+                let notified = self.inner.idle.notified();
+                if self.running() == 0 {
+                    return;
+                }
+                notified.await;
+            }
+        };
+
+        tokio::time::timeout(timeout, wait_idle).await.is_ok()
+    }
+}
+
+/// Decrements the running count even if the task panics
+struct TaskGuard(Arc<TrackerInner>);
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        if self.0.running.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+        time::Duration,
+    };
+
+    use super::TaskTracker;
+
+    #[tokio::test]
+    async fn drain_waits_for_critical_task() {
+        let tracker = TaskTracker::new();
+        let persisted = Arc::new(AtomicBool::new(false));
+
+        let flag = persisted.clone();
+        tracker.spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            flag.store(true, Ordering::SeqCst);
+        });
+
+        assert!(tracker.drain(Duration::from_secs(2)).await);
+        assert!(persisted.load(Ordering::SeqCst));
+        assert_eq!(tracker.running(), 0);
+    }
+
+    #[tokio::test]
+    async fn try_spawn_rejects_over_limit() {
+        let tracker = TaskTracker::with_limit(1);
+
+        assert!(
+            tracker
+                .try_spawn(tokio::time::sleep(Duration::from_secs(10)))
+                .is_some()
+        );
+        assert!(tracker.try_spawn(async {}).is_none());
+
+        let metrics = tracker.metrics();
+        assert_eq!(metrics.running, 1);
+        assert_eq!(metrics.rejected, 1);
+    }
+
+    #[tokio::test]
+    async fn drain_gives_up_after_timeout() {
+        let tracker = TaskTracker::new();
+        tracker.spawn(tokio::time::sleep(Duration::from_secs(10)));
+
+        assert!(!tracker.drain(Duration::from_millis(50)).await);
+        assert_eq!(tracker.running(), 1);
+    }
+}
@@ -1,17 +1,52 @@
+use std::net::{IpAddr, SocketAddr};
+
 use axum::http::HeaderMap;
 use reqwest::StatusCode;
+use tracing::warn;
 use uuid::Uuid;
 
 use crate::models::error::ServerError;
 
-pub fn to_uuid(value: &str) -> Result<Uuid, ServerError> {
-    let Ok(guest_id) = value.parse() else {
+/// Longest accepted guest header, a hyphenated uuid
+const MAX_GUEST_ID_LEN: usize = 36;
+
+/// Checks length and charset before parsing, so oversized or garbage headers
+/// are rejected without being echoed into logs
+pub fn parse_guest_id(value: &str) -> Result<Uuid, ServerError> {
+    let value = value.trim();
+    if value.is_empty() {
         return Err(ServerError::Api(
-            StatusCode::UNAUTHORIZED,
-            "Guest id is invalid".into(),
+            StatusCode::BAD_REQUEST,
+            "Guest id is missing".into(),
         ));
-    };
-    Ok(guest_id)
+    }
+
+    let well_formed =
+        value.len() <= MAX_GUEST_ID_LEN && value.chars().all(|c| c.is_ascii_hexdigit() || c == '-');
+
+    match well_formed.then(|| value.parse::<Uuid>().ok()).flatten() {
+        Some(guest_id) => Ok(guest_id),
+        None => {
+            warn!("Rejected malformed guest id of length {}", value.len());
+            Err(ServerError::Api(
+                StatusCode::BAD_REQUEST,
+                "Guest id is malformed".into(),
+            ))
+        }
+    }
+}
+
+/// Splits a game key into its `(prefix, suffix)` words, ignoring surrounding
+/// and repeated whitespace. Anything but exactly two words is rejected.
+pub fn parse_game_key(value: &str) -> Result<(String, String), ServerError> {
+    let mut words = value.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some(prefix), Some(suffix), None) => Ok((prefix.to_string(), suffix.to_string())),
+        _ => Err(ServerError::Api(
+            StatusCode::BAD_REQUEST,
+            "Key word in invalid format".into(),
+        )),
+    }
 }
 
 pub fn extract_header(key: &str, header_map: &HeaderMap) -> Option<String> {
@@ -20,3 +55,119 @@ pub fn extract_header(key: &str, header_map: &HeaderMap) -> Option<String> {
         .and_then(|header| header.to_str().ok())
         .map(|s| s.to_owned())
 }
+
+/// The rightmost `X-Forwarded-For` hop that is not a trusted proxy when the peer
+/// is one, otherwise the peer address. Hops left of it are client supplied.
+pub fn client_ip(headers: &HeaderMap, peer: SocketAddr, trusted_proxies: &[IpAddr]) -> IpAddr {
+    if !trusted_proxies.contains(&peer.ip()) {
+        return peer.ip();
+    }
+
+    let Some(forwarded) = extract_header("x-forwarded-for", headers) else {
+        return peer.ip();
+    };
+
+    for hop in forwarded.rsplit(',') {
+        match hop.trim().parse::<IpAddr>() {
+            Ok(ip) if trusted_proxies.contains(&ip) => continue,
+            Ok(ip) => return ip,
+            Err(_) => break,
+        }
+    }
+
+    peer.ip()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, SocketAddr};
+
+    use axum::http::HeaderMap;
+
+    use super::{client_ip, parse_game_key, parse_guest_id};
+
+    #[test]
+    fn game_key_ignores_surrounding_whitespace() {
+        assert_eq!(
+            parse_game_key("  happy  otter ").unwrap(),
+            ("happy".to_string(), "otter".to_string())
+        );
+    }
+
+    #[test]
+    fn game_key_needs_exactly_two_words() {
+        assert!(parse_game_key("happy").is_err());
+        assert!(parse_game_key("happy brave otter").is_err());
+        assert!(parse_game_key("   ").is_err());
+    }
+
+    #[test]
+    fn client_ip_takes_rightmost_untrusted_hop_behind_a_trusted_proxy() {
+        let peer: SocketAddr = "10.0.0.1:443".parse().unwrap();
+        let trusted: Vec<IpAddr> = vec!["10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap()];
+        let mut headers = HeaderMap::new();
+        assert_eq!(client_ip(&headers, peer, &trusted), peer.ip());
+
+        headers.insert(
+            "x-forwarded-for",
+            "203.0.113.7, 198.51.100.4, 10.0.0.2".parse().unwrap(),
+        );
+        assert_eq!(
+            client_ip(&headers, peer, &trusted),
+            "198.51.100.4".parse::<IpAddr>().unwrap()
+        );
+
+        headers.insert("x-forwarded-for", "garbage".parse().unwrap());
+        assert_eq!(client_ip(&headers, peer, &trusted), peer.ip());
+    }
+
+    #[test]
+    fn client_ip_ignores_spoofed_forwarded_header() {
+        let peer: SocketAddr = "198.51.100.4:5123".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "203.0.113.7".parse().unwrap());
+
+        // Direct client that is not a proxy
+        let trusted: Vec<IpAddr> = vec!["10.0.0.1".parse().unwrap()];
+        assert_eq!(client_ip(&headers, peer, &trusted), peer.ip());
+
+        // No proxy configured
+        assert_eq!(client_ip(&headers, peer, &[]), peer.ip());
+
+        // Forged hop prepended to what the trusted proxy appended
+        let proxy: SocketAddr = "10.0.0.1:443".parse().unwrap();
+        headers.insert(
+            "x-forwarded-for",
+            "203.0.113.7, 198.51.100.4".parse().unwrap(),
+        );
+        assert_eq!(
+            client_ip(&headers, proxy, &trusted),
+            "198.51.100.4".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_guest_id_is_rejected() {
+        assert!(parse_guest_id("").is_err());
+        assert!(parse_guest_id("   ").is_err());
+    }
+
+    #[test]
+    fn malformed_guest_id_is_rejected() {
+        assert!(parse_guest_id("not-a-uuid").is_err());
+        assert!(parse_guest_id(&"a".repeat(4096)).is_err());
+        assert!(parse_guest_id("{67e55044-10b1-426f-9247-bb680e5fe0c8}").is_err());
+    }
+
+    #[test]
+    fn unrendered_auth0_template_is_rejected() {
+        assert!(parse_guest_id("{{event.user.app_metadata.pseudo_id}}").is_err());
+        assert!(parse_guest_id("undefined").is_err());
+    }
+
+    #[test]
+    fn valid_guest_id_is_parsed() {
+        let id = parse_guest_id("67e55044-10b1-426f-9247-bb680e5fe0c8").unwrap();
+        assert_eq!(id.to_string(), "67e55044-10b1-426f-9247-bb680e5fe0c8");
+    }
+}
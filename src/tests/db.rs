@@ -0,0 +1,18 @@
+#![cfg(test)]
+
+use std::env;
+
+use dotenvy::dotenv;
+use sqlx::{Pool, Postgres, postgres::PgPoolOptions};
+
+/// Pool on the dev database. Outside `ENVIRONMENT=dev` there is none and the
+/// calling test returns early, e.g. `let Some(pool) = dev_pool().await else { return; };`
+pub async fn dev_pool() -> Option<Pool<Postgres>> {
+    if env::var("ENVIRONMENT").unwrap_or_default() != "dev" {
+        return None;
+    }
+
+    dotenv().ok();
+    let url = env::var("TERO__DATABASE_URL").expect("TERO__DATABASE_URL not set");
+    Some(PgPoolOptions::new().connect(&url).await.unwrap())
+}
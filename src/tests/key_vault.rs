@@ -1,30 +1,67 @@
 #[cfg(test)]
 mod tests {
-    use std::{env, sync::Arc};
+    use std::{sync::Arc, time::Duration};
 
-    use dotenvy::dotenv;
     use uuid::Uuid;
 
     use crate::{
-        app_state::AppState, models::game_base::GameType, service::key_vault::KeyVaultError,
+        app_state::AppState,
+        models::game_base::GameType,
+        service::{
+            key_vault::{KeyVault, KeyVaultError},
+            util::parse_game_key,
+        },
+        tests::db::dev_pool,
     };
 
-    async fn setup_app_state() -> Arc<AppState> {
-        dotenv().ok();
-        let connection_string =
-            env::var("TERO__DATABASE_URL").expect("Failed to obtain connection string");
-        AppState::from_connection_string(&connection_string)
-            .await
+    #[tokio::test]
+    async fn blocked_combo_is_never_handed_out() {
+        let words = |a: &str, b: &str| vec![String::from(a), String::from(b)];
+        let vault = KeyVault::from_words(words("arg", "sur"), words("bil", "katt"))
             .unwrap()
+            .with_blocked([(String::from("Sur"), String::from("katt"))]);
+
+        // create_key never touches the pool, a lazy one is never connected
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+
+        let mut keys = Vec::new();
+        while let Ok(key) = vault.create_key(&pool, GameType::Quiz, false, Uuid::new_v4()) {
+            keys.push(key);
+        }
+
+        assert_eq!(keys.len(), 3);
+        assert!(!keys.contains(&String::from("sur katt")));
+    }
+
+    #[tokio::test]
+    async fn key_is_only_held_for_its_game_type() {
+        let words = |a: &str, b: &str| vec![String::from(a), String::from(b)];
+        let vault = KeyVault::from_words(words("arg", "sur"), words("bil", "katt")).unwrap();
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+
+        let key = vault
+            .create_key(&pool, GameType::Roulette, false, Uuid::new_v4())
+            .unwrap();
+        let key = parse_game_key(&key).unwrap();
+
+        assert!(vault.holds_key(&key, GameType::Roulette));
+        assert!(!vault.holds_key(&key, GameType::Duel));
+
+        vault.remove_key(key.clone());
+        assert!(!vault.holds_key(&key, GameType::Roulette));
     }
 
     #[allow(clippy::assertions_on_constants)]
     #[tokio::test]
     async fn max_limit_keys() {
-        if env::var("ENVIRONMENT").unwrap_or_default() != "dev" {
+        let Some(pool) = dev_pool().await else {
             return;
-        }
-        let state = setup_app_state().await;
+        };
+        let state = AppState::from_pool(pool).await.unwrap();
         let vault = state.get_vault();
 
         for num in 0..10_000 {
@@ -45,73 +82,68 @@ mod tests {
         }
     }
 
-    #[tokio::test]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
     async fn test_concurrent_key_creation() {
-        if env::var("ENVIRONMENT").unwrap_or_default() != "dev" {
-            return;
-        }
-        let state = setup_app_state().await;
-
-        let mut handles = Vec::new();
-
-        for i in 0..10001 {
-            let state_clone = Arc::clone(&state);
-
-            let handle = tokio::spawn(async move {
-                let vault = state_clone.get_vault();
-                match vault.create_key(
-                    state_clone.get_pool(),
-                    GameType::Quiz,
-                    false,
-                    Uuid::new_v4(),
-                ) {
-                    Ok(key) => {
-                        //println!("Task {} opprettet nøkkel: {}", i, key);
-                        Ok(key)
-                    }
-                    Err(e) => {
-                        println!("Task {} feilet: {:?}", i, e);
-                        Err(e)
-                    }
-                }
-            });
-
-            handles.push(handle);
-        }
-
-        // Await alle tasks samtidig
-        let results = futures::future::join_all(handles).await;
-
-        // Analyser resultater
-        let mut successful_keys: Vec<String> = Vec::new();
-        let mut failed_count = 0;
-
-        for result in results {
+        let words = |prefix: &str| (0..12).map(|i| format!("{}{}", prefix, i)).collect();
+        let vault = Arc::new(
+            KeyVault::from_words(words("arg"), words("bil"))
+                .unwrap()
+                .with_blocked([(String::from("arg0"), String::from("bil0"))]),
+        );
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+
+        let capacity = vault.capacity();
+        assert_eq!(capacity, 143);
+        let total_requests = capacity + 57;
+
+        let handles: Vec<_> = (0..total_requests)
+            .map(|_| {
+                let vault = Arc::clone(&vault);
+                let pool = pool.clone();
+                tokio::spawn(async move {
+                    vault.create_key(&pool, GameType::Quiz, false, Uuid::new_v4())
+                })
+            })
+            .collect();
+
+        let mut successful_keys = Vec::new();
+        let mut full_count = 0;
+        for result in futures::future::join_all(handles).await {
             match result.unwrap() {
-                // unwrap the JoinResult
                 Ok(key) => successful_keys.push(key),
-                Err(_) => failed_count += 1,
+                Err(KeyVaultError::FullCapasity) => full_count += 1,
+                Err(e) => panic!("Unexpected key vault error: {}", e),
             }
         }
 
-        println!("Vellykkede nøkler: {}", successful_keys.len());
-        println!("Feilede forsøk: {}", failed_count);
-
-        // Sjekk at alle nøkler er unike
-        let mut unique_keys = std::collections::HashSet::new();
-        for key in &successful_keys {
-            assert!(
-                unique_keys.insert(key.clone()),
-                "Duplikat nøkkel funnet: {}",
-                key
-            );
-        }
-
-        assert!(!successful_keys.is_empty(), "Ingen nøkler ble opprettet");
+        let unique_keys: std::collections::HashSet<&String> = successful_keys.iter().collect();
         assert_eq!(
-            successful_keys.len(),
             unique_keys.len(),
-            "Duplikate nøkler oppdaget"
+            successful_keys.len(),
+            "Duplicate keys handed out"
         );
+        assert_eq!(successful_keys.len(), total_requests.min(capacity));
+        assert_eq!(full_count, total_requests - successful_keys.len());
+        assert!(!successful_keys.contains(&String::from("arg0 bil0")));
+    }
+
+    #[tokio::test]
+    async fn dropped_vault_stops_its_cleanup_task() {
+        let words = |a: &str, b: &str| vec![String::from(a), String::from(b)];
+        let (vault, cleanup) =
+            KeyVault::from_words_with_cleanup(words("arg", "sur"), words("bil", "katt")).unwrap();
+
+        let cancel = vault.cancel_token();
+        assert!(!cancel.is_cancelled());
+
+        drop(vault);
+        assert!(cancel.is_cancelled());
+
+        tokio::time::timeout(Duration::from_secs(1), cleanup)
+            .await
+            .expect("cleanup task kept running after the vault was dropped")
+            .unwrap();
     }
 }
@@ -1 +1,2 @@
+pub mod db;
 pub mod key_vault;